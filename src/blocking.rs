@@ -0,0 +1,32 @@
+//! A synchronous api over the [`Scanner`], for programs without an async runtime of their own.
+
+use crate::{
+    error::ScanError,
+    scanner::{ScanConfig, Scanner},
+    types::GatewayDetection,
+};
+
+/// Scans the targets of `config` on a runtime of its own, returning the gateways found.
+///
+/// Must not be called from within an async runtime.
+pub fn scan(config: ScanConfig) -> Result<Vec<GatewayDetection>, ScanError> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(ScanError::Runtime)?;
+    runtime.block_on(async { Ok(Scanner::new(config)?.scan().await) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_config_fails_without_scanning() {
+        let config = ScanConfig {
+            concurrency: 0,
+            ..ScanConfig::default()
+        };
+        assert!(matches!(scan(config), Err(ScanError::InvalidConfig(_))));
+    }
+}
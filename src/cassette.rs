@@ -0,0 +1,249 @@
+//! Recorded http interactions with gateways, for reproducing field issues offline.
+//!
+//! A cassette is an NDJSON file with one [`Interaction`] per line. Credentials are never
+//! recorded.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    error::{ErrorCode, TransportError},
+    transport::{HttpRequest, HttpResponse, Transport},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Interaction {
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<Value>,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum Outcome {
+    Response { status: u16, body: String },
+    Error { code: ErrorCode, message: String },
+}
+
+/// Method, url and request body, which identify the requests a recorded outcome answers.
+type RequestKey = (String, String, Option<String>);
+
+impl Interaction {
+    fn key(&self) -> RequestKey {
+        (
+            self.method.clone(),
+            self.url.clone(),
+            self.request.as_ref().map(Value::to_string),
+        )
+    }
+}
+
+/// Collects interactions while running against real gateways.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    interactions: Mutex<Vec<Interaction>>,
+}
+
+impl Recorder {
+    pub fn record(&self, interaction: Interaction) {
+        self.interactions
+            .lock()
+            .expect("Recorder lock must not be poisoned")
+            .push(interaction);
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("Error creating cassette {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        for interaction in self
+            .interactions
+            .lock()
+            .expect("Recorder lock must not be poisoned")
+            .iter()
+        {
+            serde_json::to_writer(&mut writer, interaction).context("Error writing cassette")?;
+            writeln!(writer).context("Error writing cassette")?;
+        }
+        writer.flush().context("Error writing cassette")
+    }
+}
+
+/// Answers requests from recorded interactions, loaded from a file or built in memory with
+/// [`Cassette::from_interactions`] to program responses.
+///
+/// Identical requests get their recorded outcomes in order, and the last one is repeated once
+/// the others are used up so retries keep working.
+#[derive(Debug)]
+pub struct Cassette {
+    outcomes: Mutex<HashMap<RequestKey, VecDeque<Outcome>>>,
+    hosts: Vec<(String, u16)>,
+}
+
+impl Cassette {
+    pub fn from_interactions(interactions: impl IntoIterator<Item = Interaction>) -> Self {
+        let mut outcomes: HashMap<_, VecDeque<Outcome>> = HashMap::new();
+        let mut hosts = Vec::new();
+        for interaction in interactions {
+            if let Ok(url) = url::Url::parse(&interaction.url) {
+                if let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) {
+                    hosts.push((host.to_string(), port));
+                }
+            }
+            outcomes
+                .entry(interaction.key())
+                .or_default()
+                .push_back(interaction.outcome);
+        }
+        Self {
+            outcomes: Mutex::new(outcomes),
+            hosts,
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Error opening cassette {}", path.display()))?;
+        let mut interactions = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.context("Error reading cassette")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            interactions.push(
+                serde_json::from_str(&line)
+                    .with_context(|| format!("Invalid interaction on line {}", i + 1))?,
+            );
+        }
+        Ok(Self::from_interactions(interactions))
+    }
+
+    /// Whether anything was recorded for `host:port`.
+    pub fn has_host(&self, host: &str, port: u16) -> bool {
+        self.hosts.iter().any(|(h, p)| h == host && *p == port)
+    }
+
+    pub fn replay(&self, method: &str, url: &str, request: Option<&Value>) -> Option<Outcome> {
+        let mut outcomes = self
+            .outcomes
+            .lock()
+            .expect("Cassette lock must not be poisoned");
+        let queue = outcomes.get_mut(&(
+            method.to_string(),
+            url.to_string(),
+            request.map(Value::to_string),
+        ))?;
+        if queue.len() > 1 {
+            queue.pop_front()
+        } else {
+            queue.front().cloned()
+        }
+    }
+}
+
+/// Hosts connect when anything was recorded for them, requests get their recorded outcome and
+/// everything else fails to connect.
+impl Transport for Cassette {
+    fn connect<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+        _timeout: Duration,
+    ) -> BoxFuture<'a, Result<(), TransportError>> {
+        let result = if self.has_host(host, port) {
+            Ok(())
+        } else {
+            Err(TransportError::Connect {
+                message: "Host not in cassette".to_string(),
+            })
+        };
+        futures::future::ready(result).boxed()
+    }
+
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+        let result = match self.replay(
+            request.method.as_str(),
+            request.url.as_str(),
+            request.json.as_ref(),
+        ) {
+            Some(Outcome::Response { status, body }) => StatusCode::from_u16(status)
+                .map(|status| HttpResponse { status, body })
+                .map_err(|_| TransportError::Request {
+                    message: format!("Invalid status {} in cassette", status),
+                }),
+            Some(Outcome::Error { code, message }) => Err(TransportError::recorded(code, message)),
+            None => Err(TransportError::Connect {
+                message: format!(
+                    "No recorded interaction for {} {}",
+                    request.method, request.url
+                ),
+            }),
+        };
+        futures::future::ready(result).boxed()
+    }
+}
+
+/// Passes requests to another transport and records every outcome.
+pub struct RecordingTransport {
+    inner: Box<dyn Transport>,
+    recorder: Arc<Recorder>,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Box<dyn Transport>, recorder: Arc<Recorder>) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn connect<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+        timeout: Duration,
+    ) -> BoxFuture<'a, Result<(), TransportError>> {
+        self.inner.connect(host, port, timeout)
+    }
+
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+        async move {
+            let method = request.method.to_string();
+            let url = request.url.to_string();
+            let json = request.json.clone();
+            let response = self.inner.send(request).await;
+            self.recorder.record(Interaction {
+                method,
+                url,
+                request: json,
+                outcome: match &response {
+                    Ok(response) => Outcome::Response {
+                        status: response.status.as_u16(),
+                        body: response.body.clone(),
+                    },
+                    Err(err) => Outcome::Error {
+                        code: err.code(),
+                        message: err.to_string(),
+                    },
+                },
+            });
+            response
+        }
+        .boxed()
+    }
+}
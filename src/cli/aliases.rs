@@ -0,0 +1,66 @@
+use std::{ffi::OsString, path::PathBuf};
+
+use rtls_ctl::config::Config;
+
+/// Global options that take a value, so the value isn't mistaken for the command.
+const VALUE_OPTIONS: &[&str] = &["--config", "--profile"];
+
+/// Replaces a command that names an `[aliases]` entry with the arguments it stands for.
+///
+/// Built in commands always win over aliases of the same name. The config file is located the
+/// same way as for the rest of the cli, but a file that can't be read is left for the normal
+/// load to report.
+pub fn expand(args: Vec<OsString>, commands: &[&str]) -> Vec<OsString> {
+    let Some(position) = command_position(&args) else {
+        return args;
+    };
+    let Some(name) = args[position].to_str() else {
+        return args;
+    };
+    if commands.contains(&name) {
+        return args;
+    }
+    let Some(alias) = config_path(&args)
+        .and_then(|path| Config::load(path).ok())
+        .and_then(|config| config.aliases.get(name).map(|alias| alias.args()))
+    else {
+        return args;
+    };
+
+    let mut expanded = args[..position].to_vec();
+    expanded.extend(alias.into_iter().map(OsString::from));
+    expanded.extend_from_slice(&args[position + 1..]);
+    expanded
+}
+
+fn command_position(args: &[OsString]) -> Option<usize> {
+    let mut i = 1;
+    while i < args.len() {
+        let arg = args[i].to_string_lossy();
+        if arg == "--" {
+            return None;
+        } else if VALUE_OPTIONS.contains(&arg.as_ref()) {
+            i += 2;
+        } else if arg.starts_with('-') {
+            i += 1;
+        } else {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn config_path(args: &[OsString]) -> Option<PathBuf> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == "--config" {
+            return args.next().map(PathBuf::from);
+        } else if let Some(path) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    std::env::var_os("RTLS_CTL_CONFIG")
+        .map(PathBuf::from)
+        .or_else(Config::default_path)
+}
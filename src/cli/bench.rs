@@ -0,0 +1,181 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use clap::Args;
+use futures::StreamExt;
+use rtls_ctl::{
+    duration::parse_duration,
+    error::DetectError,
+    scanner::ScanConfig,
+    simulator::{self, SimulatedCredentials},
+    target::{IpRange, Subnet},
+};
+use serde::Serialize;
+
+use super::{
+    global::GlobalOptions, http::Http, porcelain::print_record, scan::ProbeContext,
+    settings::LoadedConfig,
+};
+
+const USERNAME: &str = "admin";
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Scan simulated gateways on local addresses (currently required)
+    #[arg(long)]
+    simulated: bool,
+    /// Number of addresses scanned in every run
+    #[arg(long, default_value_t = 4096)]
+    hosts: u32,
+    /// Number of simulated gateways, spread evenly over the addresses
+    #[arg(long, default_value_t = 64)]
+    gateways: u32,
+    /// Concurrency levels to run, comma separated
+    #[arg(long, value_delimiter = ',', default_value = "64,256,1024")]
+    concurrency: Vec<usize>,
+    /// Rate limits (probes per second) to run with each concurrency, comma separated
+    /// [default: unlimited]
+    #[arg(long, value_delimiter = ',')]
+    rate_limit: Vec<u32>,
+    /// Timeout for each probe stage
+    #[arg(short, long, default_value = "3s", value_parser = parse_duration)]
+    timeout: Duration,
+    /// Subnet the simulated gateways listen on
+    #[arg(long, default_value = "127.20.0.0/16")]
+    bind: Subnet,
+    /// Port the simulated gateways listen on
+    #[arg(short, long, default_value_t = 18080)]
+    port: u16,
+}
+
+/// Measurements of one scan run.
+#[derive(Debug, Serialize)]
+struct BenchResult {
+    concurrency: usize,
+    rate_limit: Option<u32>,
+    hosts: u32,
+    elapsed_ms: u128,
+    hosts_per_sec: f64,
+    p50_ms: u128,
+    p95_ms: u128,
+    found: usize,
+    expected: u32,
+    /// Fraction of probes failing with anything but a refused connection
+    error_rate: f64,
+}
+
+pub async fn run(args: BenchArgs, global: GlobalOptions) -> anyhow::Result<()> {
+    if !args.simulated {
+        anyhow::bail!("Only simulated benchmarks are supported, pass --simulated");
+    }
+    if args.gateways == 0 || args.gateways > args.hosts {
+        anyhow::bail!("--gateways must be between 1 and --hosts");
+    }
+    if u64::from(args.hosts) > args.bind.hosts().len() {
+        anyhow::bail!("{} has fewer than {} addresses", args.bind, args.hosts);
+    }
+
+    let range = args.bind.hosts();
+    let start = u32::from(range.into_iter().next().expect("Range must not be empty"));
+    let range = IpRange::new(Ipv4Addr::from(start), Ipv4Addr::from(start + args.hosts));
+    let g1 = args.gateways / 2;
+    let mut gateways = simulator::layout(
+        g1 as usize,
+        (args.gateways - g1) as usize,
+        &args.bind,
+        args.port,
+    )?;
+    let step = args.hosts / args.gateways;
+    for (i, gateway) in gateways.iter_mut().enumerate() {
+        gateway.ip = Ipv4Addr::from(start + i as u32 * step);
+    }
+    let server = simulator::bind(
+        gateways,
+        SimulatedCredentials {
+            username: USERNAME.to_string(),
+            password: String::new(),
+        },
+    )?;
+    let server = tokio::spawn(server);
+
+    let rate_limits: Vec<Option<u32>> = if args.rate_limit.is_empty() {
+        vec![None]
+    } else {
+        args.rate_limit.iter().copied().map(Some).collect()
+    };
+    let mut results = Vec::new();
+    for &concurrency in &args.concurrency {
+        for &rate_limit in &rate_limits {
+            let context = ProbeContext::new(
+                ScanConfig {
+                    port: args.port,
+                    timeout: args.timeout,
+                    rate_limit,
+                    g1_username: USERNAME.to_string(),
+                    ..ScanConfig::default()
+                },
+                Http::new(&global, global.http_client(&LoadedConfig::default())?)?,
+                None,
+            )?;
+            log::info!(
+                "Benchmarking concurrency {} rate limit {:?}",
+                concurrency,
+                rate_limit
+            );
+            results.push(bench(&context, &args, range, concurrency, rate_limit).await);
+        }
+    }
+    server.abort();
+
+    if global.porcelain {
+        for result in &results {
+            print_record(&[
+                "run",
+                &result.concurrency.to_string(),
+                &result.rate_limit.map(|r| r.to_string()).unwrap_or_default(),
+                &result.hosts_per_sec.to_string(),
+                &result.p95_ms.to_string(),
+                &result.error_rate.to_string(),
+            ]);
+        }
+    } else {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&results).expect("Results must be serializable")
+        );
+    }
+    Ok(())
+}
+
+async fn bench(
+    context: &ProbeContext,
+    args: &BenchArgs,
+    range: IpRange,
+    concurrency: usize,
+    rate_limit: Option<u32>,
+) -> BenchResult {
+    let started = std::time::Instant::now();
+    let probes: Vec<_> = context.scanner.probe(range, concurrency).collect().await;
+    let elapsed = started.elapsed();
+
+    let mut latencies: Vec<u128> = probes.iter().map(|(_, _, d)| d.as_millis()).collect();
+    latencies.sort_unstable();
+    let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+    let found = probes.iter().filter(|(_, r, _)| r.is_ok()).count();
+    let errors = probes
+        .iter()
+        .filter_map(|(_, r, _)| r.as_ref().err())
+        .filter(|err| !matches!(err, DetectError::ConnectionRefused))
+        .count();
+    BenchResult {
+        concurrency,
+        rate_limit,
+        hosts: args.hosts,
+        elapsed_ms: elapsed.as_millis(),
+        hosts_per_sec: (f64::from(args.hosts) / elapsed.as_secs_f64() * 10.0).round() / 10.0,
+        p50_ms: percentile(50),
+        p95_ms: percentile(95),
+        found,
+        expected: args.gateways,
+        error_rate: errors as f64 / probes.len() as f64,
+    }
+}
@@ -0,0 +1,85 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use rtls_ctl::{
+    report::ScanReport,
+    target::{IpRange, Targets},
+    types::GatewayDetection,
+};
+
+/// Gateways found by earlier scans, kept as a scan report so it can also be given to `--seed`.
+pub struct DetectionCache {
+    path: PathBuf,
+    gateways: Vec<GatewayDetection>,
+}
+
+impl DetectionCache {
+    /// `$XDG_CACHE_HOME/rtls-ctl/gateways.json`, falling back to `~/.cache/rtls-ctl/gateways.json`
+    /// (or `%LOCALAPPDATA%\rtls-ctl\gateways.json` on windows).
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+            .map(|dir| dir.join("rtls-ctl").join("gateways.json"))
+    }
+
+    /// Reads the cache, starting empty when it doesn't exist yet or can't be parsed.
+    pub fn load(path: &Path) -> Self {
+        let gateways = match ScanReport::load(path) {
+            Ok(report) => report.gateways,
+            Err(err) => {
+                if path.exists() {
+                    log::warn!("Ignoring detection cache: {:#}", anyhow::Error::from(err));
+                }
+                Vec::new()
+            }
+        };
+        Self {
+            path: path.to_path_buf(),
+            gateways,
+        }
+    }
+
+    pub fn gateways(&self) -> &[GatewayDetection] {
+        &self.gateways
+    }
+
+    pub fn addresses(&self) -> Targets {
+        Targets::new(
+            self.gateways
+                .iter()
+                .map(|gateway| IpRange::from(gateway.ip))
+                .collect(),
+        )
+    }
+
+    /// Replaces what is known about the `scanned` addresses with the gateways found there.
+    pub fn update(&mut self, scanned: &Targets, found: &[GatewayDetection]) {
+        self.gateways.retain(|cached| {
+            !scanned.contains(cached.ip)
+                && !found
+                    .iter()
+                    .any(|gateway| gateway.ip == cached.ip || gateway.mac == cached.mac)
+        });
+        self.gateways.extend_from_slice(found);
+        self.gateways.sort_by_key(|gateway| gateway.ip);
+    }
+
+    /// Writes the cache through a temporary file, so concurrent scans never read it truncated.
+    pub fn save(&self) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Error creating cache directory {}", dir.display()))?;
+        }
+        let report = ScanReport::new(self.gateways.clone());
+        let temporary = self.path.with_extension("tmp");
+        std::fs::write(
+            &temporary,
+            serde_json::to_vec_pretty(&report).expect("Gateways must be serializable"),
+        )
+        .and_then(|_| std::fs::rename(&temporary, &self.path))
+        .with_context(|| format!("Error writing detection cache {}", self.path.display()))
+    }
+}
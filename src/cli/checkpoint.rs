@@ -0,0 +1,123 @@
+use std::{
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use rtls_ctl::{
+    error::ProbeFailure,
+    target::{IpRange, Targets},
+    types::GatewayDetection,
+};
+use serde::{Deserialize, Serialize};
+
+/// Time between writes of the checkpoint file
+const INTERVAL: Duration = Duration::from_secs(10);
+
+/// Contents of a checkpoint file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+struct Stored {
+    /// Probed addresses as a comma separated target list
+    completed: String,
+    gateways: Vec<GatewayDetection>,
+    #[serde(default)]
+    errors: Vec<ProbeFailure>,
+}
+
+/// Progress of a scan, written to a file regularly so an interrupted scan can be resumed.
+pub struct Checkpoint {
+    path: PathBuf,
+    completed: Vec<IpRange>,
+    saved: Instant,
+}
+
+impl Checkpoint {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            completed: Vec::new(),
+            saved: Instant::now(),
+        }
+    }
+
+    /// Reads the checkpoint of an earlier run, returning it with the gateways and errors that run
+    /// found. A missing file starts from scratch.
+    pub fn resume(path: &Path) -> anyhow::Result<(Self, Vec<GatewayDetection>, Vec<ProbeFailure>)> {
+        let mut checkpoint = Self::new(path);
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                log::info!("No checkpoint at {}, starting a new scan", path.display());
+                return Ok((checkpoint, Vec::new(), Vec::new()));
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Error reading checkpoint {}", path.display()))
+            }
+        };
+        let stored: Stored = serde_json::from_str(&contents)
+            .with_context(|| format!("Error parsing checkpoint {}", path.display()))?;
+        if !stored.completed.is_empty() {
+            checkpoint.completed = Targets::from_str(&stored.completed)
+                .with_context(|| format!("Error parsing checkpoint {}", path.display()))?
+                .ranges()
+                .to_vec();
+        }
+        Ok((checkpoint, stored.gateways, stored.errors))
+    }
+
+    /// Addresses probed by earlier runs and this one.
+    pub fn completed(&self) -> Targets {
+        Targets::new(self.completed.clone())
+    }
+
+    /// Marks `ip` as probed, writing the file with the results so far when it is due.
+    pub fn complete(
+        &mut self,
+        ip: Ipv4Addr,
+        gateways: &[GatewayDetection],
+        errors: &[ProbeFailure],
+    ) -> anyhow::Result<()> {
+        self.completed.push(ip.into());
+        if self.saved.elapsed() >= INTERVAL {
+            self.save(gateways, errors)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the file through a temporary file, so an interruption never leaves it truncated.
+    pub fn save(
+        &mut self,
+        gateways: &[GatewayDetection],
+        errors: &[ProbeFailure],
+    ) -> anyhow::Result<()> {
+        let completed = self.completed();
+        self.completed = completed.ranges().to_vec();
+        let stored = Stored {
+            completed: completed.to_string(),
+            gateways: gateways.to_vec(),
+            errors: errors.to_vec(),
+        };
+        let temporary = self.path.with_extension("tmp");
+        std::fs::write(
+            &temporary,
+            serde_json::to_vec(&stored).expect("Checkpoints must be serializable"),
+        )
+        .and_then(|_| std::fs::rename(&temporary, &self.path))
+        .with_context(|| format!("Error writing checkpoint {}", self.path.display()))?;
+        self.saved = Instant::now();
+        Ok(())
+    }
+
+    /// Removes the file once the scan is complete, so it isn't resumed again.
+    pub fn finish(self) -> anyhow::Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err)
+                .with_context(|| format!("Error removing checkpoint {}", self.path.display())),
+            _ => Ok(()),
+        }
+    }
+}
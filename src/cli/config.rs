@@ -0,0 +1,194 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{ArgMatches, Args, Subcommand};
+use rtls_ctl::{config::Config, diff};
+use serde_json::Value;
+
+use super::{
+    global::GlobalOptions,
+    porcelain::print_record,
+    scan::{ScanArgs, ScanSettings},
+    settings::{LoadedConfig, Setting},
+};
+
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the settings a scan would use and where each one comes from
+    ShowEffective(Box<ScanArgs>),
+    /// Show the structural differences between two config files
+    Diff(DiffArgs),
+}
+
+#[derive(Args, Debug)]
+struct DiffArgs {
+    old: PathBuf,
+    new: PathBuf,
+}
+
+pub fn run(
+    args: ConfigArgs,
+    matches: &ArgMatches,
+    config: &LoadedConfig,
+    global: GlobalOptions,
+) -> anyhow::Result<()> {
+    match (args.command, matches.subcommand()) {
+        (ConfigCommand::ShowEffective(args), Some((_, matches))) => {
+            show_effective(&ScanSettings::resolve(&args, matches, config), config);
+            Ok(())
+        }
+        (ConfigCommand::Diff(args), _) => diff_files(&args, global.porcelain, global.color()),
+        (_, None) => unreachable!("Parsed subcommands must have matches"),
+    }
+}
+
+fn show_effective(settings: &ScanSettings, config: &LoadedConfig) {
+    match &config.path {
+        Some(path) => println!("# config file: {}", path.display()),
+        None => println!("# config file: none"),
+    }
+    if let Some((name, _)) = &config.profile {
+        println!("# profile: {}", name);
+    }
+    print_setting("scan.range", &settings.range, |range| match range {
+        Some(range) => format!("{:?}", range),
+        None => "<local subnets>".to_string(),
+    });
+    print_setting("scan.port", &settings.port, |p| p.to_string());
+    print_setting("scan.concurrency", &settings.concurrency, |c| c.to_string());
+    print_setting("scan.timeout", &settings.timeout, |t| {
+        format!("\"{}\"", humantime::format_duration(*t))
+    });
+    print_setting("defaults.retries", &settings.retries, |r| r.to_string());
+    print_setting("defaults.rate_limit", &settings.rate_limit, |r| match r {
+        Some(r) => r.to_string(),
+        None => "<unlimited>".to_string(),
+    });
+    print_setting("credentials.g1.username", &settings.g1_username, |u| {
+        format!("{:?}", u)
+    });
+    print_setting("credentials.g1.password", &settings.g1_password, |p| {
+        if p.is_empty() { "<empty>" } else { "<set>" }.to_string()
+    });
+}
+
+fn print_setting<T>(name: &str, setting: &Setting<T>, format: impl FnOnce(&T) -> String) {
+    println!(
+        "{} = {}  # {}",
+        name,
+        format(&setting.value),
+        setting.source
+    );
+}
+
+fn diff_files(args: &DiffArgs, porcelain: bool, color: bool) -> anyhow::Result<()> {
+    let load = |path: &PathBuf| -> anyhow::Result<Value> {
+        serde_json::to_value(Config::load(path)?).context("Error converting config")
+    };
+    let (mut old, mut new) = (load(&args.old)?, load(&args.new)?);
+    redact(&mut new, Some(&old), "<changed>");
+    redact(&mut old, None, "<set>");
+    let changes = diff::diff(&old, &new);
+    if porcelain {
+        print_porcelain(&changes);
+        return Ok(());
+    }
+    print!(
+        "{}",
+        diff::Rendered {
+            changes: &changes,
+            color,
+        }
+    );
+    Ok(())
+}
+
+/// Keys of the config holding secrets, in any section or profile.
+const SECRET_KEYS: &[&str] = &["password"];
+
+/// Masks the secrets of `value` as `"<set>"` like show-effective, or as `changed` where the secret
+/// differs from the one at the same path of `other`, so a changed password is still reported.
+fn redact(value: &mut Value, other: Option<&Value>, changed: &str) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                let other = other.and_then(|other| other.get(key));
+                if !SECRET_KEYS.contains(&key.as_str()) {
+                    redact(field, other, changed);
+                } else if !field.is_null() {
+                    let differs = other.is_some_and(|other| !other.is_null() && other != field);
+                    *field = Value::from(if differs { changed } else { "<set>" });
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (idx, item) in items.iter_mut().enumerate() {
+                redact(item, other.and_then(|other| other.get(idx)), changed);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `added <path> <value>`, `removed <path> <value>` and `changed <path> <old> <new>` records
+/// with compact json values.
+fn print_porcelain(changes: &[diff::Change]) {
+    for change in changes {
+        match change {
+            diff::Change::Added { path, value } => {
+                print_record(&["added", path, &value.to_string()])
+            }
+            diff::Change::Removed { path, value } => {
+                print_record(&["removed", path, &value.to_string()])
+            }
+            diff::Change::Changed { path, old, new } => {
+                print_record(&["changed", path, &old.to_string(), &new.to_string()])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn diff_never_shows_secrets() {
+        let mut old = json!({
+            "credentials": { "g1": { "username": "admin", "password": "old-secret" } },
+            "mqtt": { "password": "same" },
+            "profiles": { "site-a": { "credentials": { "g1": { "password": null } } } },
+        });
+        let mut new = json!({
+            "credentials": { "g1": { "username": "admin", "password": "new-secret" } },
+            "mqtt": { "password": "same" },
+            "profiles": { "site-a": { "credentials": { "g1": { "password": "added" } } } },
+        });
+        redact(&mut new, Some(&old), "<changed>");
+        redact(&mut old, None, "<set>");
+        let changes = diff::diff(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                diff::Change::Changed {
+                    path: "credentials.g1.password".to_string(),
+                    old: json!("<set>"),
+                    new: json!("<changed>"),
+                },
+                diff::Change::Changed {
+                    path: "profiles.site-a.credentials.g1.password".to_string(),
+                    old: Value::Null,
+                    new: json!("<set>"),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,30 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use anyhow::Context;
+
+/// Asks the user to confirm `summary` unless `assume_yes` is set, failing if they decline.
+///
+/// Without a terminal on stdin there is nobody to ask, so `--yes` is required.
+pub fn confirm(summary: &str, assume_yes: bool) -> anyhow::Result<()> {
+    if assume_yes {
+        return Ok(());
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!(
+            "{}: refusing to continue without --yes when not interactive",
+            summary
+        );
+    }
+    eprint!("{} - continue? [y/N] ", summary);
+    std::io::stderr().flush().context("Error writing prompt")?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("Error reading confirmation")?;
+    if matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+        Ok(())
+    } else {
+        anyhow::bail!("Aborted")
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::BufRead;
+
+use anyhow::Context;
+use clap::{Args, Subcommand, ValueEnum};
+use rtls_ctl::keychain::Secret;
+
+#[derive(Args, Debug)]
+pub struct CredsArgs {
+    #[command(subcommand)]
+    command: CredsCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum CredsCommand {
+    /// Store a password in the platform keyring, prompting for it
+    Set {
+        target: Target,
+        /// Read the password from the first line of stdin instead of prompting
+        #[arg(long)]
+        stdin: bool,
+    },
+    /// Check whether a password is stored without printing it
+    Check { target: Target },
+    /// Remove a stored password
+    Delete { target: Target },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Target {
+    /// G1 admin password
+    G1,
+    /// MQTT broker password for MG3 provisioning
+    Mqtt,
+}
+
+impl From<Target> for Secret {
+    fn from(target: Target) -> Self {
+        match target {
+            Target::G1 => Secret::G1Password,
+            Target::Mqtt => Secret::MqttPassword,
+        }
+    }
+}
+
+/// Passwords are stored per `--profile` so every site can use its own.
+pub fn run(args: CredsArgs, profile: Option<&str>) -> anyhow::Result<()> {
+    match args.command {
+        CredsCommand::Set { target, stdin } => {
+            let secret = Secret::from(target);
+            let password = if stdin {
+                let mut line = String::new();
+                std::io::stdin()
+                    .lock()
+                    .read_line(&mut line)
+                    .context("Error reading password from stdin")?;
+                line.trim_end_matches(['\r', '\n']).to_string()
+            } else {
+                rpassword::prompt_password(format!("Password for {}: ", secret.account(profile)))
+                    .context("Error reading password")?
+            };
+            secret.set(profile, &password)?;
+            eprintln!("Stored {} in the keyring", secret.account(profile));
+        }
+        CredsCommand::Check { target } => {
+            let secret = Secret::from(target);
+            match secret.get(profile)? {
+                Some(_) => println!("{} is stored", secret.account(profile)),
+                None => anyhow::bail!("{} is not stored", secret.account(profile)),
+            }
+        }
+        CredsCommand::Delete { target } => {
+            let secret = Secret::from(target);
+            if secret.delete(profile)? {
+                eprintln!("Deleted {} from the keyring", secret.account(profile));
+            } else {
+                eprintln!("{} was not stored", secret.account(profile));
+            }
+        }
+    }
+    Ok(())
+}
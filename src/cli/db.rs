@@ -0,0 +1,83 @@
+//! `--db`, appending every scan to a SQLite database to keep the history of the fleet.
+//!
+//! ```sql
+//! -- When each gateway was first and last seen
+//! SELECT mac, MIN(started_at), MAX(started_at)
+//! FROM gateways JOIN scans ON scans.id = gateways.scan_id
+//! GROUP BY mac;
+//! ```
+
+use std::{path::Path, time::SystemTime};
+
+use anyhow::Context;
+use rtls_ctl::report::ScanReport;
+use rusqlite::{params, Connection};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS scans (
+    id INTEGER PRIMARY KEY,
+    started_at TEXT NOT NULL,
+    finished_at TEXT NOT NULL,
+    gateways INTEGER NOT NULL,
+    errors INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS gateways (
+    scan_id INTEGER NOT NULL REFERENCES scans(id),
+    ip TEXT NOT NULL,
+    mac TEXT NOT NULL,
+    type TEXT NOT NULL,
+    interface TEXT,
+    vlan INTEGER,
+    hostname TEXT,
+    port INTEGER,
+    rtt_ms REAL,
+    firmware TEXT,
+    model TEXT
+);
+CREATE INDEX IF NOT EXISTS gateways_mac ON gateways(mac);
+";
+
+/// Adds the report as a new scan, creating the database and its tables when needed. Timestamps
+/// are RFC 3339 in UTC, which sort chronologically as text.
+pub fn append(path: &Path, report: &ScanReport, started: SystemTime) -> anyhow::Result<()> {
+    let mut db = Connection::open(path)
+        .with_context(|| format!("Error opening database {}", path.display()))?;
+    db.execute_batch(SCHEMA)
+        .with_context(|| format!("Error creating tables in {}", path.display()))?;
+    let transaction = db.transaction()?;
+    transaction.execute(
+        "INSERT INTO scans (started_at, finished_at, gateways, errors) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            humantime::format_rfc3339_seconds(started).to_string(),
+            humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+            report.gateways.len(),
+            report.errors.len(),
+        ],
+    )?;
+    let scan = transaction.last_insert_rowid();
+    {
+        let mut insert = transaction.prepare(
+            "INSERT INTO gateways
+                 (scan_id, ip, mac, type, interface, vlan, hostname, port, rtt_ms, firmware, model)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )?;
+        for gateway in &report.gateways {
+            insert.execute(params![
+                scan,
+                gateway.ip.to_string(),
+                gateway.mac.to_string(),
+                gateway.gateway.as_str(),
+                gateway.interface,
+                gateway.vlan,
+                gateway.hostname,
+                gateway.port,
+                gateway.rtt_ms,
+                gateway.firmware,
+                gateway.model,
+            ])?;
+        }
+    }
+    transaction
+        .commit()
+        .with_context(|| format!("Error saving scan to {}", path.display()))
+}
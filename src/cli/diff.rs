@@ -0,0 +1,119 @@
+use std::{
+    io::IsTerminal,
+    path::{Path, PathBuf},
+};
+
+use clap::Args;
+use rtls_ctl::report::{GatewayChange, ScanReport};
+
+use super::{global::GlobalOptions, porcelain::print_record};
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// Scan report from before, like the one of the last maintenance window
+    old: PathBuf,
+    /// Scan report to compare it with, - for stdin
+    #[arg(default_value = "-")]
+    new: PathBuf,
+}
+
+pub fn run(args: DiffArgs, global: GlobalOptions) -> anyhow::Result<()> {
+    let old = ScanReport::load(&args.old)?;
+    let new = if args.new == Path::new("-") {
+        ScanReport::from_reader(std::io::stdin().lock())?
+    } else {
+        ScanReport::load(&args.new)?
+    };
+    print_changes(&new.changes_since(&old), &global);
+    Ok(())
+}
+
+/// Prints the changes as porcelain records, colored lines on a terminal or else a json array.
+pub fn print_changes(changes: &[GatewayChange], global: &GlobalOptions) {
+    if global.porcelain {
+        print_porcelain(changes);
+    } else if std::io::stdout().is_terminal() {
+        print_lines(changes, global.color());
+    } else {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(changes).expect("Changes must be serializable")
+        );
+    }
+}
+
+/// `+ mac ip type`, `- mac ip type` and `~ mac old -> new`, in green, red and yellow with `color`.
+fn print_lines(changes: &[GatewayChange], color: bool) {
+    if changes.is_empty() {
+        println!("No changes");
+    }
+    for change in changes {
+        let (code, line) = match change {
+            GatewayChange::Added { gateway } => (
+                "\x1b[32m",
+                format!(
+                    "+ {} {} {}",
+                    gateway.mac,
+                    gateway.ip,
+                    gateway.gateway.as_str()
+                ),
+            ),
+            GatewayChange::Removed { gateway } => (
+                "\x1b[31m",
+                format!(
+                    "- {} {} {}",
+                    gateway.mac,
+                    gateway.ip,
+                    gateway.gateway.as_str()
+                ),
+            ),
+            GatewayChange::Moved {
+                mac,
+                gateway,
+                old_ip,
+                new_ip,
+            } => (
+                "\x1b[33m",
+                format!("~ {} {} -> {} {}", mac, old_ip, new_ip, gateway.as_str()),
+            ),
+        };
+        if color {
+            println!("{}{}\x1b[0m", code, line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+/// `added <mac> <ip> <type>`, `removed <mac> <ip> <type>` and
+/// `moved <mac> <old ip> <new ip> <type>` records.
+fn print_porcelain(changes: &[GatewayChange]) {
+    for change in changes {
+        match change {
+            GatewayChange::Added { gateway } => print_record(&[
+                "added",
+                &gateway.mac.to_string(),
+                &gateway.ip.to_string(),
+                gateway.gateway.as_str(),
+            ]),
+            GatewayChange::Removed { gateway } => print_record(&[
+                "removed",
+                &gateway.mac.to_string(),
+                &gateway.ip.to_string(),
+                gateway.gateway.as_str(),
+            ]),
+            GatewayChange::Moved {
+                mac,
+                gateway,
+                old_ip,
+                new_ip,
+            } => print_record(&[
+                "moved",
+                &mac.to_string(),
+                &old_ip.to_string(),
+                &new_ip.to_string(),
+                gateway.as_str(),
+            ]),
+        }
+    }
+}
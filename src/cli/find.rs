@@ -0,0 +1,104 @@
+use clap::Args;
+use futures::StreamExt;
+use rtls_ctl::{
+    diagnostics,
+    scanner::CONCURRENCY,
+    target::Targets,
+    types::{GatewayDetection, Mac},
+};
+
+use super::{
+    global::GlobalOptions,
+    porcelain::print_record,
+    scan::{self, ProbeContext},
+    settings::LoadedConfig,
+};
+
+#[derive(Args, Debug)]
+pub struct FindArgs {
+    /// Mac address of the gateway
+    #[arg(long)]
+    mac: Mac,
+    /// Addresses to scan when the neighbor table doesn't know the gateway [default: the subnet of
+    /// every local interface]
+    #[arg(long)]
+    range: Option<Targets>,
+    /// Only consult the neighbor table, never scan
+    #[arg(long, conflicts_with = "range")]
+    no_scan: bool,
+    /// Http port of the gateway [default: 80]
+    #[arg(short, long, env = "RTLS_CTL_PORT")]
+    port: Option<u16>,
+}
+
+/// Prints the gateway with the mac, trying the addresses the neighbor table has for it before
+/// scanning.
+pub async fn run(
+    args: FindArgs,
+    config: &LoadedConfig,
+    global: GlobalOptions,
+) -> anyhow::Result<()> {
+    let mac = args.mac;
+    let context = ProbeContext::for_host(config, &global, args.port)?;
+    let detection = match find_neighbor(&context, mac).await {
+        Some(detection) => Some(detection),
+        None if args.no_scan => None,
+        None => {
+            let targets = match args.range {
+                Some(range) => range,
+                None => scan::local_targets()?
+                    .into_iter()
+                    .map(|target| target.range)
+                    .collect(),
+            };
+            log::info!("Scanning {} for {}...", targets, mac);
+            context
+                .scanner
+                .probe(targets, CONCURRENCY)
+                .filter_map(|(_, result, _)| async move {
+                    result.ok().filter(|detection| detection.mac == mac)
+                })
+                .boxed()
+                .next()
+                .await
+        }
+    };
+    context.finish()?;
+    let Some(detection) = detection else {
+        anyhow::bail!("No gateway with mac {} found", mac);
+    };
+    if global.porcelain {
+        print_record(&[
+            "gateway",
+            &detection.ip.to_string(),
+            detection.gateway.as_str(),
+            &detection.mac.to_string(),
+        ]);
+    } else {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&detection).expect("Gateways must be serializable")
+        );
+    }
+    Ok(())
+}
+
+/// The gateway at an address the neighbor table resolves to `mac`, confirmed with a probe as
+/// entries can be stale.
+async fn find_neighbor(context: &ProbeContext, mac: Mac) -> Option<GatewayDetection> {
+    let neighbors = match diagnostics::neighbors() {
+        Ok(neighbors) => neighbors,
+        Err(err) => {
+            log::info!("Skipping the neighbor table: {:#}", err);
+            return None;
+        }
+    };
+    for (ip, _) in neighbors.into_iter().filter(|(_, entry)| *entry == mac) {
+        match context.scanner.detect(ip).await {
+            Ok(detection) if detection.mac == mac => return Some(detection),
+            Ok(detection) => log::info!("{} now answers as {}", ip, detection.mac),
+            Err(err) => log::info!("{} did not answer: {:#}", ip, err),
+        }
+    }
+    None
+}
@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Args;
+
+#[derive(Args, Debug)]
+pub struct GenDocsArgs {
+    /// Generate man pages (currently the only format)
+    #[arg(long)]
+    man: bool,
+    /// Directory to write the pages to
+    #[arg(long, default_value = "man")]
+    out: PathBuf,
+}
+
+/// Writes `rtls-ctl.1` plus a `rtls-ctl-<sub>.1` page for every subcommand.
+pub fn run(args: GenDocsArgs, command: clap::Command) -> anyhow::Result<()> {
+    if !args.man {
+        anyhow::bail!("Nothing to generate, pass --man");
+    }
+    std::fs::create_dir_all(&args.out)
+        .with_context(|| format!("Error creating {}", args.out.display()))?;
+    clap_mangen::generate_to(command, &args.out)
+        .with_context(|| format!("Error writing man pages to {}", args.out.display()))?;
+    println!("Wrote man pages to {}", args.out.display());
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use std::{collections::BTreeSet, net::Ipv4Addr, path::PathBuf};
+
+use anyhow::Context;
+use clap::Args;
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+use rtls_ctl::{
+    report::ScanReport,
+    simulator::MINEW_OUI,
+    types::{GatewayDetection, GatewayType, Mac},
+};
+
+#[derive(Args, Debug)]
+pub struct GenFixtureArgs {
+    /// Number of gateways in the report
+    #[arg(long, default_value_t = 200)]
+    gateways: usize,
+    /// Number of /24 subnets the gateways are spread over
+    #[arg(long, default_value_t = 4)]
+    subnets: usize,
+    /// Seed for reproducible fixtures [default: random]
+    #[arg(long)]
+    seed: Option<u64>,
+    /// File to write the report to [default: stdout]
+    #[arg(short, long)]
+    out: Option<PathBuf>,
+}
+
+/// Writes a fake scan report of MG3 and G1 gateways with unique Minew macs.
+pub fn run(args: GenFixtureArgs) -> anyhow::Result<()> {
+    if args.subnets == 0 || args.subnets > 256 {
+        anyhow::bail!("--subnets must be between 1 and 256");
+    }
+    if args.gateways > args.subnets * 254 {
+        anyhow::bail!(
+            "{} subnets have room for at most {} gateways",
+            args.subnets,
+            args.subnets * 254
+        );
+    }
+    let mut rng = match args.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let subnet_base: u8 = rng.gen_range(0..=(256 - args.subnets) as u16) as u8;
+    let mut macs = BTreeSet::new();
+    let mut gateways = Vec::with_capacity(args.gateways);
+    for subnet in 0..args.subnets {
+        let count =
+            args.gateways / args.subnets + usize::from(subnet < args.gateways % args.subnets);
+        let third_octet = subnet_base + subnet as u8;
+        for host in (1..=254u8).choose_multiple(&mut rng, count) {
+            let mac = loop {
+                let suffix: [u8; 3] = rng.gen();
+                let mac = Mac::from([
+                    MINEW_OUI[0],
+                    MINEW_OUI[1],
+                    MINEW_OUI[2],
+                    suffix[0],
+                    suffix[1],
+                    suffix[2],
+                ]);
+                if macs.insert(mac) {
+                    break mac;
+                }
+            };
+            let gateway = if rng.gen_bool(0.7) {
+                GatewayType::MG3
+            } else {
+                GatewayType::G1
+            };
+            gateways.push(GatewayDetection::new(
+                Ipv4Addr::new(10, 20, third_octet, host),
+                gateway,
+                mac,
+            ));
+        }
+    }
+    gateways.sort_by_key(|gateway| gateway.ip);
+
+    let report = serde_json::to_string_pretty(&ScanReport::new(gateways))
+        .expect("Gateways must be serializable");
+    match &args.out {
+        Some(path) => std::fs::write(path, report + "\n")
+            .with_context(|| format!("Error writing {}", path.display()))?,
+        None => println!("{}", report),
+    }
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use std::{io::IsTerminal, path::PathBuf};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use rtls_ctl::transport::{Remap, Socks5Proxy};
+
+use super::{progress::ProgressFormat, settings::LoadedConfig};
+
+/// Global flags shared by every command.
+#[derive(Debug, Clone)]
+pub struct GlobalOptions {
+    pub porcelain: bool,
+    pub progress: Option<ProgressFormat>,
+    pub offline: bool,
+    /// Canonical, timing independent output
+    pub deterministic: bool,
+    /// Cassette to record gateway interactions to
+    pub record: Option<PathBuf>,
+    /// Cassette to answer gateway requests from instead of the network
+    pub replay: Option<PathBuf>,
+    /// Test only rewrites of gateway addresses
+    pub remap: Vec<Remap>,
+    /// Extra root certificates, overriding `tls.ca_bundle` of the config
+    pub ca_bundle: Option<PathBuf>,
+    /// SOCKS5 proxy gateway connections are tunneled through, from `--proxy` or `--ssh-jump`
+    pub proxy: Option<Socks5Proxy>,
+    pub color: ColorChoice,
+}
+
+/// When human readable output uses ansi colors.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// When stdout is a terminal and NO_COLOR isn't set
+    Auto,
+    Always,
+    Never,
+}
+
+impl GlobalOptions {
+    /// Http client builder for talking to gateways, trusting the CA bundle of the command line
+    /// or the selected profile on top of the system roots.
+    ///
+    /// Proxies from the environment are only used when online, since in offline mode nothing but
+    /// the targets may be contacted. `--proxy` tunnels every request through its proxy.
+    pub fn http_client(&self, config: &LoadedConfig) -> anyhow::Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::Client::builder();
+        if self.offline {
+            builder = builder.no_proxy();
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy.url()).context("Invalid proxy")?);
+        }
+        let ca_bundle = self.ca_bundle.clone().or_else(|| {
+            config
+                .lookup(|c| c.tls.ca_bundle.clone())
+                .map(|(path, _)| path)
+        });
+        if let Some(path) = ca_bundle {
+            let pem = std::fs::read(&path)
+                .with_context(|| format!("Error reading CA bundle {}", path.display()))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid CA bundle {}", path.display()))?;
+            log::debug!(
+                "Trusting {} certificates from {}",
+                certificates.len(),
+                path.display()
+            );
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(builder)
+    }
+
+    /// Whether to color output on stdout.
+    pub fn color(&self) -> bool {
+        match self.color {
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+
+    /// Fails when `feature` needs network access beyond the targets and `--offline` is set.
+    pub fn require_network(&self, feature: &str) -> anyhow::Result<()> {
+        if self.offline {
+            anyhow::bail!(
+                "{} needs network access beyond the targets, which --offline disables",
+                feature
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use clap::Args;
+use rtls_ctl::{
+    cassette::{Cassette, Recorder, RecordingTransport},
+    duration::parse_duration,
+    transport::{RemappingTransport, ReqwestTransport, SocketOptions, Transport},
+};
+
+use super::{global::GlobalOptions, settings::LoadedConfig};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(90);
+
+/// Connection reuse for commands making several requests to the same gateways.
+#[derive(Args, Debug, Clone)]
+pub struct ConnectionArgs {
+    /// How long idle connections to a gateway are kept for reuse, 0s to disable [default: 90s]
+    #[arg(long, env = "RTLS_CTL_KEEP_ALIVE", value_parser = parse_duration)]
+    keep_alive: Option<Duration>,
+    /// Speak HTTP/2 without negotiating it, for gateways or proxies known to support it
+    #[arg(long, env = "RTLS_CTL_HTTP2")]
+    http2: bool,
+}
+
+impl ConnectionArgs {
+    /// Applies the options, falling back to `[defaults]` of the config.
+    pub fn apply(
+        &self,
+        builder: reqwest::ClientBuilder,
+        config: &LoadedConfig,
+    ) -> reqwest::ClientBuilder {
+        let keep_alive = self
+            .keep_alive
+            .or_else(|| config.lookup(|c| c.defaults.keep_alive).map(|(k, _)| k))
+            .unwrap_or(KEEP_ALIVE);
+        let http2 = self.http2
+            || config
+                .lookup(|c| c.defaults.http2)
+                .is_some_and(|(http2, _)| http2);
+        let builder = if keep_alive.is_zero() {
+            builder.pool_max_idle_per_host(0)
+        } else {
+            builder
+                .pool_idle_timeout(keep_alive)
+                .tcp_keepalive(keep_alive)
+        };
+        if http2 {
+            builder.http2_prior_knowledge()
+        } else {
+            builder
+        }
+    }
+}
+
+/// The transport for gateway requests, recording to or replaying from a cassette when
+/// `--record` or `--replay` is given.
+pub struct Http {
+    pub transport: Arc<dyn Transport>,
+    recording: Option<(Arc<Recorder>, PathBuf)>,
+}
+
+impl Http {
+    pub fn new(global: &GlobalOptions, builder: reqwest::ClientBuilder) -> anyhow::Result<Self> {
+        Self::with_socket_options(global, builder, SocketOptions::default())
+    }
+
+    /// Like [`Http::new`], opening tcp probe connections with `sockets`.
+    pub fn with_socket_options(
+        global: &GlobalOptions,
+        builder: reqwest::ClientBuilder,
+        sockets: SocketOptions,
+    ) -> anyhow::Result<Self> {
+        if let Some(path) = &global.replay {
+            return Ok(Self {
+                transport: Arc::new(Cassette::load(path)?),
+                recording: None,
+            });
+        }
+        let sockets = SocketOptions {
+            proxy: global.proxy.clone(),
+            ..sockets
+        };
+        let client = builder.build().context("Error building http client")?;
+        let mut live: Box<dyn Transport> =
+            Box::new(ReqwestTransport::with_socket_options(client, sockets));
+        if !global.remap.is_empty() {
+            live = Box::new(RemappingTransport::new(live, global.remap.clone()));
+        }
+        Ok(match &global.record {
+            Some(path) => {
+                let recorder = Arc::new(Recorder::default());
+                Self {
+                    transport: Arc::new(RecordingTransport::new(live, recorder.clone())),
+                    recording: Some((recorder, path.clone())),
+                }
+            }
+            None => Self {
+                transport: live.into(),
+                recording: None,
+            },
+        })
+    }
+
+    /// Writes the cassette when recording.
+    pub fn finish(&self) -> anyhow::Result<()> {
+        if let Some((recorder, path)) = &self.recording {
+            recorder.save(path)?;
+        }
+        Ok(())
+    }
+}
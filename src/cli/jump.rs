@@ -0,0 +1,72 @@
+//! `--ssh-jump`, reaching gateways behind a bastion through the SOCKS5 proxy of `ssh -D`.
+
+use std::{
+    net::Ipv4Addr,
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use rtls_ctl::transport::Socks5Proxy;
+use tokio::process::{Child, Command};
+
+/// How long ssh gets to log in and open the proxy port
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A running `ssh -D`, stopped when dropped.
+pub struct SshJump {
+    _child: Child,
+    pub proxy: Socks5Proxy,
+}
+
+impl SshJump {
+    /// Starts ssh to `destination` (`user@host`, or a host of the ssh config) with a dynamic
+    /// forward on a free local port, waiting until the forward accepts connections.
+    pub async fn start(destination: &str) -> anyhow::Result<Self> {
+        let port = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .context("Error finding a free local port")?
+            .port();
+        log::info!(
+            "Tunneling through {} with a SOCKS5 proxy on port {}",
+            destination,
+            port
+        );
+        let mut child = Command::new("ssh")
+            .args(["-N", "-o", "ExitOnForwardFailure=yes", "-D"])
+            .arg(format!("{}:{}", Ipv4Addr::LOCALHOST, port))
+            .arg(destination)
+            // Password prompts go to the terminal, not stdin
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Error running ssh")?;
+
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                anyhow::bail!("ssh to {} exited with {}", destination, status);
+            }
+            if tokio::net::TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+                .await
+                .is_ok()
+            {
+                break;
+            }
+            if Instant::now() > deadline {
+                anyhow::bail!(
+                    "Timeout waiting for ssh to {} to open the proxy",
+                    destination
+                );
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Ok(Self {
+            _child: child,
+            proxy: Socks5Proxy {
+                host: Ipv4Addr::LOCALHOST.to_string(),
+                port,
+            },
+        })
+    }
+}
@@ -0,0 +1,52 @@
+use std::{collections::HashSet, time::Duration};
+
+use clap::Args;
+use rtls_ctl::{discovery, duration::parse_duration};
+
+use super::{global::GlobalOptions, porcelain::print_record};
+
+#[derive(Args, Debug)]
+pub struct ListenArgs {
+    /// Stop listening after this long (e.g. 30s, 10m) [default: until interrupted]
+    #[arg(long, value_parser = parse_duration)]
+    duration: Option<Duration>,
+    /// mDNS service types MG3 gateways advertise (repeatable) [default: _rtls._tcp]
+    #[arg(long, env = "RTLS_CTL_MDNS_SERVICE", value_delimiter = ',')]
+    mdns_service: Vec<String>,
+}
+
+/// Prints every gateway announcing itself once, as a line of JSON, without sending any probe.
+pub async fn run(args: ListenArgs, global: GlobalOptions) -> anyhow::Result<()> {
+    let services = if args.mdns_service.is_empty() {
+        vec![discovery::MDNS_SERVICE.to_string()]
+    } else {
+        args.mdns_service
+    };
+    let mut seen = HashSet::new();
+    let listen = discovery::listen(&services, |detection| {
+        if !seen.insert((detection.ip, detection.mac)) {
+            return;
+        }
+        if global.porcelain {
+            print_record(&[
+                "gateway",
+                &detection.ip.to_string(),
+                detection.gateway.as_str(),
+                &detection.mac.to_string(),
+            ]);
+        } else {
+            println!(
+                "{}",
+                serde_json::to_string(&detection).expect("Gateways must be serializable")
+            );
+        }
+    });
+    log::info!("Listening for gateway announcements...");
+    match args.duration {
+        Some(duration) => match tokio::time::timeout(duration, listen).await {
+            Ok(result) => result,
+            Err(_) => Ok(()),
+        },
+        None => listen.await,
+    }
+}
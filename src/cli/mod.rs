@@ -0,0 +1,29 @@
+pub mod aliases;
+pub mod bench;
+pub mod cache;
+pub mod checkpoint;
+pub mod config;
+pub mod confirm;
+pub mod creds;
+pub mod db;
+pub mod diff;
+pub mod find;
+pub mod gen_docs;
+pub mod gen_fixture;
+pub mod global;
+pub mod http;
+pub mod jump;
+pub mod listen;
+pub mod output;
+pub mod porcelain;
+pub mod progress;
+pub mod reboot;
+pub mod runtime;
+pub mod scan;
+pub mod settings;
+pub mod simulate;
+pub mod template;
+pub mod trace;
+pub mod trace_file;
+pub mod validate;
+pub mod wake;
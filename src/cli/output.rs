@@ -0,0 +1,590 @@
+//! Renderings of a scan report for `--format`.
+
+use std::{
+    collections::HashSet,
+    io::{IsTerminal, Write},
+    net::Ipv4Addr,
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use rtls_ctl::{
+    report::{ScanInfo, ScanReport},
+    types::{GatewayDetection, GatewayType, Mac},
+};
+use serde::Serialize;
+
+use super::{scan::Compat, template::Template};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The scan report, or the bare gateway array with --compat v1
+    Json,
+    /// `ip,mac,gateway,rtt` rows with a header, rtt in milliseconds, for spreadsheets and asset imports
+    Csv,
+    /// Aligned columns for reading in a terminal, the default when stdout is one
+    Table,
+    /// One compact json object per line, printed as each gateway is found unless --deterministic
+    Ndjson,
+    /// The json structure as yaml, with the same field names
+    Yaml,
+    /// Prometheus metrics for node_exporter's textfile collector, best written with -o
+    Prom,
+    /// A JUnit test case per gateway of the --inventory, failing when it wasn't found
+    Junit,
+    /// A Graphviz graph of this host, the subnets scanned and the gateways found on them
+    Dot,
+    /// Only the ip of every gateway, one per line, for xargs and parallel
+    Ips,
+}
+
+impl OutputFormat {
+    /// The table when stdout is a terminal, json for everything reading the output.
+    pub fn default_for_stdout() -> Self {
+        if std::io::stdout().is_terminal() {
+            Self::Table
+        } else {
+            Self::Json
+        }
+    }
+}
+
+/// How a report is printed.
+pub struct Rendering<'a> {
+    pub format: OutputFormat,
+    pub compat: Compat,
+    /// Ansi escapes where the format has any
+    pub color: bool,
+    /// Gateways expected by `--format junit`, with their names
+    pub inventory: &'a [(Mac, Option<String>)],
+    /// Line per gateway from `--template`, printed instead of the format
+    pub template: Option<&'a Template>,
+}
+
+impl Rendering<'_> {
+    /// The report in the format, ending with a newline.
+    pub fn render(&self, report: &ScanReport) -> String {
+        render(report, self)
+    }
+}
+
+fn render(report: &ScanReport, rendering: &Rendering) -> String {
+    if let Some(template) = rendering.template {
+        return report
+            .gateways
+            .iter()
+            .map(|gateway| format!("{}\n", template.render(gateway)))
+            .collect();
+    }
+    let compat = rendering.compat;
+    match rendering.format {
+        OutputFormat::Json => {
+            let json = match compat {
+                Compat::V1 => serde_json::to_string_pretty(&v1(&report.gateways)),
+                Compat::V2 => serde_json::to_string_pretty(report),
+            };
+            format!("{}\n", json.expect("Gateways must be serializable"))
+        }
+        OutputFormat::Csv => csv(&report.gateways),
+        OutputFormat::Table => table(report, rendering.color),
+        OutputFormat::Junit => junit(report, rendering.inventory),
+        OutputFormat::Dot => dot(report),
+        OutputFormat::Ips => report
+            .gateways
+            .iter()
+            .map(|gateway| format!("{}\n", gateway.ip))
+            .collect(),
+        OutputFormat::Yaml => {
+            let yaml = match compat {
+                Compat::V1 => serde_yaml::to_string(&v1(&report.gateways)),
+                Compat::V2 => serde_yaml::to_string(report),
+            };
+            yaml.expect("Gateways must be serializable")
+        }
+        OutputFormat::Prom => prometheus(report),
+        OutputFormat::Ndjson => {
+            let gateways = report.gateways.iter().map(ndjson_line);
+            let errors = report.errors.iter().map(ndjson_line);
+            gateways.chain(errors).collect()
+        }
+    }
+}
+
+/// A gateway with only the fields of versions before the scan report, for `--compat v1`.
+#[derive(Serialize)]
+struct GatewayV1 {
+    ip: Ipv4Addr,
+    gateway: GatewayType,
+    mac: Mac,
+}
+
+fn v1(gateways: &[GatewayDetection]) -> Vec<GatewayV1> {
+    gateways
+        .iter()
+        .map(|gateway| GatewayV1 {
+            ip: gateway.ip,
+            gateway: gateway.gateway,
+            mac: gateway.mac,
+        })
+        .collect()
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it into place, so readers of
+/// `path` only ever see complete results.
+pub fn write_atomic(path: &Path, contents: &str) -> anyhow::Result<()> {
+    let name = path
+        .file_name()
+        .with_context(|| format!("{} is not a file path", path.display()))?;
+    let mut temporary = name.to_os_string();
+    temporary.push(format!(".{}.tmp", std::process::id()));
+    let temporary = path.with_file_name(temporary);
+    let result = std::fs::File::create(&temporary)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result.with_context(|| format!("Error writing results to {}", path.display()))
+}
+
+/// A gateway or failed probe as a line of `--format ndjson`. Failures are told apart by their
+/// `code` field.
+pub fn ndjson_line(value: &impl Serialize) -> String {
+    format!(
+        "{}\n",
+        serde_json::to_string(value).expect("Gateways must be serializable")
+    )
+}
+
+fn csv(gateways: &[GatewayDetection]) -> String {
+    let mut output = String::from("ip,mac,gateway,rtt\n");
+    for gateway in gateways {
+        let row = [
+            gateway.ip.to_string(),
+            gateway.mac.to_string(),
+            gateway.gateway.as_str().to_string(),
+            gateway
+                .rtt_ms
+                .map_or_else(String::new, |rtt| rtt.to_string()),
+        ];
+        let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        output.push_str(&row.join(","));
+        output.push('\n');
+    }
+    output
+}
+
+/// Quotes a field containing separators, quotes or line breaks, doubling its quotes.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// A test suite with a test case per expected gateway, passing when a gateway with its mac was
+/// detected, for CI dashboards to show missing gateways.
+fn junit(report: &ScanReport, inventory: &[(Mac, Option<String>)]) -> String {
+    let mut cases = String::new();
+    let mut failures = 0;
+    for (mac, name) in inventory {
+        let title = match name {
+            Some(name) => format!("{} ({})", name, mac),
+            None => mac.to_string(),
+        };
+        let found = report.gateways.iter().find(|gateway| gateway.mac == *mac);
+        match found {
+            Some(gateway) => cases.push_str(&format!(
+                "    <testcase classname=\"gateways\" name=\"{}\" time=\"{:.3}\">\n\
+                 \x20     <system-out>{} {} at {}</system-out>\n\
+                 \x20   </testcase>\n",
+                xml_escape(&title),
+                gateway.rtt_ms.unwrap_or_default() / 1000.0,
+                gateway.gateway.as_str(),
+                gateway.mac,
+                gateway.ip
+            )),
+            None => {
+                failures += 1;
+                cases.push_str(&format!(
+                    "    <testcase classname=\"gateways\" name=\"{}\">\n\
+                     \x20     <failure message=\"Gateway {} was not detected\"/>\n\
+                     \x20   </testcase>\n",
+                    xml_escape(&title),
+                    mac
+                ));
+            }
+        }
+    }
+    // Left out of canonical reports, which have no timing
+    let timestamp = report
+        .scan
+        .as_ref()
+        .and_then(|scan| scan.started_at.as_deref())
+        .map(|started| format!(" timestamp=\"{}\"", xml_escape(started)))
+        .unwrap_or_default();
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <testsuites>\n\
+         \x20 <testsuite name=\"rtls-ctl\" tests=\"{}\" failures=\"{}\"{}>\n\
+         {}\
+         \x20 </testsuite>\n\
+         </testsuites>\n",
+        inventory.len(),
+        failures,
+        timestamp,
+        cases
+    )
+}
+
+/// `host -> target -> gateway` edges, gateways labelled with their type and mac. Gateways are
+/// under the range of the scanned targets containing them. The ones resolved from a hostname, and
+/// all of them for reports without scan info, are linked to the host directly.
+fn dot(report: &ScanReport) -> String {
+    let host = dns_lookup::get_hostname().unwrap_or_else(|_| "rtls-ctl".to_string());
+    let ranges = report
+        .targets()
+        .map(|targets| targets.ranges().to_vec())
+        .unwrap_or_default();
+    let mut output = String::from("digraph rtls {\n    rankdir=LR;\n");
+    output.push_str(&format!(
+        "    \"{}\" [shape=box, style=bold];\n",
+        dot_escape(&host)
+    ));
+    for range in &ranges {
+        if !report
+            .gateways
+            .iter()
+            .any(|gateway| range.contains(gateway.ip))
+        {
+            continue;
+        }
+        output.push_str(&format!(
+            "    \"{}\" [shape=ellipse];\n    \"{}\" -> \"{}\";\n",
+            range,
+            dot_escape(&host),
+            range
+        ));
+    }
+    for gateway in &report.gateways {
+        let parent = ranges
+            .iter()
+            .find(|range| range.contains(gateway.ip))
+            .map_or_else(|| dot_escape(&host), ToString::to_string);
+        output.push_str(&format!(
+            "    \"{}\" [shape=record, label=\"{}|{}|{}\"];\n    \"{}\" -> \"{}\";\n",
+            gateway.ip,
+            gateway.ip,
+            gateway.gateway.as_str(),
+            gateway.mac,
+            parent,
+            gateway.ip
+        ));
+    }
+    output.push_str("}\n");
+    output
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `rtls_gateway_up` for every gateway and totals of the scan in the Prometheus text format.
+fn prometheus(report: &ScanReport) -> String {
+    let mut output = String::from(
+        "# HELP rtls_gateway_up Gateway detected by the last scan\n\
+         # TYPE rtls_gateway_up gauge\n",
+    );
+    for gateway in &report.gateways {
+        output.push_str(&format!(
+            "rtls_gateway_up{{ip=\"{}\",mac=\"{}\",type=\"{}\"}} 1\n",
+            gateway.ip,
+            gateway.mac,
+            gateway.gateway.as_str()
+        ));
+    }
+    output.push_str(
+        "# HELP rtls_scan_gateways Gateways found by the last scan\n\
+         # TYPE rtls_scan_gateways gauge\n",
+    );
+    for kind in [GatewayType::G1, GatewayType::MG3] {
+        let count = report
+            .gateways
+            .iter()
+            .filter(|gateway| gateway.gateway == kind)
+            .count();
+        output.push_str(&format!(
+            "rtls_scan_gateways{{type=\"{}\"}} {}\n",
+            kind.as_str(),
+            count
+        ));
+    }
+    output.push_str(&format!(
+        "# HELP rtls_scan_errors Failed probes reported by the last scan, with --include-errors\n\
+         # TYPE rtls_scan_errors gauge\n\
+         rtls_scan_errors {}\n",
+        report.errors.len()
+    ));
+    // Left out of canonical reports, which have no timing
+    let finished = report.scan.as_ref().and_then(ScanInfo::finished_at);
+    if let Some(finished) = finished {
+        output.push_str(&format!(
+            "# HELP rtls_scan_timestamp_seconds When the last scan finished\n\
+             # TYPE rtls_scan_timestamp_seconds gauge\n\
+             rtls_scan_timestamp_seconds {}\n",
+            finished
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        ));
+    }
+    output
+}
+
+/// Gateways, and failed probes when there are any, as left aligned columns. Gateway types are
+/// colored (G1 cyan, MG3 magenta), macs found more than once and failures red and gateways only
+/// known from their announcement, not confirmed over http, dimmed.
+fn table(report: &ScanReport, color: bool) -> String {
+    let paint = |code: &str, text: String| {
+        if color && !code.is_empty() {
+            format!("{}{}\x1b[0m", code, text)
+        } else {
+            text
+        }
+    };
+    let mut seen = HashSet::new();
+    let duplicates: HashSet<Mac> = report
+        .gateways
+        .iter()
+        .map(|gateway| gateway.mac)
+        .filter(|mac| !seen.insert(*mac))
+        .collect();
+    let gateways: Vec<Vec<String>> = report
+        .gateways
+        .iter()
+        .map(|gateway| {
+            vec![
+                gateway.ip.to_string(),
+                gateway.mac.to_string(),
+                gateway.gateway.as_str().to_string(),
+                gateway
+                    .rtt_ms
+                    .map_or_else(|| "-".to_string(), |rtt| format!("{:.1} ms", rtt)),
+            ]
+        })
+        .collect();
+    let mut output = String::new();
+    for (row, cells) in columns(&["IP", "MAC", "TYPE", "RTT"], &gateways)
+        .into_iter()
+        .enumerate()
+    {
+        let mut cells = cells;
+        if let Some(last) = cells.last_mut() {
+            *last = last.trim_end().to_string();
+        }
+        let line = match row.checked_sub(1).map(|idx| &report.gateways[idx]) {
+            None => paint("\x1b[1m", cells.join("  ")),
+            Some(gateway) => {
+                let dim = if gateway.unconfirmed { "\x1b[2m" } else { "" };
+                let kind = match gateway.gateway {
+                    GatewayType::G1 => "\x1b[36m",
+                    GatewayType::MG3 => "\x1b[35m",
+                };
+                let mac = if duplicates.contains(&gateway.mac) {
+                    "\x1b[31m"
+                } else {
+                    dim
+                };
+                let cells: Vec<String> = cells
+                    .into_iter()
+                    .enumerate()
+                    .map(|(column, cell)| match column {
+                        1 => paint(mac, cell),
+                        2 => paint(&format!("{}{}", dim, kind), cell),
+                        _ => paint(dim, cell),
+                    })
+                    .collect();
+                cells.join("  ")
+            }
+        };
+        output.push_str(&line);
+        output.push('\n');
+    }
+    if !report.errors.is_empty() {
+        let errors: Vec<Vec<String>> = report
+            .errors
+            .iter()
+            .map(|failure| {
+                vec![
+                    failure.ip.to_string(),
+                    failure.code.as_str().to_string(),
+                    failure.message.clone(),
+                ]
+            })
+            .collect();
+        output.push('\n');
+        for (row, cells) in columns(&["IP", "ERROR", "MESSAGE"], &errors)
+            .into_iter()
+            .enumerate()
+        {
+            let code = if row == 0 { "\x1b[1m" } else { "\x1b[31m" };
+            output.push_str(&paint(code, cells.join("  ").trim_end().to_string()));
+            output.push('\n');
+        }
+    }
+    output
+}
+
+/// The header and rows with every cell padded to the width of its column.
+fn columns(header: &[&str], rows: &[Vec<String>]) -> Vec<Vec<String>> {
+    let header: Vec<String> = header.iter().map(|title| title.to_string()).collect();
+    let lines: Vec<&Vec<String>> = std::iter::once(&header).chain(rows).collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            lines
+                .iter()
+                .map(|cells| cells[column].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    lines
+        .into_iter()
+        .map(|cells| {
+            cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rtls_ctl::parse;
+
+    use super::*;
+
+    /// A report with every optional field of a gateway set.
+    fn report() -> ScanReport {
+        let mut gateway = GatewayDetection::new(
+            Ipv4Addr::new(192, 168, 1, 20),
+            GatewayType::MG3,
+            parse::mac("AC:23:3F:00:00:01").unwrap(),
+        );
+        gateway.interface = Some("eth0.10".to_string());
+        gateway.vlan = Some(10);
+        gateway.hostname = Some("gw-1.local".to_string());
+        gateway.port = Some(8080);
+        gateway.rtt_ms = Some(12.5);
+        gateway.firmware = Some("1.2.3".to_string());
+        gateway.model = Some("MG3".to_string());
+        gateway.vendor = Some("Minew".to_string());
+        let g1 = GatewayDetection::new(
+            Ipv4Addr::new(192, 168, 1, 21),
+            GatewayType::G1,
+            parse::mac("AC:23:3F:00:00:02").unwrap(),
+        );
+        ScanReport::new(vec![gateway, g1])
+    }
+
+    fn v1(format: OutputFormat) -> String {
+        Rendering {
+            format,
+            compat: Compat::V1,
+            color: false,
+            inventory: &[],
+            template: None,
+        }
+        .render(&report())
+    }
+
+    #[test]
+    fn compat_v1_json_matches_fixture() {
+        assert_eq!(
+            v1(OutputFormat::Json),
+            include_str!("../../tests/fixtures/compat_v1.json")
+        );
+    }
+
+    #[test]
+    fn compat_v1_yaml_matches_fixture() {
+        assert_eq!(
+            v1(OutputFormat::Yaml),
+            include_str!("../../tests/fixtures/compat_v1.yaml")
+        );
+    }
+
+    #[test]
+    fn table_dims_only_unconfirmed_gateways() {
+        let mut report = report();
+        // --deterministic clears the timings of confirmed gateways
+        report.canonicalize();
+        assert!(!table(&report, true).contains("\x1b[2m"));
+        report.gateways[1].unconfirmed = true;
+        let table = table(&report, true);
+        let dimmed: Vec<&str> = table
+            .lines()
+            .filter(|line| line.contains("\x1b[2m"))
+            .collect();
+        assert_eq!(dimmed.len(), 1);
+        assert!(dimmed[0].contains("192.168.1.21"));
+    }
+
+    #[test]
+    fn prom_timestamp_comes_from_the_scan() {
+        let mut report = report();
+        report.scan = Some(ScanInfo {
+            started_at: Some("2026-01-01T00:00:00Z".to_string()),
+            duration_ms: Some(2500),
+            ..ScanInfo::new("192.168.1.0/24".to_string(), 80)
+        });
+        assert!(prometheus(&report).contains("rtls_scan_timestamp_seconds 1767225602\n"));
+        report.canonicalize();
+        let canonical = prometheus(&report);
+        assert!(!canonical.contains("rtls_scan_timestamp_seconds"));
+        assert_eq!(canonical, prometheus(&report));
+    }
+
+    #[test]
+    fn junit_timestamp_comes_from_the_scan() {
+        let mut report = report();
+        let inventory = [(report.gateways[0].mac, None)];
+        report.scan = Some(ScanInfo {
+            started_at: Some("2026-01-01T00:00:00Z".to_string()),
+            ..ScanInfo::new("192.168.1.0/24".to_string(), 80)
+        });
+        assert!(junit(&report, &inventory).contains(" timestamp=\"2026-01-01T00:00:00Z\">"));
+        report.canonicalize();
+        assert!(!junit(&report, &inventory).contains("timestamp="));
+    }
+
+    #[test]
+    fn dot_groups_gateways_by_scanned_target() {
+        let mut report = report();
+        report.scan = Some(ScanInfo::new(
+            "192.168.1.10..192.168.1.21,gw-2.local".to_string(),
+            80,
+        ));
+        let dot = dot(&report);
+        assert!(dot.contains("\"192.168.1.10..192.168.1.21\" -> \"192.168.1.20\";"));
+        // Not in any range, so found through the hostname
+        assert!(dot.contains("\" -> \"192.168.1.21\";"));
+        assert!(!dot.contains("192.168.1.10..192.168.1.21\" -> \"192.168.1.21\""));
+        assert!(!dot.contains("/24"));
+    }
+}
@@ -0,0 +1,12 @@
+//! `--porcelain` output: one tab separated record per line, starting with the record kind.
+//!
+//! Fields are only ever appended to a record, so scripts can rely on their position.
+
+/// Prints one record, replacing tabs and newlines inside fields with spaces.
+pub fn print_record(fields: &[&str]) {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| field.replace(['\t', '\n', '\r'], " "))
+        .collect();
+    println!("{}", fields.join("\t"));
+}
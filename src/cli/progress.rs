@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// How often progress is reported while work is ongoing.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// One json object per line on stderr
+    Json,
+}
+
+#[derive(Debug, Serialize)]
+struct ProgressEvent {
+    event: &'static str,
+    percent: f64,
+    done: usize,
+    total: usize,
+    found: usize,
+    elapsed_ms: u128,
+    eta_ms: Option<u128>,
+}
+
+/// Reports how much of a batch of hosts is done, at most once per [`INTERVAL`] plus once at the
+/// end.
+pub struct Progress {
+    format: Option<ProgressFormat>,
+    total: usize,
+    done: usize,
+    found: usize,
+    started: Instant,
+    last_report: Instant,
+}
+
+impl Progress {
+    pub fn new(format: Option<ProgressFormat>, total: usize) -> Self {
+        let now = Instant::now();
+        Self {
+            format,
+            total,
+            done: 0,
+            found: 0,
+            started: now,
+            last_report: now,
+        }
+    }
+
+    /// Marks one host as done, `found` when it is a gateway or succeeded.
+    pub fn advance(&mut self, found: bool) {
+        self.done += 1;
+        if found {
+            self.found += 1;
+        }
+        if self.done == self.total || self.last_report.elapsed() >= INTERVAL {
+            self.report();
+        }
+    }
+
+    fn report(&mut self) {
+        self.last_report = Instant::now();
+        let Some(ProgressFormat::Json) = self.format else {
+            return;
+        };
+        let elapsed = self.started.elapsed();
+        let event = ProgressEvent {
+            event: "progress",
+            percent: if self.total == 0 {
+                100.0
+            } else {
+                (self.done as f64 * 1000.0 / self.total as f64).round() / 10.0
+            },
+            done: self.done,
+            total: self.total,
+            found: self.found,
+            elapsed_ms: elapsed.as_millis(),
+            eta_ms: (self.done > 0).then(|| {
+                elapsed.as_millis() * (self.total - self.done) as u128 / self.done as u128
+            }),
+        };
+        eprintln!(
+            "{}",
+            serde_json::to_string(&event).expect("Progress must be serializable")
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::{collections::HashSet, net::Ipv4Addr, path::PathBuf, time::Duration};
+
+use clap::Args;
+use futures::StreamExt;
+use rtls_ctl::{
+    client::GatewayClient,
+    duration::parse_duration,
+    error::{ClientError, ErrorCode},
+    limits::{retry, RateLimiter},
+    report::ScanReport,
+    target::IpRange,
+    types::{GatewayDetection, GatewayType, Mac},
+};
+use serde::Serialize;
+
+use super::{
+    confirm::confirm,
+    global::GlobalOptions,
+    http::{ConnectionArgs, Http},
+    porcelain::print_record,
+    progress::Progress,
+    settings::LoadedConfig,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const CONCURRENCY: usize = 16;
+
+#[derive(Args, Debug)]
+pub struct RebootArgs {
+    /// Scan report listing the gateways to reboot
+    report: PathBuf,
+    /// Only reboot these gateways (may be repeated)
+    #[arg(long = "ip")]
+    ips: Vec<Ipv4Addr>,
+    /// Http port of the gateways [default: 80]
+    #[arg(short, long, env = "RTLS_CTL_PORT")]
+    port: Option<u16>,
+    /// Timeout for each request (e.g. 500ms, 10s, 2m) [default: 10s]
+    #[arg(short, long, value_parser = parse_duration)]
+    timeout: Option<Duration>,
+    /// Extra attempts for gateways that time out or drop the connection [default: 0]
+    #[arg(long, env = "RTLS_CTL_RETRIES")]
+    retries: Option<u32>,
+    /// Maximum number of requests started per second [default: unlimited]
+    #[arg(long, env = "RTLS_CTL_RATE_LIMIT")]
+    rate_limit: Option<u32>,
+    /// Don't ask for confirmation
+    #[arg(short, long)]
+    yes: bool,
+    #[command(flatten)]
+    connection: ConnectionArgs,
+}
+
+/// Outcome of a management action on one gateway.
+#[derive(Debug, Serialize)]
+struct ActionResult {
+    ip: Ipv4Addr,
+    mac: Mac,
+    gateway: GatewayType,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<ErrorCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+pub async fn run(
+    args: RebootArgs,
+    config: &LoadedConfig,
+    global: GlobalOptions,
+) -> anyhow::Result<()> {
+    let report = ScanReport::load(&args.report)?;
+    let gateways: Vec<GatewayDetection> = report
+        .gateways
+        .iter()
+        .filter(|gateway| args.ips.is_empty() || args.ips.contains(&gateway.ip))
+        .cloned()
+        .collect();
+    if gateways.is_empty() {
+        anyhow::bail!("No gateways selected");
+    }
+    confirm(&summary(&report, &gateways), args.yes)?;
+
+    let port = args
+        .port
+        .or_else(|| config.lookup(|c| c.scan.port).map(|(port, _)| port))
+        .unwrap_or(80);
+    let timeout = args
+        .timeout
+        .or_else(|| config.lookup(|c| c.defaults.timeout).map(|(t, _)| t))
+        .unwrap_or(TIMEOUT);
+    let concurrency = config
+        .lookup(|c| c.defaults.concurrency)
+        .map_or(CONCURRENCY, |(c, _)| c);
+    let retries = args
+        .retries
+        .or_else(|| config.lookup(|c| c.defaults.retries).map(|(r, _)| r))
+        .unwrap_or(0);
+    let limiter = RateLimiter::new(
+        args.rate_limit
+            .or_else(|| config.lookup(|c| c.defaults.rate_limit).map(|(r, _)| r)),
+    );
+    let limiter = &limiter;
+    let http = Http::new(
+        &global,
+        args.connection
+            .apply(global.http_client(config)?, config)
+            .timeout(timeout),
+    )?;
+    let http = &http;
+    let mut progress = Progress::new(global.progress, gateways.len());
+    let mut results: Vec<ActionResult> = futures::stream::iter(gateways)
+        .map(|gateway| async move {
+            let mut target = gateway.clone();
+            target.port.get_or_insert(port);
+            let result = retry(retries, limiter, || reboot(http, &target)).await;
+            if let Err(err) = &result {
+                log::warn!("Error rebooting {}: {:#}", gateway.ip, err);
+            }
+            ActionResult {
+                ip: gateway.ip,
+                mac: gateway.mac,
+                gateway: gateway.gateway,
+                ok: result.is_ok(),
+                code: result.as_ref().err().map(ClientError::code),
+                message: result.err().map(|err| err.to_string()),
+            }
+        })
+        .buffer_unordered(concurrency)
+        .inspect(|result| progress.advance(result.ok))
+        .collect()
+        .await;
+
+    http.finish()?;
+    if global.deterministic {
+        results.sort_by_key(|result| result.ip);
+    }
+    let failed = results.iter().filter(|result| !result.ok).count();
+    if global.porcelain {
+        print_porcelain(&results);
+    } else {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&results).expect("Results must be serializable")
+        );
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} gateways failed to reboot", failed, results.len());
+    }
+    Ok(())
+}
+
+/// `About to reboot 47 gateways across 3 targets`, counting the targets of the scan that found
+/// the gateways. Gateways of a report without them are only counted.
+fn summary(report: &ScanReport, gateways: &[GatewayDetection]) -> String {
+    let Some(targets) = report.targets() else {
+        return format!("About to reboot {} gateways", gateways.len());
+    };
+    let ranges: HashSet<IpRange> = gateways
+        .iter()
+        .map(|gateway| {
+            targets
+                .ranges()
+                .iter()
+                .find(|range| range.contains(gateway.ip))
+                .copied()
+                // Resolved from a hostname target
+                .unwrap_or_else(|| IpRange::from(gateway.ip))
+        })
+        .collect();
+    format!(
+        "About to reboot {} gateways across {} targets",
+        gateways.len(),
+        ranges.len()
+    )
+}
+
+/// `ok <ip> <type> <mac>` and `failed <ip> <type> <mac> <code> <message>` records.
+fn print_porcelain(results: &[ActionResult]) {
+    for result in results {
+        let (ip, mac) = (result.ip.to_string(), result.mac.to_string());
+        let mut fields = vec![
+            if result.ok { "ok" } else { "failed" },
+            &ip,
+            result.gateway.as_str(),
+            &mac,
+        ];
+        if let Some(code) = result.code {
+            fields.push(code.as_str());
+        }
+        if let Some(message) = &result.message {
+            fields.push(message);
+        }
+        print_record(&fields);
+    }
+}
+
+async fn reboot(http: &Http, gateway: &GatewayDetection) -> Result<(), ClientError> {
+    // G1 gateways can't be rebooted over http, so their password is never sent
+    GatewayClient::new(gateway, http.transport.clone(), "")
+        .reboot()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use rtls_ctl::{parse, report::ScanInfo};
+
+    use super::*;
+
+    fn gateway(ip: [u8; 4]) -> GatewayDetection {
+        GatewayDetection::new(
+            Ipv4Addr::from(ip),
+            GatewayType::MG3,
+            parse::mac("AC:23:3F:00:00:01").unwrap(),
+        )
+    }
+
+    #[test]
+    fn summary_counts_scanned_targets() {
+        // One /23 target spans two /24s
+        let gateways = vec![
+            gateway([10, 0, 0, 5]),
+            gateway([10, 0, 1, 5]),
+            gateway([10, 2, 0, 5]),
+        ];
+        let mut report = ScanReport::new(gateways.clone());
+        report.scan = Some(ScanInfo::new("10.0.0.0/23,10.2.0.0/16".to_string(), 80));
+        assert_eq!(
+            summary(&report, &gateways),
+            "About to reboot 3 gateways across 2 targets"
+        );
+        report.scan = None;
+        assert_eq!(summary(&report, &gateways), "About to reboot 3 gateways");
+    }
+}
@@ -0,0 +1,40 @@
+use std::num::NonZeroUsize;
+
+use anyhow::Context;
+
+/// Targets a single worker thread keeps busy.
+const TARGETS_PER_WORKER: u64 = 256;
+/// Threads for blocking work (dns lookups, file and keyring access) unless configured.
+const MAX_BLOCKING_THREADS: usize = 64;
+
+/// Builds the runtime commands run on.
+///
+/// Without `worker_threads` a scan of `targets` addresses gets one worker per
+/// [`TARGETS_PER_WORKER`] targets, up to the number of cpus, so small scans on embedded boxes stay
+/// lean. Other commands use every cpu.
+pub fn build(
+    worker_threads: Option<u16>,
+    max_blocking_threads: Option<u16>,
+    targets: Option<u64>,
+) -> anyhow::Result<tokio::runtime::Runtime> {
+    let cpus = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let workers = match (worker_threads, targets) {
+        (Some(threads), _) => usize::from(threads),
+        (None, Some(targets)) => {
+            usize::try_from(targets / TARGETS_PER_WORKER).map_or(cpus, |n| n.clamp(1, cpus))
+        }
+        (None, None) => cpus,
+    };
+    let blocking = max_blocking_threads.map_or(MAX_BLOCKING_THREADS, usize::from);
+    log::debug!(
+        "Using {} worker threads and up to {} blocking threads",
+        workers,
+        blocking
+    );
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(workers)
+        .max_blocking_threads(blocking)
+        .enable_all()
+        .build()
+        .context("Error starting async runtime")
+}
@@ -0,0 +1,1406 @@
+use anyhow::Context;
+use clap::{ArgMatches, Args, ValueEnum};
+use log::info;
+use rtls_ctl::diagnostics;
+use rtls_ctl::discovery;
+use rtls_ctl::dns;
+use rtls_ctl::duration::parse_duration;
+use rtls_ctl::error::ProbeFailure;
+use rtls_ctl::interfaces::{self, Interface};
+use rtls_ctl::keychain::Secret;
+use rtls_ctl::oui::OuiTable;
+use rtls_ctl::parse;
+use rtls_ctl::report::{ScanInfo, ScanReport};
+use rtls_ctl::scanner::{
+    ScanConfig, ScanEvent, Scanner, CONCURRENCY, G1_USERNAME, PORT, RETRIES, TIMEOUT,
+};
+use rtls_ctl::simulator::MINEW_OUI;
+use rtls_ctl::snmp;
+use rtls_ctl::target::{IpRange, Subnet, Targets};
+use rtls_ctl::transport::{PortRange, SocketOptions};
+use rtls_ctl::types::{GatewayDetection, GatewayType, Mac};
+use rtls_ctl::wol;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use std::{net::Ipv4Addr, time::Duration};
+
+use futures::StreamExt;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use super::cache::DetectionCache;
+use super::checkpoint::Checkpoint;
+use super::db;
+use super::diff;
+use super::global::GlobalOptions;
+use super::http::Http;
+use super::output::{self, OutputFormat, Rendering};
+use super::porcelain::print_record;
+use super::progress::Progress;
+use super::settings::{LoadedConfig, Setting};
+use super::template::Template;
+use super::trace_file::TraceFile;
+
+/// Reverse lookups in flight at once
+const RDNS_CONCURRENCY: usize = 32;
+
+#[derive(Args, Debug)]
+pub struct ScanArgs {
+    #[arg(
+        env = "RTLS_CTL_RANGE",
+        help = "Addresses to scan, comma separated ranges (192.168.1.1..192.168.1.20), subnets (192.168.1.0/24), ips or hostnames. Default is the subnet of every local interface."
+    )]
+    pub range: Vec<String>,
+    /// More addresses to scan, merged with the positional ones (repeatable)
+    #[arg(long = "range", value_name = "RANGE")]
+    pub ranges: Vec<String>,
+    /// Read more targets from this file, one range, subnet, ip or hostname per line ('-' for
+    /// stdin). Empty lines and lines starting with '#' are skipped
+    #[arg(long, env = "RTLS_CTL_TARGETS_FILE")]
+    pub targets_file: Option<PathBuf>,
+    /// Only probe the hosts leased by a DHCP server, from its ISC dhcpd or dnsmasq lease file
+    #[arg(long, env = "RTLS_CTL_FROM_DHCP_LEASES")]
+    pub from_dhcp_leases: Option<PathBuf>,
+    /// Only probe the hosts an nmap scan (-oX xml output) found with one of the ports open
+    #[arg(long, env = "RTLS_CTL_FROM_NMAP")]
+    pub from_nmap: Option<PathBuf>,
+    /// Only probe the hosts with a gateway vendor mac in the ARP table of this SNMP agent, like a
+    /// router or core switch (ip or ip:port)
+    #[arg(long, env = "RTLS_CTL_FROM_SNMP")]
+    pub from_snmp: Option<String>,
+    /// SNMP v2c community for --from-snmp
+    #[arg(
+        long,
+        env = "RTLS_CTL_SNMP_COMMUNITY",
+        default_value = "public",
+        hide_env_values = true
+    )]
+    pub community: String,
+    /// Probe the gateways of an earlier scan's output first
+    #[arg(long, env = "RTLS_CTL_SEED")]
+    pub seed: Option<PathBuf>,
+    /// Only probe the gateways of the --seed report
+    #[arg(long, requires = "seed")]
+    pub seed_only: bool,
+    /// Send Wake-on-LAN magic packets to the gateways of --seed and --cache before probing them
+    #[arg(long, env = "RTLS_CTL_WOL")]
+    pub wol: bool,
+    /// How long woken gateways get to bring their network up before the scan starts
+    #[arg(long, default_value = "10s", value_parser = parse_duration, requires = "wol")]
+    pub wol_wait: Duration,
+    /// Probe the gateways found by earlier scans first and remember the ones found by this one,
+    /// in ~/.cache/rtls-ctl/gateways.json
+    #[arg(long, env = "RTLS_CTL_CACHE")]
+    pub cache: bool,
+    /// Detection cache to use instead of the default, implies --cache
+    #[arg(long, env = "RTLS_CTL_CACHE_FILE")]
+    pub cache_file: Option<PathBuf>,
+    /// Addresses to skip, in the same forms as the range (repeatable)
+    #[arg(long, env = "RTLS_CTL_EXCLUDE")]
+    pub exclude: Vec<Targets>,
+    /// How hosts are found before fingerprinting, arp, mdns and syn only probe the targets
+    /// that answer
+    #[arg(long, value_enum, env = "RTLS_CTL_DISCOVERY", default_value_t = Discovery::Tcp)]
+    pub discovery: Discovery,
+    /// Service types browsed by mdns discovery (repeatable) [default: _rtls._tcp]
+    #[arg(long, env = "RTLS_CTL_MDNS_SERVICE", value_delimiter = ',')]
+    pub mdns_service: Vec<String>,
+    /// Only fingerprint hosts answering an ICMP echo sweep, skipped when ICMP sockets aren't
+    /// permitted
+    #[arg(long, env = "RTLS_CTL_PRE_PING")]
+    pub pre_ping: bool,
+    /// Http port of the gateways [default: 80]
+    #[arg(short, long, env = "RTLS_CTL_PORT")]
+    pub port: Option<u16>,
+    /// Http ports to try on every host, recording the one each gateway answered on
+    /// (e.g. 80,8080,443)
+    #[arg(
+        long,
+        env = "RTLS_CTL_PORTS",
+        value_delimiter = ',',
+        conflicts_with = "port"
+    )]
+    pub ports: Vec<u16>,
+    /// Number of hosts probed at once [default: 512]
+    #[arg(short, long, env = "RTLS_CTL_CONCURRENCY")]
+    pub concurrency: Option<usize>,
+    /// Timeout for each probe stage (e.g. 500ms, 10s, 2m) [default: 3s]
+    #[arg(short, long, env = "RTLS_CTL_TIMEOUT", value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+    /// Extra attempts for hosts that time out or drop the connection [default: 0]
+    #[arg(long, env = "RTLS_CTL_RETRIES")]
+    pub retries: Option<u32>,
+    /// Maximum number of probes started per second [default: unlimited]
+    #[arg(long, env = "RTLS_CTL_RATE_LIMIT")]
+    pub rate_limit: Option<u32>,
+    /// Username for G1 gateways [default: admin]
+    #[arg(long, env = "RTLS_CTL_G1_USERNAME")]
+    pub g1_username: Option<String>,
+    /// Password for G1 gateways, prefer the environment variable over the flag [default: empty]
+    #[arg(long, env = "RTLS_CTL_G1_PASSWORD", hide_env_values = true)]
+    pub g1_password: Option<String>,
+    /// Record every probe attempt (timing, http status, response body) to this NDJSON file
+    #[arg(long, env = "RTLS_CTL_TRACE_FILE")]
+    pub trace_file: Option<PathBuf>,
+    /// Save the progress of the scan to this file every few seconds, removed once it completes
+    #[arg(long, env = "RTLS_CTL_CHECKPOINT")]
+    pub checkpoint: Option<PathBuf>,
+    /// Continue the scan saved in the --checkpoint file, skipping the addresses it probed
+    #[arg(long, requires = "checkpoint")]
+    pub resume: bool,
+    /// Include every failed probe with its error code in the output
+    #[arg(long, env = "RTLS_CTL_INCLUDE_ERRORS")]
+    pub include_errors: bool,
+    /// Output structure to emit, v1 is the bare array of gateways written by older versions
+    #[arg(long, value_enum, env = "RTLS_CTL_COMPAT", default_value_t = Compat::V2)]
+    pub compat: Compat,
+    /// Output format of the results [default: table on a terminal, json otherwise]
+    #[arg(long, value_enum, env = "RTLS_CTL_FORMAT")]
+    pub format: Option<OutputFormat>,
+    /// Print a line per gateway from this template instead, like '{{ip:15}} {{mac}} {{gateway}}'
+    #[arg(long, env = "RTLS_CTL_TEMPLATE", conflicts_with = "format")]
+    pub template: Option<Template>,
+    /// Expected gateways for --format junit, a mac per line optionally followed by a name
+    #[arg(long, env = "RTLS_CTL_INVENTORY")]
+    pub inventory: Option<PathBuf>,
+    /// Print how the gateways changed since this earlier scan report instead of the results
+    #[arg(long, env = "RTLS_CTL_DIFF_AGAINST")]
+    pub diff_against: Option<PathBuf>,
+    /// Append the results to this SQLite database, keeping a history of every scan
+    #[arg(long, env = "RTLS_CTL_DB")]
+    pub db: Option<PathBuf>,
+    /// Write the results to this file instead of stdout, replacing it only once they are complete
+    #[arg(short, long, env = "RTLS_CTL_OUTPUT")]
+    pub output: Option<PathBuf>,
+    /// Local ports tcp probes connect from (e.g. 40000-40999) [default: any]
+    #[arg(long, env = "RTLS_CTL_SOURCE_PORTS")]
+    pub source_ports: Option<PortRange>,
+    /// DSCP value (0-63) marked on tcp probes for QoS classification
+    #[arg(long, env = "RTLS_CTL_DSCP", value_parser = clap::value_parser!(u8).range(0..=63))]
+    pub dscp: Option<u8>,
+    /// Drop tcp probes whose data stays unacknowledged this long, linux only (e.g. 2s)
+    #[arg(long, env = "RTLS_CTL_TCP_USER_TIMEOUT", value_parser = parse_duration)]
+    pub tcp_user_timeout: Option<Duration>,
+    /// Interface to probe from, its subnet is the default range [default: every interface]
+    #[arg(long, env = "RTLS_CTL_INTERFACE")]
+    pub interface: Option<String>,
+    /// Scan the subnet of every VLAN sub-interface (eth0.10, vlan20, ...) instead of a range
+    #[arg(long, conflicts_with_all = ["range", "ranges", "targets_file", "interface"])]
+    pub all_vlans: bool,
+    /// Scan the subnets of the VLAN sub-interfaces with these ids (e.g. 110,120)
+    #[arg(
+        long,
+        env = "RTLS_CTL_VLAN",
+        value_delimiter = ',',
+        value_parser = clap::value_parser!(u16).range(1..=4094),
+        conflicts_with_all = ["range", "ranges", "targets_file", "interface", "all_vlans"]
+    )]
+    pub vlan: Vec<u16>,
+    /// Probe the addresses of each range in random order instead of ascending
+    #[arg(long, env = "RTLS_CTL_RANDOMIZE")]
+    pub randomize: bool,
+    /// Seed for --randomize, to repeat the order of an earlier scan [default: random, logged]
+    #[arg(long, requires = "randomize")]
+    pub randomize_seed: Option<u64>,
+    /// File of OUIs and vendor names (IEEE oui.txt or Wireshark manuf) extending the bundled
+    /// table used for the vendor of each gateway
+    #[arg(long, env = "RTLS_CTL_OUI_FILE")]
+    pub oui_file: Option<PathBuf>,
+    /// Only report gateways whose mac vendor contains this text, ignoring case (e.g. minew)
+    #[arg(long, env = "RTLS_CTL_ONLY_VENDOR")]
+    pub only_vendor: Option<String>,
+    /// Only report gateways of this type, G1 or MG3
+    #[arg(long = "type", env = "RTLS_CTL_TYPE")]
+    pub gateway_type: Option<GatewayType>,
+    /// Only report gateways whose mac starts with this prefix (e.g. AC:23:3F)
+    #[arg(long, env = "RTLS_CTL_MAC_PREFIX", value_parser = parse_mac_prefix)]
+    pub mac_prefix: Option<String>,
+    /// Only report gateways in this subnet (repeatable, e.g. 10.3.0.0/16)
+    #[arg(long, env = "RTLS_CTL_IP_IN", value_delimiter = ',')]
+    pub ip_in: Vec<Subnet>,
+    /// Announce every gateway found over mDNS as a _rtls-gw._tcp service
+    #[arg(long, env = "RTLS_CTL_ANNOUNCE")]
+    pub announce: bool,
+    /// Look up the hostname of every gateway found in reverse DNS, 32 lookups at a time
+    #[arg(long, visible_alias = "resolve-names", env = "RTLS_CTL_RDNS")]
+    pub rdns: bool,
+}
+
+impl ScanArgs {
+    /// Every range given on the command line as one comma separated list.
+    fn targets(&self) -> Option<String> {
+        let targets: Vec<&str> = self
+            .range
+            .iter()
+            .chain(&self.ranges)
+            .map(String::as_str)
+            .collect();
+        (!targets.is_empty()).then(|| targets.join(","))
+    }
+
+    fn cache_path(&self) -> anyhow::Result<Option<PathBuf>> {
+        match &self.cache_file {
+            Some(path) => Ok(Some(path.clone())),
+            None if self.cache => DetectionCache::default_path()
+                .context("No cache directory found, please give --cache-file")
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether a detection passes the filters of the results.
+    fn keeps(&self, detection: &GatewayDetection) -> bool {
+        let vendor = self.only_vendor.as_ref().is_none_or(|wanted| {
+            detection
+                .vendor
+                .as_ref()
+                .is_some_and(|vendor| vendor.to_lowercase().contains(&wanted.to_lowercase()))
+        });
+        let kind = self
+            .gateway_type
+            .is_none_or(|kind| kind == detection.gateway);
+        let mac = self
+            .mac_prefix
+            .as_ref()
+            .is_none_or(|prefix| hex::encode_upper(detection.mac.bytes).starts_with(prefix));
+        let ip = self.ip_in.is_empty()
+            || self
+                .ip_in
+                .iter()
+                .any(|subnet| subnet.contains(detection.ip));
+        vendor && kind && mac && ip
+    }
+
+    fn excluded(&self) -> Targets {
+        self.exclude.iter().cloned().collect()
+    }
+
+    /// Number of addresses the scan will probe, when known from the command line alone.
+    pub fn target_count(&self) -> Option<u64> {
+        if self.all_vlans || !self.vlan.is_empty() || self.targets_file.is_some() {
+            return None;
+        }
+        // Without a range the local subnets are scanned
+        let range = self.targets()?;
+        Targets::from_str(&range)
+            .ok()
+            .map(|targets| targets.without(&self.excluded()).len())
+    }
+
+    fn socket_options(&self, interface: Option<&Interface>) -> SocketOptions {
+        SocketOptions {
+            source_ports: self.source_ports,
+            dscp: self.dscp,
+            tcp_user_timeout: self.tcp_user_timeout,
+            source_ip: interface.map(|interface| interface.ip),
+            // Set from the global options by `Http`
+            proxy: None,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Discovery {
+    /// Probe every target over tcp
+    Tcp,
+    /// Probe the targets that answer an ARP sweep
+    Arp,
+    /// Probe the targets advertising a gateway service over mDNS
+    Mdns,
+    /// Probe every target over tcp and add the G1 gateways answering a udp broadcast
+    Broadcast,
+    /// Probe the targets with the port open in a raw SYN sweep, needs root or CAP_NET_RAW
+    Syn,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compat {
+    V1,
+    V2,
+}
+
+/// Scan options after merging the command line, environment and config file.
+#[derive(Debug)]
+pub struct ScanSettings {
+    pub range: Setting<Option<String>>,
+    pub port: Setting<u16>,
+    pub concurrency: Setting<usize>,
+    pub timeout: Setting<Duration>,
+    pub retries: Setting<u32>,
+    pub rate_limit: Setting<Option<u32>>,
+    pub g1_username: Setting<String>,
+    pub g1_password: Setting<String>,
+}
+
+impl ScanSettings {
+    pub fn resolve(args: &ScanArgs, matches: &ArgMatches, config: &LoadedConfig) -> Self {
+        // Credentials come from a single layer so a profile's username is never combined
+        // with the password of another site.
+        let (g1, g1_keyring) = if args.g1_username.is_some() || args.g1_password.is_some() {
+            (None, None)
+        } else {
+            (
+                config.lookup(|c| c.credentials.g1.clone()),
+                config.secret(Secret::G1Password),
+            )
+        };
+        Self {
+            range: Setting::resolve(
+                matches,
+                "range",
+                args.targets().map(Some),
+                config.lookup(|c| c.scan.range.clone().map(Some)),
+                || None,
+            ),
+            port: Setting::resolve(
+                matches,
+                "port",
+                args.port,
+                config.lookup(|c| c.scan.port),
+                || PORT,
+            ),
+            concurrency: Setting::resolve(
+                matches,
+                "concurrency",
+                args.concurrency,
+                config.lookup(|c| c.scan.concurrency.or(c.defaults.concurrency)),
+                || CONCURRENCY,
+            ),
+            timeout: Setting::resolve(
+                matches,
+                "timeout",
+                args.timeout,
+                config.lookup(|c| c.scan.timeout.or(c.defaults.timeout)),
+                || TIMEOUT,
+            ),
+            retries: Setting::resolve(
+                matches,
+                "retries",
+                args.retries,
+                config.lookup(|c| c.defaults.retries),
+                || RETRIES,
+            ),
+            rate_limit: Setting::resolve(
+                matches,
+                "rate_limit",
+                args.rate_limit.map(Some),
+                config.lookup(|c| c.defaults.rate_limit.map(Some)),
+                || None,
+            ),
+            g1_username: Setting::resolve(
+                matches,
+                "g1_username",
+                args.g1_username.clone(),
+                g1.clone().map(|(credentials, source)| {
+                    let username = credentials.username.unwrap_or_else(|| G1_USERNAME.into());
+                    (username, source)
+                }),
+                || G1_USERNAME.to_string(),
+            ),
+            g1_password: Setting::resolve(
+                matches,
+                "g1_password",
+                args.g1_password.clone(),
+                g1_keyring.or_else(|| {
+                    g1.map(|(credentials, source)| {
+                        (credentials.password.unwrap_or_default(), source)
+                    })
+                }),
+                String::new,
+            ),
+        }
+    }
+}
+
+/// The scanner of a command, with the transport and trace file it was built from.
+pub struct ProbeContext {
+    pub scanner: Scanner,
+    pub http: Http,
+    pub trace: Option<Arc<TraceFile>>,
+}
+
+impl ProbeContext {
+    /// Probes with `config` through `http`, recording every attempt to `trace`.
+    pub fn new(config: ScanConfig, http: Http, trace: Option<TraceFile>) -> anyhow::Result<Self> {
+        let trace = trace.map(Arc::new);
+        let mut scanner = Scanner::with_transport(config, http.transport.clone())
+            .context("Invalid scan options")?;
+        if let Some(trace) = trace.clone() {
+            scanner = scanner.on_attempt(move |attempt| trace.record_attempt(attempt));
+        }
+        Ok(Self {
+            scanner,
+            http,
+            trace,
+        })
+    }
+
+    /// Context for probing single hosts outside of a scan, with the port, timeout and G1
+    /// credentials of the config.
+    pub fn for_host(
+        config: &LoadedConfig,
+        global: &GlobalOptions,
+        port: Option<u16>,
+    ) -> anyhow::Result<Self> {
+        let g1 = config
+            .lookup(|c| c.credentials.g1.clone())
+            .map(|(credentials, _)| credentials)
+            .unwrap_or_default();
+        let scan = ScanConfig {
+            port: port
+                .or_else(|| config.lookup(|c| c.scan.port).map(|(port, _)| port))
+                .unwrap_or(PORT),
+            timeout: config
+                .lookup(|c| c.scan.timeout.or(c.defaults.timeout))
+                .map_or(TIMEOUT, |(timeout, _)| timeout),
+            g1_username: g1.username.unwrap_or_else(|| G1_USERNAME.to_string()),
+            g1_password: config
+                .secret(Secret::G1Password)
+                .map(|(password, _)| password)
+                .or(g1.password)
+                .unwrap_or_default(),
+            ..ScanConfig::default()
+        };
+        let http = Http::new(global, global.http_client(config)?)?;
+        Self::new(scan, http, None)
+    }
+
+    /// Writes the trace file, and the cassette when recording.
+    pub fn finish(&self) -> anyhow::Result<()> {
+        if let Some(trace) = &self.trace {
+            trace.flush()?;
+        }
+        self.http.finish()
+    }
+}
+
+pub async fn run(
+    args: ScanArgs,
+    matches: &ArgMatches,
+    config: &LoadedConfig,
+    global: GlobalOptions,
+) -> anyhow::Result<()> {
+    let started = SystemTime::now();
+    let settings = RunSettings::resolve(&args, matches, config, &global)?;
+    let plan = plan_targets(&args, &settings).await?;
+    let scanned = plan.scanned.clone();
+    let (results, errors) = probe_targets(&args, &settings, plan, config, &global).await?;
+    write_report(
+        &args, &settings, results, errors, &scanned, started, &global,
+    )
+    .await
+}
+
+/// The options of a scan run that are settled before any target is built: the merged scan
+/// settings, where and how the report is written, and the ports and interface to scan.
+struct RunSettings {
+    scan: ScanSettings,
+    /// Report to print the changes since, instead of the report
+    previous: Option<ScanReport>,
+    inventory: Vec<(Mac, Option<String>)>,
+    format: OutputFormat,
+    /// Whether every result is printed as it is found
+    streaming: bool,
+    port: u16,
+    extra_ports: Vec<u16>,
+    interface: Option<Interface>,
+}
+
+impl RunSettings {
+    fn resolve(
+        args: &ScanArgs,
+        matches: &ArgMatches,
+        config: &LoadedConfig,
+        global: &GlobalOptions,
+    ) -> anyhow::Result<Self> {
+        let scan = ScanSettings::resolve(args, matches, config);
+        // Read before scanning, so a missing report doesn't waste the scan
+        let previous = args
+            .diff_against
+            .as_deref()
+            .map(ScanReport::load)
+            .transpose()?;
+        let inventory = match &args.inventory {
+            Some(path) => read_inventory(path)?,
+            None if args.format == Some(OutputFormat::Junit) => {
+                anyhow::bail!("--format junit needs the expected gateways from --inventory")
+            }
+            None => Vec::new(),
+        };
+        let format = match (args.format, &args.output) {
+            (Some(format), _) => format,
+            (None, Some(_)) => OutputFormat::Json,
+            (None, None) => OutputFormat::default_for_stdout(),
+        };
+        // A file is only renamed into place once complete, and canonical output can only be
+        // sorted once every gateway is known
+        let streaming = format == OutputFormat::Ndjson
+            && !global.porcelain
+            && !global.deterministic
+            && args.output.is_none()
+            && previous.is_none();
+        if args.rdns {
+            global.require_network("--rdns")?;
+        }
+        let (port, extra_ports) = match args.ports.split_first() {
+            Some((first, rest)) => (*first, rest.to_vec()),
+            None => (scan.port.value, Vec::new()),
+        };
+        let interface = args.interface.as_deref().map(find_interface).transpose()?;
+        Ok(Self {
+            scan,
+            previous,
+            inventory,
+            format,
+            streaming,
+            port,
+            extra_ports,
+            interface,
+        })
+    }
+}
+
+/// The addresses of a scan run, in the order they are probed, and what was learned about them
+/// while building them.
+struct ScanPlan {
+    targets: Vec<ScanTarget>,
+    /// Number of leading targets holding the known gateways, probed before the rest
+    first: usize,
+    /// Addresses of the seeded and cached gateways
+    known: Targets,
+    /// Every address probed
+    scanned: Targets,
+    /// Hostname targets the addresses were resolved from
+    names: HashMap<Ipv4Addr, String>,
+    /// Gateways that answered the discovery broadcast
+    announced: Vec<GatewayDetection>,
+    /// Hostnames that didn't resolve, with --include-errors
+    errors: Vec<ProbeFailure>,
+    seed: Option<Vec<GatewayDetection>>,
+    cache: Option<DetectionCache>,
+}
+
+/// Builds the targets from the range, files and interfaces, narrows them to the candidates and
+/// the hosts found by discovery, and puts the known gateways first.
+async fn plan_targets(args: &ScanArgs, settings: &RunSettings) -> anyhow::Result<ScanPlan> {
+    let timeout = settings.scan.timeout.value;
+    // Hosts known to be up from another source, scanning is limited to them
+    let leases = args
+        .from_dhcp_leases
+        .as_deref()
+        .map(read_dhcp_leases)
+        .transpose()?;
+    let nmap = args
+        .from_nmap
+        .as_deref()
+        .map(|path| {
+            read_nmap(
+                path,
+                std::iter::once(settings.port).chain(settings.extra_ports.iter().copied()),
+            )
+        })
+        .transpose()?;
+    let snmp = match &args.from_snmp {
+        Some(agent) => Some(read_snmp(agent, &args.community, timeout).await?),
+        None => None,
+    };
+    let seed = args.seed.as_deref().map(read_seed).transpose()?;
+    let cache = args.cache_path()?.map(|path| DetectionCache::load(&path));
+    let only_seed = seed.as_deref().filter(|_| args.seed_only).map(addresses);
+    let candidates = [leases, nmap, snmp, only_seed]
+        .into_iter()
+        .flatten()
+        .reduce(|candidates, other| candidates.intersect(&other));
+    let mut targets = if args.all_vlans || !args.vlan.is_empty() {
+        vlan_targets(&args.vlan)?
+    } else {
+        let range = match &args.targets_file {
+            Some(path) => {
+                let file = read_targets_file(path)?;
+                match args.targets() {
+                    Some(range) => Some([Targets::from_str(&range)?, file].into_iter().collect()),
+                    None => Some(file),
+                }
+            }
+            None => match (&candidates, &settings.scan.range.value) {
+                (_, Some(range)) => Some(Targets::from_str(range)?),
+                // Without a range the candidates are the targets
+                (Some(candidates), None) => Some(candidates.clone()),
+                (None, None) => None,
+            },
+        };
+        match range {
+            Some(range) => vec![ScanTarget {
+                range,
+                interface: None,
+            }],
+            None => match &settings.interface {
+                Some(interface) => vec![ScanTarget {
+                    range: interface.subnet.hosts().into(),
+                    interface: Some(interface.clone()),
+                }],
+                None => local_targets()?,
+            },
+        }
+    };
+    if let Some(candidates) = &candidates {
+        for target in &mut targets {
+            target.range = target.range.intersect(candidates);
+        }
+    }
+    let mut errors = Vec::new();
+    let names = resolve_targets(&mut targets, args.include_errors.then_some(&mut errors)).await;
+    let excluded = args.excluded();
+    if !excluded.is_empty() {
+        info!("Excluding {}", excluded);
+        for target in &mut targets {
+            target.range = target.range.without(&excluded);
+        }
+    }
+    let announced = discover(args, settings, &mut targets).await?;
+    if args.pre_ping {
+        for target in &mut targets {
+            match diagnostics::ping_sweep(target.range.clone(), timeout).await {
+                Ok(replied) => {
+                    info!("{} hosts of {} answered ping", replied.len(), target.range);
+                    target.range = Targets::new(replied.into_iter().map(|ip| ip.into()).collect());
+                }
+                Err(err) => {
+                    log::warn!("Probing every host, ping sweep failed: {:#}", err);
+                    break;
+                }
+            }
+        }
+    }
+    for target in &targets {
+        match &target.interface {
+            Some(interface) => info!("Scanning range {} on {}...", target.range, interface.name),
+            None => info!("Scanning range {}...", target.range),
+        }
+    }
+
+    // Known gateways are probed before the rest of the targets
+    let mut known = Vec::new();
+    if let Some(seed) = seed.as_deref().filter(|_| !args.seed_only) {
+        let seed = addresses(seed).without(&excluded);
+        info!("Probing the {} seeded gateways first", seed.len());
+        known.push(seed);
+    }
+    if let Some(cache) = &cache {
+        // Cached gateways outside of the targets are left alone
+        let scanning: Targets = targets.iter().map(|target| target.range.clone()).collect();
+        let cached = cache.addresses().intersect(&scanning);
+        info!("Probing the {} cached gateways first", cached.len());
+        known.push(cached);
+    }
+    let known: Targets = known.into_iter().collect();
+    let mut first = 0;
+    if !known.is_empty() {
+        for target in &mut targets {
+            target.range = target.range.without(&known);
+        }
+        targets.insert(
+            0,
+            ScanTarget {
+                range: known.clone(),
+                interface: None,
+            },
+        );
+        first = 1;
+    }
+    let scanned = targets.iter().map(|target| target.range.clone()).collect();
+    Ok(ScanPlan {
+        targets,
+        first,
+        known,
+        scanned,
+        names,
+        announced,
+        errors,
+        seed,
+        cache,
+    })
+}
+
+/// Narrows the targets to the hosts answering `--discovery`, returning the gateways that
+/// answered the G1 broadcast.
+async fn discover(
+    args: &ScanArgs,
+    settings: &RunSettings,
+    targets: &mut [ScanTarget],
+) -> anyhow::Result<Vec<GatewayDetection>> {
+    let timeout = settings.scan.timeout.value;
+    let mut announced = Vec::new();
+    match args.discovery {
+        Discovery::Tcp => {}
+        Discovery::Broadcast => {
+            for target in targets.iter() {
+                let gateways = discovery::g1_broadcast(target.broadcast(), timeout).await?;
+                info!("{} gateways answered the broadcast", gateways.len());
+                announced.extend(
+                    gateways
+                        .into_iter()
+                        .filter(|(ip, _)| target.range.contains(*ip))
+                        .map(|(ip, mac)| {
+                            let mut detection = GatewayDetection::new(ip, GatewayType::G1, mac);
+                            detection.unconfirmed = true;
+                            target.tag(detection)
+                        }),
+                );
+            }
+        }
+        Discovery::Arp => {
+            for target in targets.iter_mut() {
+                let hosts = discovery::arp_sweep(target.range.clone(), timeout).await?;
+                info!("{} hosts of {} answered ARP", hosts.len(), target.range);
+                target.range = Targets::new(hosts.into_iter().map(|(ip, _)| ip.into()).collect());
+            }
+        }
+        Discovery::Syn => {
+            for target in targets.iter_mut() {
+                let open =
+                    discovery::syn_scan(target.range.clone(), settings.port, timeout).await?;
+                info!(
+                    "{} hosts of {} have the port open",
+                    open.len(),
+                    target.range
+                );
+                target.range = Targets::new(open.into_iter().map(|ip| ip.into()).collect());
+            }
+        }
+        Discovery::Mdns => {
+            let services = if args.mdns_service.is_empty() {
+                vec![discovery::MDNS_SERVICE.to_string()]
+            } else {
+                args.mdns_service.clone()
+            };
+            let hosts = discovery::mdns_browse(&services, timeout).await?;
+            for target in targets.iter_mut() {
+                let answered: Vec<IpRange> = hosts
+                    .iter()
+                    .filter(|ip| target.range.contains(**ip))
+                    .map(|ip| (*ip).into())
+                    .collect();
+                info!("{} hosts of {} answered mDNS", answered.len(), target.range);
+                target.range = Targets::new(answered);
+            }
+        }
+    }
+    Ok(announced)
+}
+
+/// Probes every address of the plan, resuming from and recording to the checkpoint, and
+/// updates the cache with the gateways found.
+async fn probe_targets(
+    args: &ScanArgs,
+    settings: &RunSettings,
+    plan: ScanPlan,
+    config: &LoadedConfig,
+    global: &GlobalOptions,
+) -> anyhow::Result<(Vec<GatewayDetection>, Vec<ProbeFailure>)> {
+    let ScanPlan {
+        mut targets,
+        first,
+        known,
+        scanned,
+        names,
+        announced,
+        mut errors,
+        seed,
+        mut cache,
+    } = plan;
+    let streaming = settings.streaming;
+    if args.wol {
+        let sleeping: Vec<&GatewayDetection> = seed
+            .iter()
+            .flatten()
+            .chain(cache.iter().flat_map(|cache| cache.gateways()))
+            .filter(|gateway| scanned.contains(gateway.ip))
+            .collect();
+        wake_known(&sleeping, args.wol_wait).await?;
+    }
+    let mut results = Vec::new();
+    let mut checkpoint = match &args.checkpoint {
+        Some(path) if args.resume => {
+            let (checkpoint, gateways, failures) = Checkpoint::resume(path)?;
+            let completed = checkpoint.completed();
+            info!(
+                "Resuming scan, skipping {} probed addresses",
+                completed.len()
+            );
+            for target in &mut targets {
+                target.range = target.range.without(&completed);
+            }
+            if streaming {
+                gateways.iter().for_each(print_line);
+                failures.iter().for_each(print_line);
+            }
+            results.extend(gateways);
+            errors.extend(failures);
+            Some(checkpoint)
+        }
+        Some(path) => Some(Checkpoint::new(path)),
+        None => None,
+    };
+    let total: u64 = targets.iter().map(|target| target.range.len()).sum();
+    let scan = ScanConfig::builder()
+        .range(targets.iter().map(|target| target.range.clone()).collect())
+        .port(settings.port)
+        .extra_ports(settings.extra_ports.clone())
+        .concurrency(settings.scan.concurrency.value)
+        .timeout(settings.scan.timeout.value)
+        .retries(settings.scan.retries.value)
+        .rate_limit(settings.scan.rate_limit.value)
+        .g1_credentials(
+            settings.scan.g1_username.value.clone(),
+            settings.scan.g1_password.value.clone(),
+        )
+        .sockets(args.socket_options(settings.interface.as_ref()))
+        .build()
+        .context("Invalid scan options")?;
+    let http = Http::with_socket_options(
+        global,
+        match &settings.interface {
+            Some(interface) => global
+                .http_client(config)?
+                .local_address(IpAddr::V4(interface.ip)),
+            None => global.http_client(config)?,
+        },
+        scan.sockets.clone(),
+    )?;
+    let trace = args
+        .trace_file
+        .as_deref()
+        .map(TraceFile::create)
+        .transpose()?;
+    let progress = Mutex::new(Progress::new(global.progress, total as usize));
+    let mut context = ProbeContext::new(scan, http, trace)?;
+    context.scanner = context.scanner.on_event(move |event| {
+        let found = match event {
+            ScanEvent::Detection(_) => true,
+            ScanEvent::ProbeError { .. } => false,
+            _ => return,
+        };
+        progress
+            .lock()
+            .expect("Progress lock must not be poisoned")
+            .advance(found);
+    });
+    let scanner = &context.scanner;
+    let concurrency = (settings.scan.concurrency.value / (targets.len() - first).max(1)).max(1);
+    let mut order = args.randomize.then(|| {
+        let seed = args.randomize_seed.unwrap_or_else(rand::random);
+        info!("Randomizing the scan order with seed {}", seed);
+        StdRng::seed_from_u64(seed)
+    });
+    let ouis = match &args.oui_file {
+        Some(path) => OuiTable::load(path)?,
+        None => OuiTable::builtin(),
+    };
+    let identify = |mut detection: GatewayDetection| {
+        detection.vendor = ouis.vendor(detection.mac).map(String::from);
+        detection
+    };
+    let identify = &identify;
+    let names = &names;
+    let mut streams: Vec<_> = targets
+        .iter()
+        .enumerate()
+        .map(|(idx, target)| {
+            let concurrency = if idx < first {
+                settings.scan.concurrency.value
+            } else {
+                concurrency
+            };
+            let addresses: Box<dyn Iterator<Item = Ipv4Addr> + Send> = match &mut order {
+                Some(rng) => {
+                    let mut addresses: Vec<Ipv4Addr> = target.range.clone().into_iter().collect();
+                    addresses.shuffle(rng);
+                    Box::new(addresses.into_iter())
+                }
+                None => Box::new(target.range.clone().into_iter()),
+            };
+            Box::pin(
+                scanner
+                    .probe(addresses, concurrency)
+                    .map(move |(ip, result, elapsed)| {
+                        let result = result.map(|mut detection| {
+                            detection.hostname = names.get(&ip).cloned();
+                            target.tag(identify(detection))
+                        });
+                        (ip, result, elapsed)
+                    }),
+            )
+        })
+        .collect();
+    let rest = streams.split_off(first);
+    // Streamed gateways are printed with their name, the buffered report is resolved at the end
+    let rdns = args.rdns && streaming;
+    let mut probes = futures::stream::select_all(streams)
+        .chain(futures::stream::select_all(rest))
+        .map(|(ip, result, _)| async move {
+            let result = match result {
+                Ok(mut detection)
+                    if rdns && detection.hostname.is_none() && args.keeps(&detection) =>
+                {
+                    detection.hostname = dns::reverse_lookup(ip).await;
+                    Ok(detection)
+                }
+                result => result,
+            };
+            (ip, result)
+        })
+        .buffered(RDNS_CONCURRENCY);
+    while let Some((ip, result)) = probes.next().await {
+        match result {
+            Ok(detection) if !args.keeps(&detection) => {
+                log::debug!("Leaving out {} at {}, filtered", detection.mac, ip);
+            }
+            Ok(detection) => {
+                if streaming {
+                    print_line(&detection);
+                }
+                if known.contains(ip) {
+                    info!(
+                        "Found known {} gateway {} at {}",
+                        detection.gateway.as_str(),
+                        detection.mac,
+                        ip
+                    );
+                }
+                results.push(detection);
+            }
+            Err(err) => {
+                let failure = ProbeFailure::new(ip, &err);
+                log::trace!("Error: [{}] {}", failure.code, failure.message);
+                if args.include_errors {
+                    if streaming {
+                        print_line(&failure);
+                    }
+                    errors.push(failure);
+                }
+            }
+        }
+        if let Some(checkpoint) = &mut checkpoint {
+            checkpoint.complete(ip, &results, &errors)?;
+        }
+    }
+    if let Some(checkpoint) = checkpoint {
+        checkpoint.finish()?;
+    }
+    for detection in announced.into_iter().map(identify) {
+        // Gateways blocking tcp are only known from their answer to the broadcast
+        if !results.iter().any(|found| found.ip == detection.ip) && args.keeps(&detection) {
+            errors.retain(|failure| failure.ip != detection.ip);
+            if streaming {
+                print_line(&detection);
+            }
+            results.push(detection);
+        }
+    }
+    info!("Scan ended finding {} gateways", results.len());
+    if let Some(cache) = &mut cache {
+        cache.update(&scanned, &results);
+        if let Err(err) = cache.save() {
+            log::warn!("{:#}", err);
+        }
+    }
+    context.finish()?;
+    Ok((results, errors))
+}
+
+/// Completes the results with reverse DNS and announces them, then writes the report, or the
+/// changes since `--diff-against`, in the requested format.
+async fn write_report(
+    args: &ScanArgs,
+    settings: &RunSettings,
+    mut results: Vec<GatewayDetection>,
+    errors: Vec<ProbeFailure>,
+    scanned: &Targets,
+    started: SystemTime,
+    global: &GlobalOptions,
+) -> anyhow::Result<()> {
+    if args.rdns && !settings.streaming {
+        resolve_hostnames(&mut results).await;
+    }
+
+    if args.announce {
+        discovery::announce(&results).await?;
+    }
+
+    let mut report = ScanReport::new(results);
+    report.errors = errors;
+    report.scan = Some(ScanInfo {
+        started_at: Some(humantime::format_rfc3339_seconds(started).to_string()),
+        duration_ms: started
+            .elapsed()
+            .ok()
+            .map(|elapsed| elapsed.as_millis() as u64),
+        ..ScanInfo::new(scanned.to_string(), settings.port)
+    });
+    if global.deterministic {
+        report.canonicalize();
+    }
+    if let Some(path) = &args.db {
+        db::append(path, &report, started)?;
+    }
+    let rendering = Rendering {
+        format: settings.format,
+        compat: args.compat,
+        color: global.color(),
+        inventory: &settings.inventory,
+        template: args.template.as_ref(),
+    };
+    if let Some(previous) = &settings.previous {
+        diff::print_changes(&report.changes_since(previous), global);
+        return Ok(());
+    }
+    if let Some(path) = &args.output {
+        let rendering = Rendering {
+            color: false,
+            ..rendering
+        };
+        return output::write_atomic(path, &rendering.render(&report));
+    }
+    if global.porcelain {
+        print_porcelain(&report);
+        return Ok(());
+    }
+    if settings.streaming {
+        // Every line was printed as it was found
+        return Ok(());
+    }
+    print!("{}", rendering.render(&report));
+
+    Ok(())
+}
+
+/// Replaces the hostnames of every target with the addresses they resolve to, returning the
+/// name each address came from. Hostnames that don't resolve are logged and skipped.
+async fn resolve_targets(
+    targets: &mut [ScanTarget],
+    mut errors: Option<&mut Vec<ProbeFailure>>,
+) -> HashMap<Ipv4Addr, String> {
+    let mut names = HashMap::new();
+    for target in targets {
+        if target.range.hostnames().is_empty() {
+            continue;
+        }
+        let hostnames = target.range.hostnames();
+        let resolved = futures::future::join_all(hostnames.iter().map(|host| dns::resolve(host)));
+        let mut ranges = target.range.ranges().to_vec();
+        for (host, result) in hostnames.iter().zip(resolved.await) {
+            match result {
+                Ok(ips) => {
+                    info!("Resolved {} to {:?}", host, ips);
+                    for ip in ips {
+                        names.entry(ip).or_insert_with(|| host.clone());
+                        ranges.push(IpRange::from(ip));
+                    }
+                }
+                Err(err) => {
+                    log::warn!("{:#}", err);
+                    if let Some(errors) = errors.as_deref_mut() {
+                        errors.push(ProbeFailure::unresolved(host, &err));
+                    }
+                }
+            }
+        }
+        target.range = Targets::new(ranges);
+    }
+    names
+}
+
+/// Fills in the hostname of every detection from reverse DNS, keeping the names of hostname
+/// targets.
+async fn resolve_hostnames(detections: &mut [GatewayDetection]) {
+    let names: Vec<Option<String>> = futures::stream::iter(detections.iter().map(|d| d.ip))
+        .map(dns::reverse_lookup)
+        .buffered(RDNS_CONCURRENCY)
+        .collect()
+        .await;
+    for (detection, name) in detections.iter_mut().zip(names) {
+        if detection.hostname.is_none() {
+            detection.hostname = name;
+        }
+    }
+}
+
+/// Addresses to scan and, for local subnets, the interface they are attached to.
+pub struct ScanTarget {
+    pub range: Targets,
+    pub interface: Option<Interface>,
+}
+
+impl ScanTarget {
+    /// Broadcast address of the target's interface, the limited broadcast without one.
+    fn broadcast(&self) -> Ipv4Addr {
+        self.interface
+            .as_ref()
+            .map_or(Ipv4Addr::BROADCAST, |interface| {
+                interface.subnet.broadcast()
+            })
+    }
+
+    fn tag(&self, mut detection: GatewayDetection) -> GatewayDetection {
+        if let Some(interface) = &self.interface {
+            detection.interface = Some(interface.name.clone());
+            detection.vlan = interface.vlan();
+        }
+        detection
+    }
+}
+
+/// Subnets with shorter prefixes are too large to scan unless asked for explicitly
+const MIN_LOCAL_PREFIX: u8 = 16;
+
+/// The first ipv4 address of the interface called `name`.
+fn find_interface(name: &str) -> anyhow::Result<Interface> {
+    interfaces::list()?
+        .into_iter()
+        .find(|interface| interface.name == name)
+        .with_context(|| format!("No interface {} with an ipv4 address found", name))
+}
+
+/// The hosts of the subnet of every up, non-loopback interface, the targets when no range is
+/// given.
+pub fn local_targets() -> anyhow::Result<Vec<ScanTarget>> {
+    let mut targets: Vec<ScanTarget> = Vec::new();
+    for interface in interfaces::list()? {
+        if !interface.up || interface.loopback {
+            continue;
+        }
+        if interface.subnet.prefix() < MIN_LOCAL_PREFIX {
+            log::warn!(
+                "Skipping {} on {}, give it as the range to scan it",
+                interface.subnet,
+                interface.name
+            );
+            continue;
+        }
+        // Interfaces can have several addresses on the same subnet
+        if targets.iter().any(|target| {
+            target
+                .interface
+                .as_ref()
+                .is_some_and(|other| other.subnet == interface.subnet)
+        }) {
+            continue;
+        }
+        targets.push(ScanTarget {
+            range: interface.subnet.hosts().into(),
+            interface: Some(interface),
+        });
+    }
+    if targets.is_empty() {
+        anyhow::bail!("No local interface with an ipv4 address found. Please specify a range");
+    }
+    Ok(targets)
+}
+
+/// Targets listed one per line in `path`, or in stdin for `-`.
+fn read_targets_file(path: &Path) -> anyhow::Result<Targets> {
+    let contents = if path == Path::new("-") {
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .context("Error reading targets from stdin")?;
+        contents
+    } else {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("Error reading targets file {}", path.display()))?
+    };
+    contents
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(idx, line)| {
+            Targets::from_str(line)
+                .with_context(|| format!("Error parsing line {} of {}", idx + 1, path.display()))
+        })
+        .collect()
+}
+
+/// The leased addresses of a DHCP server's lease file.
+fn read_dhcp_leases(path: &Path) -> anyhow::Result<Targets> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Error reading lease file {}", path.display()))?;
+    let leases = parse::dhcp_leases(&contents);
+    info!("Found {} leases in {}", leases.len(), path.display());
+    Ok(Targets::new(
+        leases.into_iter().map(|(ip, _)| ip.into()).collect(),
+    ))
+}
+
+/// The addresses of the ARP table entries of an SNMP agent with the gateway vendor's mac prefix.
+async fn read_snmp(agent: &str, community: &str, timeout: Duration) -> anyhow::Result<Targets> {
+    let agent = match agent.parse::<SocketAddr>() {
+        Ok(agent) => agent,
+        Err(_) => SocketAddr::from((
+            agent
+                .parse::<Ipv4Addr>()
+                .with_context(|| format!("'{}' is not an ip or ip:port", agent))?,
+            snmp::PORT,
+        )),
+    };
+    let entries = snmp::arp_table(agent, community, timeout).await?;
+    let hosts: Vec<IpRange> = entries
+        .iter()
+        .filter(|(_, mac)| mac.bytes[..3] == MINEW_OUI)
+        .map(|(ip, _)| (*ip).into())
+        .collect();
+    info!(
+        "{} of the {} ARP entries of {} are gateways",
+        hosts.len(),
+        entries.len(),
+        agent
+    );
+    Ok(Targets::new(hosts))
+}
+
+/// The addresses of the gateways in a scan report.
+fn read_seed(path: &Path) -> anyhow::Result<Vec<GatewayDetection>> {
+    let report = ScanReport::load(path)?;
+    info!(
+        "Seeding scan with {} gateways from {}",
+        report.gateways.len(),
+        path.display()
+    );
+    Ok(report.gateways)
+}
+
+fn addresses(gateways: &[GatewayDetection]) -> Targets {
+    Targets::new(gateways.iter().map(|gateway| gateway.ip.into()).collect())
+}
+
+/// Sends a Wake-on-LAN magic packet to every gateway, to the broadcast address of the local subnet
+/// it is on or the limited broadcast, then gives them `wait` to come up.
+async fn wake_known(gateways: &[&GatewayDetection], wait: Duration) -> anyhow::Result<()> {
+    if gateways.is_empty() {
+        log::warn!("No known gateways to wake, give --seed or --cache with --wol");
+        return Ok(());
+    }
+    let local = interfaces::list()?;
+    let mut woken = Vec::new();
+    for gateway in gateways {
+        if woken.contains(&gateway.mac) {
+            continue;
+        }
+        let broadcast = local
+            .iter()
+            .find(|interface| !interface.loopback && interface.subnet.contains(gateway.ip))
+            .map_or(Ipv4Addr::BROADCAST, |interface| {
+                interface.subnet.broadcast()
+            });
+        wol::wake(gateway.mac, broadcast, wol::PORT).await?;
+        woken.push(gateway.mac);
+    }
+    info!(
+        "Sent magic packets to {} gateways, waiting {} for them to wake",
+        woken.len(),
+        humantime::format_duration(wait)
+    );
+    tokio::time::sleep(wait).await;
+    Ok(())
+}
+
+/// A mac prefix as upper case hex digits without separators, so it can be compared with the
+/// start of a mac's hex.
+fn parse_mac_prefix(s: &str) -> Result<String, String> {
+    let digits: String = s
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect::<String>()
+        .to_ascii_uppercase();
+    if digits.is_empty() || digits.len() > 12 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not a mac prefix like AC:23:3F", s));
+    }
+    Ok(digits)
+}
+
+fn read_inventory(path: &Path) -> anyhow::Result<Vec<(Mac, Option<String>)>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Error reading inventory {}", path.display()))?;
+    parse::inventory(&contents).with_context(|| format!("Invalid inventory {}", path.display()))
+}
+
+/// The hosts of an nmap xml report with any of `ports` open.
+fn read_nmap(path: &Path, ports: impl Iterator<Item = u16>) -> anyhow::Result<Targets> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Error reading nmap report {}", path.display()))?;
+    let ports: Vec<u16> = ports.collect();
+    let hosts: Vec<IpRange> = parse::nmap_xml(&contents)
+        .into_iter()
+        .filter(|(_, open)| open.iter().any(|port| ports.contains(port)))
+        .map(|(ip, _)| ip.into())
+        .collect();
+    info!(
+        "Found {} hosts with port {:?} open in {}",
+        hosts.len(),
+        ports,
+        path.display()
+    );
+    Ok(Targets::new(hosts))
+}
+
+/// The hosts of the subnet of every VLAN sub-interface with one of the `ids`, or of all of them
+/// without ids. Subnets are directly attached, so the routing table sends their probes out of the
+/// right interface.
+///
+/// Sub-interfaces are not created here since they need an address on the VLAN's subnet, which
+/// only whoever manages the network knows.
+fn vlan_targets(ids: &[u16]) -> anyhow::Result<Vec<ScanTarget>> {
+    let vlans = interfaces::vlans()?;
+    for id in ids {
+        if !vlans.iter().any(|interface| interface.vlan() == Some(*id)) {
+            anyhow::bail!(
+                "No sub-interface with an ipv4 address found for VLAN {id}, create one like \
+                 `ip link add link eth0 name eth0.{id} type vlan id {id}` and give it an address",
+                id = id
+            );
+        }
+    }
+    let targets: Vec<ScanTarget> = vlans
+        .into_iter()
+        .filter(|interface| ids.is_empty() || interface.vlan().is_some_and(|id| ids.contains(&id)))
+        .map(|interface| ScanTarget {
+            range: interface.subnet.hosts().into(),
+            interface: Some(interface),
+        })
+        .collect();
+    if targets.is_empty() {
+        anyhow::bail!("No VLAN sub-interfaces with an ipv4 address found");
+    }
+    Ok(targets)
+}
+
+/// Prints a gateway or failure of `--format ndjson` as soon as it is known.
+fn print_line(value: &impl Serialize) {
+    print!("{}", output::ndjson_line(value));
+}
+
+/// `gateway <ip> <type> <mac>` and `error <ip> <code> <message>` records.
+fn print_porcelain(report: &ScanReport) {
+    for gateway in &report.gateways {
+        print_record(&[
+            "gateway",
+            &gateway.ip.to_string(),
+            gateway.gateway.as_str(),
+            &gateway.mac.to_string(),
+        ]);
+    }
+    for failure in &report.errors {
+        print_record(&[
+            "error",
+            &failure.ip.to_string(),
+            failure.code.as_str(),
+            &failure.message,
+        ]);
+    }
+}
@@ -0,0 +1,131 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{parser::ValueSource, ArgMatches};
+use rtls_ctl::{config::Config, keychain::Secret, validation::Validate};
+
+/// Where an effective setting came from, in order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    CommandLine,
+    Env,
+    Keyring,
+    Profile,
+    ConfigFile,
+    Default,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Source::CommandLine => "command line",
+            Source::Env => "environment",
+            Source::Keyring => "keyring",
+            Source::Profile => "profile",
+            Source::ConfigFile => "config file",
+            Source::Default => "default",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Setting<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Setting<T> {
+    /// Picks the value for the arg `id` with precedence cli/env > config (see
+    /// [`LoadedConfig::lookup`]) > default.
+    pub fn resolve(
+        matches: &ArgMatches,
+        id: &str,
+        arg: Option<T>,
+        config: Option<(T, Source)>,
+        default: impl FnOnce() -> T,
+    ) -> Self {
+        if let Some(value) = arg {
+            let source = match matches.value_source(id) {
+                Some(ValueSource::EnvVariable) => Source::Env,
+                _ => Source::CommandLine,
+            };
+            Self { value, source }
+        } else if let Some((value, source)) = config {
+            Self { value, source }
+        } else {
+            Self {
+                value: default(),
+                source: Source::Default,
+            }
+        }
+    }
+}
+
+/// The config file in effect, or the default config when there is no file.
+#[derive(Debug, Default)]
+pub struct LoadedConfig {
+    pub path: Option<PathBuf>,
+    pub config: Config,
+    /// The selected profile and its settings as a config layer
+    pub profile: Option<(String, Config)>,
+}
+
+impl LoadedConfig {
+    /// Loads `explicit` if given (which must exist), otherwise the default config file if present.
+    pub fn load(explicit: Option<&Path>, profile: Option<&str>) -> anyhow::Result<Self> {
+        let mut loaded = Self::load_file(explicit)?;
+        if let Some(name) = profile {
+            let layer = loaded.config.profile(name)?.as_config();
+            loaded.profile = Some((name.to_string(), layer));
+        }
+        Ok(loaded)
+    }
+
+    /// Looks a value up in the selected profile, then in the top level config.
+    pub fn lookup<T>(&self, get: impl Fn(&Config) -> Option<T>) -> Option<(T, Source)> {
+        self.profile
+            .as_ref()
+            .and_then(|(_, profile)| get(profile))
+            .map(|value| (value, Source::Profile))
+            .or_else(|| get(&self.config).map(|value| (value, Source::ConfigFile)))
+    }
+
+    pub fn profile_name(&self) -> Option<&str> {
+        self.profile.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    /// Reads a secret of the selected profile from the keyring. A keyring that is unavailable
+    /// (e.g. no secret service on a headless box) is treated as empty.
+    pub fn secret(&self, secret: Secret) -> Option<(String, Source)> {
+        match secret.get(self.profile_name()) {
+            Ok(value) => value.map(|value| (value, Source::Keyring)),
+            Err(err) => {
+                log::debug!("Keyring unavailable: {:#}", err);
+                None
+            }
+        }
+    }
+
+    fn load_file(explicit: Option<&Path>) -> anyhow::Result<Self> {
+        let path = match explicit {
+            Some(path) => path.to_path_buf(),
+            None => match Config::default_path().filter(|path| path.exists()) {
+                Some(path) => path,
+                None => return Ok(Self::default()),
+            },
+        };
+        let config = Config::load(&path)?;
+        config
+            .validate()
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        log::debug!("Using config file {}", path.display());
+        Ok(Self {
+            path: Some(path),
+            config,
+            profile: None,
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use std::{collections::BTreeMap, net::Ipv4Addr, path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use clap::Args;
+use rtls_ctl::{
+    duration::parse_duration,
+    report::ScanReport,
+    simulator::{self, Faults, SimulatedCredentials, MINEW_OUI},
+    target::Subnet,
+    types::Mac,
+    validation::Validate,
+};
+
+use super::global::GlobalOptions;
+
+#[derive(Args, Debug)]
+pub struct SimulateArgs {
+    /// Number of simulated G1 gateways
+    #[arg(long, default_value_t = 0)]
+    g1: usize,
+    /// Number of simulated MG3 gateways
+    #[arg(long, default_value_t = 0)]
+    mg3: usize,
+    /// Subnet whose addresses the gateways listen on, one gateway per address
+    #[arg(long, default_value = "127.0.0.0/8")]
+    bind: Subnet,
+    /// Http port every gateway listens on
+    #[arg(short, long, default_value_t = 80)]
+    port: u16,
+    /// Username the G1 gateways accept
+    #[arg(long, default_value = "admin")]
+    g1_username: String,
+    /// Password the G1 gateways accept
+    #[arg(long, default_value = "")]
+    g1_password: String,
+    /// Delay every response by this long (e.g. 200ms)
+    #[arg(long, value_parser = parse_duration)]
+    latency: Option<Duration>,
+    /// Fraction of requests answered with 401
+    #[arg(long, default_value_t = 0.0)]
+    auth_failure_rate: f64,
+    /// Fraction of requests answered with 500
+    #[arg(long, default_value_t = 0.0)]
+    error_rate: f64,
+    /// Fraction of responses with an unexpected json structure
+    #[arg(long, default_value_t = 0.0)]
+    wrong_schema_rate: f64,
+    /// Fraction of responses with the body cut off halfway
+    #[arg(long, default_value_t = 0.0)]
+    truncate_rate: f64,
+    /// TOML file with a `["<ip>"]` table of faults per gateway, replacing the flags above for
+    /// that gateway
+    #[arg(long)]
+    device_faults: Option<PathBuf>,
+}
+
+impl SimulateArgs {
+    fn faults(&self) -> Faults {
+        Faults {
+            latency: self.latency,
+            auth_failure_rate: self.auth_failure_rate,
+            error_rate: self.error_rate,
+            wrong_schema_rate: self.wrong_schema_rate,
+            truncate_rate: self.truncate_rate,
+        }
+    }
+}
+
+fn load_device_faults(path: &PathBuf) -> anyhow::Result<BTreeMap<Ipv4Addr, Faults>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Error reading {}", path.display()))?;
+    let faults: BTreeMap<Ipv4Addr, Faults> = toml::from_str(&contents)
+        .with_context(|| format!("Invalid device faults file {}", path.display()))?;
+    for (ip, faults) in &faults {
+        faults
+            .validate()
+            .with_context(|| format!("Invalid faults for {}", ip))?;
+    }
+    Ok(faults)
+}
+
+/// Prints the report a scan should produce, then serves the gateways until interrupted.
+pub async fn run(args: SimulateArgs, global: GlobalOptions) -> anyhow::Result<()> {
+    if args.g1 + args.mg3 == 0 {
+        anyhow::bail!("Nothing to simulate, pass --g1 and/or --mg3");
+    }
+    let faults = args.faults();
+    faults.validate()?;
+    let device_faults = match &args.device_faults {
+        Some(path) => load_device_faults(path)?,
+        None => BTreeMap::new(),
+    };
+    let mut gateways = simulator::layout(args.g1, args.mg3, &args.bind, args.port)?;
+    let mut macs = Mac::from([MINEW_OUI[0], MINEW_OUI[1], MINEW_OUI[2], 0, 0, 1]).sequence();
+    for gateway in &mut gateways {
+        if global.deterministic {
+            gateway.mac = macs.next().expect("Mac sequence must not run out");
+        }
+        gateway.faults = device_faults
+            .get(&gateway.ip)
+            .cloned()
+            .unwrap_or_else(|| faults.clone());
+    }
+    let report = ScanReport::new(gateways.iter().map(|g| g.detection()).collect());
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&report).expect("Gateways must be serializable")
+    );
+    eprintln!(
+        "Simulating {} gateways on port {}, press ctrl-c to stop",
+        gateways.len(),
+        args.port
+    );
+
+    let credentials = SimulatedCredentials {
+        username: args.g1_username,
+        password: args.g1_password,
+    };
+    tokio::select! {
+        result = simulator::serve(gateways, credentials) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    }
+}
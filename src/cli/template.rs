@@ -0,0 +1,117 @@
+//! Line templates for `--template`, like `{{ip:15}} {{mac}} {{gateway}}`.
+
+use std::str::FromStr;
+
+use rtls_ctl::types::GatewayDetection;
+
+/// Fields of a gateway a template can refer to, as named in the json output.
+const FIELDS: &[&str] = &[
+    "ip",
+    "gateway",
+    "mac",
+    "interface",
+    "vlan",
+    "hostname",
+    "port",
+    "rtt_ms",
+    "firmware",
+    "model",
+    "vendor",
+    "unconfirmed",
+];
+
+/// A line printed per gateway, with `{{field}}` replaced by the field's value. Fields can be
+/// padded to a width with `{{field:15}}`, or right aligned with `{{field:>15}}`. Fields the
+/// gateway doesn't have are empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field {
+        name: &'static str,
+        width: usize,
+        right: bool,
+    },
+}
+
+impl Template {
+    /// The line of a gateway, without a newline.
+    pub fn render(&self, gateway: &GatewayDetection) -> String {
+        let json = serde_json::to_value(gateway).expect("Gateways must be serializable");
+        let mut line = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => line.push_str(text),
+                Part::Field { name, width, right } => {
+                    let value = match &json[*name] {
+                        serde_json::Value::Null => String::new(),
+                        serde_json::Value::String(value) => value.clone(),
+                        value => value.to_string(),
+                    };
+                    if *right {
+                        line.push_str(&format!("{:>width$}", value, width = *width));
+                    } else {
+                        line.push_str(&format!("{:<width$}", value, width = *width));
+                    }
+                }
+            }
+        }
+        line
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| format!("unclosed '{{{{' in '{}'", s))?;
+            parts.push(field(rest[start + 2..start + end].trim())?);
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+}
+
+/// A `name`, `name:width` or `name:>width` placeholder.
+fn field(placeholder: &str) -> Result<Part, String> {
+    let (name, width) = match placeholder.split_once(':') {
+        Some((name, width)) => (name.trim(), Some(width.trim())),
+        None => (placeholder, None),
+    };
+    let name = FIELDS.iter().find(|field| **field == name).ok_or_else(|| {
+        format!(
+            "unknown field '{}', expected one of {}",
+            name,
+            FIELDS.join(", ")
+        )
+    })?;
+    let (width, right) = match width {
+        None => (0, false),
+        Some(width) => {
+            let (width, right) = match width.strip_prefix('>') {
+                Some(width) => (width, true),
+                None => (width.strip_prefix('<').unwrap_or(width), false),
+            };
+            let width = width
+                .parse()
+                .map_err(|_| format!("'{}' is not like {{{{{}:15}}}}", placeholder, name))?;
+            (width, right)
+        }
+    };
+    Ok(Part::Field { name, width, right })
+}
@@ -0,0 +1,268 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use clap::Args;
+use rtls_ctl::{
+    diagnostics,
+    endpoints::Endpoints,
+    error::{ErrorCode, TransportError},
+    parse,
+    transport::{HttpRequest, HttpResponse},
+    types::GatewayType,
+};
+use serde::Serialize;
+
+use super::{
+    global::GlobalOptions, porcelain::print_record, scan::ProbeContext, settings::LoadedConfig,
+};
+
+/// Longest wait for each ttl while counting hops
+const HOP_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Args, Debug)]
+pub struct TraceArgs {
+    /// Address of the gateway
+    ip: Ipv4Addr,
+    /// Http port of the gateway [default: 80]
+    #[arg(short, long, env = "RTLS_CTL_PORT")]
+    port: Option<u16>,
+    /// Most hops tried when counting the hops to the gateway
+    #[arg(long, default_value_t = 30)]
+    max_hops: u8,
+}
+
+/// Outcome of checking one layer.
+#[derive(Debug, Serialize)]
+struct Check {
+    stage: &'static str,
+    /// Unset when the check could not run
+    ok: Option<bool>,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<ErrorCode>,
+}
+
+impl Check {
+    fn passed(stage: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            stage,
+            ok: Some(true),
+            detail: detail.into(),
+            code: None,
+        }
+    }
+
+    fn failed(stage: &'static str, detail: impl Into<String>, code: ErrorCode) -> Self {
+        Self {
+            stage,
+            ok: Some(false),
+            detail: detail.into(),
+            code: Some(code),
+        }
+    }
+
+    fn skipped(stage: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            stage,
+            ok: None,
+            detail: detail.into(),
+            code: None,
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match self.ok {
+            Some(true) => "ok",
+            Some(false) => "failed",
+            None => "skipped",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Diagnosis {
+    ip: Ipv4Addr,
+    port: u16,
+    /// Lowest layer first: arp, icmp, tcp, http, auth
+    checks: Vec<Check>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hops: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gateway: Option<GatewayType>,
+    /// Stage of the lowest failing layer, unset when the gateway is fully reachable
+    failed: Option<&'static str>,
+}
+
+/// Checks every layer between this host and the gateway and reports the lowest one that fails.
+pub async fn run(
+    args: TraceArgs,
+    config: &LoadedConfig,
+    global: GlobalOptions,
+) -> anyhow::Result<()> {
+    let context = ProbeContext::for_host(config, &global, args.port)?;
+    let scan = context.scanner.config();
+    let ip = args.ip;
+
+    let icmp = match diagnostics::ping(ip, scan.timeout).await {
+        Ok(Some(rtt)) => Check::passed("icmp", format!("echo reply in {} ms", rtt.as_millis())),
+        Ok(None) => Check::failed("icmp", "no echo reply", ErrorCode::Timeout),
+        Err(err) => Check::skipped("icmp", format!("{:#}", err)),
+    };
+    let tcp = match context
+        .http
+        .transport
+        .connect(&ip.to_string(), scan.port, scan.timeout)
+        .await
+    {
+        Ok(()) => Check::passed("tcp", format!("port {} accepts connections", scan.port)),
+        Err(err) => Check::failed("tcp", err.to_string(), err.code()),
+    };
+    // Checked after the other probes so the kernel has tried to resolve the address
+    let arp = check_arp(ip);
+    let tcp_ok = tcp.ok == Some(true);
+    let (http, auth, gateway, hops) = if tcp_ok {
+        let (http, auth, gateway) = check_http(&context, ip).await;
+        let hops = diagnostics::hop_count(ip, scan.port, args.max_hops, HOP_TIMEOUT).await;
+        (http, auth, gateway, hops)
+    } else {
+        (
+            Check::skipped("http", "no tcp connection"),
+            Check::skipped("auth", "no tcp connection"),
+            None,
+            None,
+        )
+    };
+    context.finish()?;
+
+    let checks = vec![arp, icmp, tcp, http, auth];
+    // Gateways may drop pings, which only matters when nothing else gets through either
+    let failed = checks
+        .iter()
+        .find(|check| check.ok == Some(false) && !(check.stage == "icmp" && tcp_ok))
+        .map(|check| check.stage);
+    let diagnosis = Diagnosis {
+        ip,
+        port: scan.port,
+        checks,
+        hops,
+        gateway,
+        failed,
+    };
+    if global.porcelain {
+        print_porcelain(&diagnosis);
+    } else {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&diagnosis).expect("Diagnosis must be serializable")
+        );
+    }
+    match failed {
+        Some(stage) => anyhow::bail!("{} is not reachable, the {} check failed", ip, stage),
+        None => Ok(()),
+    }
+}
+
+/// `check <stage> <ok|failed|skipped> <detail>` records followed by `hops <count>` when known and
+/// `result <failed stage|ok>`.
+fn print_porcelain(diagnosis: &Diagnosis) {
+    for check in &diagnosis.checks {
+        print_record(&["check", check.stage, check.status(), &check.detail]);
+    }
+    if let Some(hops) = diagnosis.hops {
+        print_record(&["hops", &hops.to_string()]);
+    }
+    print_record(&["result", diagnosis.failed.unwrap_or("ok")]);
+}
+
+fn check_arp(ip: Ipv4Addr) -> Check {
+    match diagnostics::is_on_link(ip) {
+        Ok(true) => {}
+        Ok(false) => return Check::skipped("arp", "not on a local subnet"),
+        Err(err) => return Check::skipped("arp", format!("{:#}", err)),
+    }
+    match diagnostics::neighbor_mac(ip) {
+        Ok(Some(mac)) => Check::passed("arp", format!("resolved to {}", mac)),
+        Ok(None) => Check::failed(
+            "arp",
+            "no ARP reply, nothing on the local segment has this address",
+            ErrorCode::Connect,
+        ),
+        Err(err) => Check::skipped("arp", format!("{:#}", err)),
+    }
+}
+
+async fn send(
+    context: &ProbeContext,
+    request: HttpRequest,
+) -> Result<HttpResponse, TransportError> {
+    tokio::time::timeout(
+        context.scanner.config().timeout,
+        context.http.transport.send(request),
+    )
+    .await?
+}
+
+/// Tries the MG3 `/hello` endpoint, then the G1 status with credentials.
+async fn check_http(context: &ProbeContext, ip: Ipv4Addr) -> (Check, Check, Option<GatewayType>) {
+    let scan = context.scanner.config();
+    let endpoints = Endpoints::for_ip(ip).with_port(scan.port);
+    let mg3: anyhow::Result<_> = async {
+        let response = send(context, HttpRequest::get(endpoints.mg3_hello()))
+            .await?
+            .error_for_status()?;
+        Ok(parse::mg3_hello(&response.body)?)
+    }
+    .await;
+    let mg3_err = match mg3 {
+        Ok(mac) => {
+            return (
+                Check::passed("http", format!("MG3 /hello answered as {}", mac)),
+                Check::skipped("auth", "MG3 gateways don't use authentication"),
+                Some(GatewayType::MG3),
+            );
+        }
+        Err(err) => err,
+    };
+
+    let g1 = match send(context, context.scanner.g1_status_request(&endpoints)).await {
+        Ok(response) => response,
+        Err(err) => {
+            return (
+                Check::failed(
+                    "http",
+                    format!("no gateway api: {:#}; {}", mg3_err, err),
+                    err.code(),
+                ),
+                Check::skipped("auth", "no gateway api"),
+                None,
+            );
+        }
+    };
+    let http = Check::passed("http", format!("G1 status answered {}", g1.status));
+    let status = g1.status;
+    let auth = match g1.error_for_status() {
+        Ok(response) => match parse::g1_status(&response.body) {
+            Ok(mac) => Check::passed(
+                "auth",
+                format!("{} accepted, G1 is {}", scan.g1_username, mac),
+            ),
+            Err(err) => Check::failed("auth", err.to_string(), err.code()),
+        },
+        Err(err) if err.code == ErrorCode::Auth => Check::failed(
+            "auth",
+            format!("credentials of {} rejected ({})", scan.g1_username, status),
+            ErrorCode::Auth,
+        ),
+        Err(err) => {
+            return (
+                Check::failed(
+                    "http",
+                    format!("no gateway api: {:#}; {}", mg3_err, err),
+                    err.code,
+                ),
+                Check::skipped("auth", "no gateway api"),
+                None,
+            )
+        }
+    };
+    (http, auth, Some(GatewayType::G1))
+}
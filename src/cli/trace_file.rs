@@ -0,0 +1,103 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    net::Ipv4Addr,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use rtls_ctl::{error::ErrorCode, scanner::ProbeAttempt};
+use serde::Serialize;
+
+/// Longest response body kept per record
+const MAX_BODY: usize = 512;
+
+/// One probe attempt against a host.
+#[derive(Debug, Serialize)]
+pub struct TraceRecord<'a> {
+    pub ip: Ipv4Addr,
+    /// `tcp`, `g1` or `mg3`
+    pub stage: &'a str,
+    /// Milliseconds since the start of the scan
+    pub start_ms: u128,
+    pub elapsed_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<ErrorCode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// NDJSON file recording every probe attempt of a scan, for debugging missed gateways.
+pub struct TraceFile {
+    started: Instant,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl TraceFile {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Error creating trace file {}", path.display()))?;
+        Ok(Self {
+            started: Instant::now(),
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    pub fn since_start(&self, instant: Instant) -> Duration {
+        instant.saturating_duration_since(self.started)
+    }
+
+    pub fn record(&self, mut record: TraceRecord) {
+        record.body = record.body.map(truncate);
+        let mut writer = self
+            .writer
+            .lock()
+            .expect("Trace file lock must not be poisoned");
+        let written = serde_json::to_writer(&mut *writer, &record)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.write_all(b"\n"));
+        if let Err(err) = written {
+            log::warn!("Error writing trace file: {}", err);
+        }
+    }
+
+    /// Records a connection or request of the scanner.
+    pub fn record_attempt(&self, attempt: &ProbeAttempt) {
+        let response = attempt.response.as_ref();
+        self.record(TraceRecord {
+            ip: attempt.ip,
+            stage: attempt.stage,
+            start_ms: self.since_start(attempt.started).as_millis(),
+            elapsed_ms: attempt.elapsed.as_millis(),
+            status: response.ok().map(|(status, _)| *status),
+            body: response.ok().map(|(_, body)| *body),
+            code: response.err().map(|err| err.code()),
+            error: response.err().map(|err| err.to_string()),
+        });
+    }
+
+    pub fn flush(&self) -> anyhow::Result<()> {
+        self.writer
+            .lock()
+            .expect("Trace file lock must not be poisoned")
+            .flush()
+            .context("Error writing trace file")
+    }
+}
+
+fn truncate(body: &str) -> &str {
+    if body.len() <= MAX_BODY {
+        return body;
+    }
+    let mut end = MAX_BODY;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    &body[..end]
+}
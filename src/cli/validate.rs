@@ -0,0 +1,25 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::Args;
+use rtls_ctl::{config::Config, validation::Validate};
+
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+    /// Config file to validate, defaults to the active config file
+    file: Option<PathBuf>,
+}
+
+pub fn run(args: ValidateArgs, config_path: Option<&Path>) -> anyhow::Result<()> {
+    let file = args
+        .file
+        .or_else(|| config_path.map(Path::to_path_buf))
+        .or_else(Config::default_path)
+        .context("No config file given and no default config location available")?;
+    let config = Config::load(&file)?;
+    config
+        .validate()
+        .with_context(|| format!("Invalid config file {}", file.display()))?;
+    println!("{} is valid", file.display());
+    Ok(())
+}
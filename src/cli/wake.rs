@@ -0,0 +1,87 @@
+use std::{
+    net::Ipv4Addr,
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+use rtls_ctl::{duration::parse_duration, types::Mac, wol};
+
+use super::{
+    global::GlobalOptions, porcelain::print_record, scan::ProbeContext, settings::LoadedConfig,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Args, Debug)]
+pub struct WakeArgs {
+    /// Mac address of the gateway to wake
+    mac: Mac,
+    /// Broadcast address of the gateway's subnet (e.g. 10.0.3.255)
+    #[arg(long, default_value = "255.255.255.255")]
+    broadcast: Ipv4Addr,
+    /// Udp port the magic packet is sent to
+    #[arg(long, default_value_t = wol::PORT)]
+    wol_port: u16,
+    /// Wait until the gateway is detected on --ip, then print it
+    #[arg(long, visible_alias = "wake-then-wait", requires = "ip")]
+    wait: bool,
+    /// Address the gateway comes up on
+    #[arg(long)]
+    ip: Option<Ipv4Addr>,
+    /// How long to wait for the gateway (e.g. 30s, 5m)
+    #[arg(long, default_value = "2m", value_parser = parse_duration)]
+    wait_timeout: Duration,
+    /// Http port of the gateway [default: 80]
+    #[arg(short, long, env = "RTLS_CTL_PORT")]
+    port: Option<u16>,
+}
+
+pub async fn run(
+    args: WakeArgs,
+    config: &LoadedConfig,
+    global: GlobalOptions,
+) -> anyhow::Result<()> {
+    wol::wake(args.mac, args.broadcast, args.wol_port).await?;
+    eprintln!("Sent magic packet for {} to {}", args.mac, args.broadcast);
+    let Some(ip) = args.ip.filter(|_| args.wait) else {
+        return Ok(());
+    };
+
+    let context = ProbeContext::for_host(config, &global, args.port)?;
+    let started = Instant::now();
+    loop {
+        match context.scanner.detect(ip).await {
+            Ok(detection) if detection.mac == args.mac => {
+                context.finish()?;
+                log::info!("{} is up after {:?}", args.mac, started.elapsed());
+                if global.porcelain {
+                    print_record(&[
+                        "gateway",
+                        &detection.ip.to_string(),
+                        detection.gateway.as_str(),
+                        &detection.mac.to_string(),
+                    ]);
+                } else {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&detection)
+                            .expect("Gateways must be serializable")
+                    );
+                }
+                return Ok(());
+            }
+            Ok(detection) => log::warn!("{} answered as {} instead", ip, detection.mac),
+            Err(err) => log::debug!("{} is not up yet: {:#}", ip, err),
+        }
+        if started.elapsed() >= args.wait_timeout {
+            context.finish()?;
+            anyhow::bail!(
+                "{} was not detected on {} within {}",
+                args.mac,
+                ip,
+                humantime::format_duration(args.wait_timeout)
+            );
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
@@ -0,0 +1,215 @@
+//! Clients for the http apis of a detected gateway, for managing it after a scan.
+
+use std::sync::Arc;
+
+use serde_json::{json, Map, Value};
+
+use crate::{
+    endpoints::{Endpoints, Scheme},
+    error::ClientError,
+    parse::{self, FirmwareInfo},
+    protocol::{G1StatusResponse, Mg3Config, Mg3Hello, Mg3SetResponse},
+    scanner::{G1_USERNAME, PORT},
+    transport::{HttpRequest, Transport},
+    types::{GatewayDetection, GatewayType},
+};
+
+/// The endpoints a gateway was detected on, over https for port 443 like the scanner.
+fn endpoints(gateway: &GatewayDetection) -> Endpoints {
+    let port = gateway.port.unwrap_or(PORT);
+    let scheme = if port == 443 {
+        Scheme::Https
+    } else {
+        Scheme::Http
+    };
+    Endpoints::for_ip(gateway.ip)
+        .with_scheme(scheme)
+        .with_port(port)
+}
+
+/// Sends the request, returning the body of a successful response.
+async fn fetch(transport: &dyn Transport, request: HttpRequest) -> Result<String, ClientError> {
+    let response = transport.send(request).await?;
+    if !response.status.is_success() {
+        return Err(ClientError::status(response.status));
+    }
+    Ok(response.body)
+}
+
+/// The `cgic-statusget` api of a G1 gateway.
+#[derive(Clone)]
+pub struct G1Client {
+    transport: Arc<dyn Transport>,
+    endpoints: Endpoints,
+    username: String,
+    password: String,
+}
+
+impl G1Client {
+    /// A client for the gateway, logging in with the default username and `password`.
+    pub fn new(gateway: &GatewayDetection, transport: Arc<dyn Transport>, password: &str) -> Self {
+        Self {
+            transport,
+            endpoints: endpoints(gateway),
+            username: G1_USERNAME.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    pub fn with_username(self, username: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            ..self
+        }
+    }
+
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
+    /// The status of the gateway, failing unless the header reports code 200.
+    pub async fn status(&self) -> Result<G1StatusResponse, ClientError> {
+        let response: G1StatusResponse = serde_json::from_str(&self.status_body().await?)?;
+        match response.header.code {
+            Some(200) => Ok(response),
+            code => Err(ClientError::Rejected {
+                action: "status".to_string(),
+                code,
+                message: None,
+            }),
+        }
+    }
+
+    pub async fn firmware(&self) -> Result<FirmwareInfo, ClientError> {
+        Ok(parse::g1_firmware(&self.status_body().await?))
+    }
+
+    /// G1 gateways have no known api for rebooting.
+    pub async fn reboot(&self) -> Result<(), ClientError> {
+        Err(ClientError::Unsupported { action: "reboot" })
+    }
+
+    async fn status_body(&self) -> Result<String, ClientError> {
+        let request = HttpRequest::post(
+            self.endpoints.g1_status(),
+            json! {{
+                "header": {
+                    "version": 1,
+                },
+            }},
+        )
+        .basic_auth(&self.username, &self.password);
+        fetch(&*self.transport, request).await
+    }
+}
+
+/// The `/hello` and `/set` apis of an MG3 gateway.
+#[derive(Clone)]
+pub struct Mg3Client {
+    transport: Arc<dyn Transport>,
+    endpoints: Endpoints,
+}
+
+impl Mg3Client {
+    pub fn new(gateway: &GatewayDetection, transport: Arc<dyn Transport>) -> Self {
+        Self {
+            transport,
+            endpoints: endpoints(gateway),
+        }
+    }
+
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.endpoints
+    }
+
+    pub async fn hello(&self) -> Result<Mg3Hello, ClientError> {
+        Ok(serde_json::from_str(&self.hello_body().await?)?)
+    }
+
+    pub async fn firmware(&self) -> Result<FirmwareInfo, ClientError> {
+        Ok(parse::mg3_firmware(&self.hello_body().await?))
+    }
+
+    pub async fn get_config(&self) -> Result<Mg3Config, ClientError> {
+        Ok(self.command("getConfig", Map::new()).await?.config)
+    }
+
+    /// Writes the sections and fields set in `config`, the others keep their value.
+    pub async fn set_config(&self, config: &Mg3Config) -> Result<(), ClientError> {
+        let Value::Object(fields) = serde_json::to_value(config)? else {
+            unreachable!("Configs must serialize to json objects");
+        };
+        self.command("SetConfig", fields).await.map(drop)
+    }
+
+    /// Restarts the gateway. It stops answering until it is back up.
+    pub async fn reboot(&self) -> Result<(), ClientError> {
+        self.command("reboot", Map::new()).await.map(drop)
+    }
+
+    /// Sends `{"action": action, ..fields}` to `/set`, returning the response once the gateway
+    /// accepts it with code 200.
+    pub async fn command(
+        &self,
+        action: &str,
+        mut fields: Map<String, Value>,
+    ) -> Result<Mg3SetResponse, ClientError> {
+        fields.insert("action".to_string(), Value::from(action));
+        let request = HttpRequest::post(self.endpoints.mg3_set(), Value::Object(fields));
+        let response: Mg3SetResponse =
+            serde_json::from_str(&fetch(&*self.transport, request).await?)?;
+        if response.code == Some(200) {
+            Ok(response)
+        } else {
+            Err(ClientError::Rejected {
+                action: action.to_string(),
+                code: response.code,
+                message: response.message,
+            })
+        }
+    }
+
+    async fn hello_body(&self) -> Result<String, ClientError> {
+        fetch(
+            &*self.transport,
+            HttpRequest::get(self.endpoints.mg3_hello()),
+        )
+        .await
+    }
+}
+
+/// The client matching the type of a detected gateway.
+#[derive(Clone)]
+pub enum GatewayClient {
+    G1(G1Client),
+    Mg3(Mg3Client),
+}
+
+impl GatewayClient {
+    /// A client for the gateway, G1 gateways logging in with the default username and
+    /// `g1_password`.
+    pub fn new(
+        gateway: &GatewayDetection,
+        transport: Arc<dyn Transport>,
+        g1_password: &str,
+    ) -> Self {
+        match gateway.gateway {
+            GatewayType::G1 => GatewayClient::G1(G1Client::new(gateway, transport, g1_password)),
+            GatewayType::MG3 => GatewayClient::Mg3(Mg3Client::new(gateway, transport)),
+        }
+    }
+
+    pub async fn firmware(&self) -> Result<FirmwareInfo, ClientError> {
+        match self {
+            GatewayClient::G1(client) => client.firmware().await,
+            GatewayClient::Mg3(client) => client.firmware().await,
+        }
+    }
+
+    pub async fn reboot(&self) -> Result<(), ClientError> {
+        match self {
+            GatewayClient::G1(client) => client.reboot().await,
+            GatewayClient::Mg3(client) => client.reboot().await,
+        }
+    }
+}
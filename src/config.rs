@@ -0,0 +1,398 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    target::Targets,
+    types::Rssi,
+    validation::{Validate, Validator},
+};
+
+fn hostname_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r"^([A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?)(\.[A-Za-z0-9]([A-Za-z0-9-]{0,61}[A-Za-z0-9])?)*$")
+            .expect("Hostname regex must be valid")
+    })
+}
+
+fn mqtt_url_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r"^mqtts?://(?P<host>[^:/]+)(:(?P<port>[0-9]+))?/?$")
+            .expect("Mqtt url regex must be valid")
+    })
+}
+
+/// The tool's own configuration file (`config.toml`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Limits shared by every command that talks to gateways
+    pub defaults: DefaultsSection,
+    pub scan: ScanSection,
+    pub credentials: CredentialsSection,
+    pub mqtt: Option<MqttSection>,
+    pub tls: TlsSection,
+    /// Named `[profiles.<name>]` sections, selected with `--profile <name>`
+    pub profiles: BTreeMap<String, Profile>,
+    /// Shorthands like `floor3 = "scan 10.3.0.1..10.3.0.255 -t 1s"`, expanded when they are
+    /// the command
+    pub aliases: BTreeMap<String, Alias>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/rtls-ctl/config.toml`, falling back to `~/.config/rtls-ctl/config.toml`
+    /// (or `%APPDATA%\rtls-ctl\config.toml` on windows).
+    pub fn default_path() -> Option<PathBuf> {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+            .map(|dir| dir.join("rtls-ctl").join("config.toml"))
+    }
+
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        toml::from_str(s).map_err(ConfigError::Parse)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        std::fs::read_to_string(path)
+            .map_err(ConfigError::Read)
+            .and_then(|contents| Self::from_toml(&contents))
+            .map_err(|err| ConfigError::File {
+                path: path.to_path_buf(),
+                source: Box::new(err),
+            })
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile, ConfigError> {
+        self.profiles
+            .get(name)
+            .ok_or_else(|| ConfigError::UnknownProfile {
+                name: name.to_string(),
+                available: self.profiles.keys().cloned().collect(),
+            })
+    }
+}
+
+/// Why the config couldn't be loaded.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file couldn't be read
+    Read(std::io::Error),
+    /// The config isn't toml with the structure of [`Config`]
+    Parse(toml::de::Error),
+    /// No `[profiles.<name>]` section has the selected name
+    UnknownProfile {
+        name: String,
+        available: Vec<String>,
+    },
+    /// The config file at `path` couldn't be loaded
+    File {
+        path: PathBuf,
+        source: Box<ConfigError>,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read(_) => write!(f, "Error reading config"),
+            Self::Parse(_) => write!(f, "Error parsing config"),
+            Self::UnknownProfile { name, available } => write!(
+                f,
+                "Profile '{}' not found, available profiles: {}",
+                name,
+                available.join(", ")
+            ),
+            Self::File { path, .. } => write!(f, "Error loading config file {}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Read(err) => Some(err),
+            Self::Parse(err) => Some(err),
+            Self::UnknownProfile { .. } => None,
+            Self::File { source, .. } => Some(source),
+        }
+    }
+}
+
+impl Validate for Config {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.nested("defaults", &self.defaults);
+        v.nested("scan", &self.scan);
+        v.nested("credentials", &self.credentials);
+        v.nested("mqtt", &self.mqtt);
+        for (name, profile) in &self.profiles {
+            v.nested(&format!("profiles.{}", name), profile);
+        }
+        for (name, alias) in &self.aliases {
+            if alias.args().is_empty() {
+                v.error(&format!("aliases.{}", name), "must not be empty");
+            }
+        }
+    }
+}
+
+/// The arguments an alias stands for, either split on whitespace or given as a list.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Alias {
+    Line(String),
+    Args(Vec<String>),
+}
+
+impl Alias {
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            Alias::Line(line) => line.split_whitespace().map(String::from).collect(),
+            Alias::Args(args) => args.clone(),
+        }
+    }
+}
+
+/// Site specific settings that override the top level config when selected.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    pub range: Option<String>,
+    pub port: Option<u16>,
+    pub credentials: CredentialsSection,
+    pub mqtt: Option<MqttSection>,
+    pub tls: TlsSection,
+}
+
+impl Profile {
+    /// The profile as a config layer, with every unset value left empty.
+    pub fn as_config(&self) -> Config {
+        Config {
+            scan: ScanSection {
+                range: self.range.clone(),
+                port: self.port,
+                ..Default::default()
+            },
+            credentials: self.credentials.clone(),
+            mqtt: self.mqtt.clone(),
+            tls: self.tls.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Validate for Profile {
+    fn validate_fields(&self, v: &mut Validator) {
+        validate_range(v, "range", self.range.as_deref());
+        v.range("port", self.port, 1..=65535);
+        v.nested("credentials", &self.credentials);
+        v.nested("mqtt", &self.mqtt);
+    }
+}
+
+fn validate_range(v: &mut Validator, field: &str, range: Option<&str>) {
+    if let Some(range) = range {
+        if let Err(err) = range.parse::<Targets>() {
+            v.error(
+                field,
+                format!("'{}' is not a valid ip range: {}", range, err),
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DefaultsSection {
+    /// Extra attempts after a request times out or its connection fails
+    pub retries: Option<u32>,
+    #[serde(
+        with = "crate::duration::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout: Option<Duration>,
+    pub concurrency: Option<usize>,
+    /// Maximum number of requests started per second
+    pub rate_limit: Option<u32>,
+    /// How long idle connections to a gateway are kept for reuse, `0s` to disable
+    #[serde(
+        with = "crate::duration::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub keep_alive: Option<Duration>,
+    /// Speak HTTP/2 to gateways without negotiating it
+    pub http2: Option<bool>,
+}
+
+impl Validate for DefaultsSection {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.range("retries", self.retries, 0..=10);
+        v.range("timeout", self.timeout.map(|t| t.as_millis()), 10..=600_000);
+        v.range("concurrency", self.concurrency, 1..=65535);
+        v.range("rate_limit", self.rate_limit, 1..=1_000_000);
+        v.range("keep_alive", self.keep_alive.map(|k| k.as_secs()), 0..=3600);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanSection {
+    /// Addresses to scan (e.g. `192.168.1.1..192.168.1.20`, `10.0.0.0/22,10.0.8.0/24`)
+    pub range: Option<String>,
+    /// Http port of the gateways
+    pub port: Option<u16>,
+    pub concurrency: Option<usize>,
+    #[serde(
+        with = "crate::duration::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout: Option<Duration>,
+}
+
+impl Validate for ScanSection {
+    fn validate_fields(&self, v: &mut Validator) {
+        validate_range(v, "range", self.range.as_deref());
+        v.range("port", self.port, 1..=65535);
+        v.range("concurrency", self.concurrency, 1..=65535);
+        v.range("timeout", self.timeout.map(|t| t.as_millis()), 10..=600_000);
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CredentialsSection {
+    pub g1: Option<Credentials>,
+}
+
+impl Validate for CredentialsSection {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.nested("g1", &self.g1);
+    }
+}
+
+/// Trust settings for gateways serving https.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsSection {
+    /// PEM file with extra root certificates, for gateways with certificates from an internal CA
+    pub ca_bundle: Option<PathBuf>,
+}
+
+/// Gateway admin credentials for http basic auth.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Credentials {
+    /// Defaults to the gateway's admin user, `admin` for G1 gateways
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Validate for Credentials {
+    fn validate_fields(&self, v: &mut Validator) {
+        if let Some(username) = &self.username {
+            if username.trim().is_empty() {
+                v.error("username", "must not be empty");
+            }
+        }
+    }
+}
+
+/// MQTT upload settings pushed to MG3 gateways when provisioning.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MqttSection {
+    /// Broker url like `mqtts://broker.example.com:8833`
+    pub url: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Minimum rssi of advertisements the gateway should upload
+    pub rssi: Option<Rssi>,
+    pub timezone: Option<String>,
+}
+
+impl Validate for MqttSection {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.required("url", self.url.as_ref());
+        if let Some(url) = &self.url {
+            match mqtt_url_regex().captures(url) {
+                Some(captures) => {
+                    let host = &captures["host"];
+                    if host.parse::<Ipv4Addr>().is_err() {
+                        v.matches("url", Some(host), hostname_regex(), "a valid hostname");
+                    }
+                    let port = captures.name("port").map(|p| p.as_str().parse::<u32>());
+                    match port {
+                        Some(Ok(port)) => v.range("url", Some(port), 1..=65535),
+                        Some(Err(_)) => v.error("url", "port is not a number"),
+                        None => {}
+                    }
+                }
+                None => v.error(
+                    "url",
+                    format!("'{}' is not a url like mqtts://host:8833", url),
+                ),
+            }
+        }
+        v.together(&[
+            ("username", self.username.is_some()),
+            ("password", self.password.is_some()),
+        ]);
+        if let Some(timezone) = &self.timezone {
+            if timezone.trim().is_empty() {
+                v.error("timezone", "must not be empty");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn g1_credentials_may_leave_out_the_username() {
+        let config = Config::from_toml("[credentials.g1]\npassword = \"secret\"\n").unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn g1_username_must_not_be_empty() {
+        let config = Config::from_toml("[credentials.g1]\nusername = \" \"\n").unwrap();
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("credentials.g1.username"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_profiles_list_the_available_ones() {
+        let config = Config::from_toml(
+            "[profiles.lab]
+[profiles.prod]
+",
+        )
+        .unwrap();
+        let err = config.profile("staging").unwrap_err();
+        assert!(
+            matches!(&err, ConfigError::UnknownProfile { name, .. } if name == "staging"),
+            "{:?}",
+            err
+        );
+        assert!(
+            err.to_string().ends_with("available profiles: lab, prod"),
+            "{}",
+            err
+        );
+    }
+}
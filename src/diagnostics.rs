@@ -0,0 +1,214 @@
+//! Low level reachability checks for telling why a gateway can't be reached: whether it answers
+//! ARP on the local segment, answers ICMP echo and how many hops away it is.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+
+use crate::{interfaces, target::Targets, types::Mac};
+
+/// Whether `ip` is on a subnet attached to a local, non-loopback interface, so it must answer
+/// ARP to be reachable.
+pub fn is_on_link(ip: Ipv4Addr) -> anyhow::Result<bool> {
+    Ok(interfaces::list()?
+        .iter()
+        .any(|interface| !interface.loopback && interface.subnet.contains(ip)))
+}
+
+/// The mac the kernel's neighbor table resolved `ip` to, if any.
+pub fn neighbor_mac(ip: Ipv4Addr) -> anyhow::Result<Option<Mac>> {
+    Ok(neighbors()?
+        .into_iter()
+        .find(|(entry, _)| *entry == ip)
+        .map(|(_, mac)| mac))
+}
+
+/// Every address the kernel's neighbor table resolved to a mac.
+#[cfg(target_os = "linux")]
+pub fn neighbors() -> anyhow::Result<Vec<(Ipv4Addr, Mac)>> {
+    let table = std::fs::read_to_string("/proc/net/arp").context("Error reading /proc/net/arp")?;
+    Ok(crate::parse::proc_net_arp(&table))
+}
+
+#[cfg(windows)]
+pub fn neighbors() -> anyhow::Result<Vec<(Ipv4Addr, Mac)>> {
+    use windows_sys::Win32::{
+        NetworkManagement::IpHelper::{FreeMibTable, GetIpNetTable2, MIB_IPNET_TABLE2},
+        Networking::WinSock::{NlnsIncomplete, NlnsUnreachable, AF_INET},
+    };
+
+    let mut table: *mut MIB_IPNET_TABLE2 = std::ptr::null_mut();
+    // SAFETY: GetIpNetTable2 only writes the table pointer, which is freed below.
+    let status = unsafe { GetIpNetTable2(AF_INET, &mut table) };
+    if status != 0 {
+        return Err(std::io::Error::from_raw_os_error(status as i32))
+            .context("Error reading the neighbor table");
+    }
+    // SAFETY: on success the table holds NumEntries rows, valid until FreeMibTable.
+    let rows = unsafe {
+        std::slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize)
+    };
+    let neighbors = rows
+        .iter()
+        .filter(|row| {
+            row.State != NlnsUnreachable
+                && row.State != NlnsIncomplete
+                && row.PhysicalAddressLength == 6
+        })
+        .map(|row| {
+            // SAFETY: the table was requested for AF_INET, so every address is a SOCKADDR_IN.
+            let ip = unsafe { row.Address.Ipv4.sin_addr.S_un.S_addr };
+            let mut mac = [0; 6];
+            mac.copy_from_slice(&row.PhysicalAddress[..6]);
+            (Ipv4Addr::from(u32::from_be(ip)), Mac::from(mac))
+        })
+        .collect();
+    // SAFETY: `table` came from GetIpNetTable2 and no row is used past this point.
+    unsafe { FreeMibTable(table as *const _) };
+    Ok(neighbors)
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn neighbors() -> anyhow::Result<Vec<(Ipv4Addr, Mac)>> {
+    anyhow::bail!("Reading the neighbor table is only supported on linux and windows")
+}
+
+/// Sends one ICMP echo request over an unprivileged ICMP socket, returning the round trip time,
+/// or `None` without a reply within `timeout`.
+///
+/// Fails when the OS doesn't allow unprivileged ICMP sockets (on linux see
+/// `net.ipv4.ping_group_range`).
+pub async fn ping(ip: Ipv4Addr, timeout: Duration) -> anyhow::Result<Option<Duration>> {
+    tokio::task::spawn_blocking(move || ping_blocking(ip, timeout))
+        .await
+        .expect("Pings must not panic")
+}
+
+fn ping_blocking(ip: Ipv4Addr, timeout: Duration) -> anyhow::Result<Option<Duration>> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket: UdpSocket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4))
+        .context("Error opening ICMP socket")?
+        .into();
+    socket.set_read_timeout(Some(timeout))?;
+    // Echo request, checksum, identifier (set by the kernel) and sequence number 1
+    let mut packet = [8, 0, 0, 0, 0, 0, 0, 1, b'r', b't', b'l', b's'];
+    let checksum = icmp_checksum(&packet);
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    let started = Instant::now();
+    socket
+        .send_to(&packet, SocketAddr::from((ip, 0)))
+        .context("Error sending ICMP echo request")?;
+    let mut buffer = [0u8; 1500];
+    while started.elapsed() < timeout {
+        let (len, from) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(None)
+            }
+            Err(err) => return Err(err).context("Error receiving ICMP echo reply"),
+        };
+        // Some platforms include the ip header
+        let icmp = match buffer[..len].first() {
+            Some(first) if first >> 4 == 4 => {
+                &buffer[(usize::from(first & 0x0F) * 4).min(len)..len]
+            }
+            _ => &buffer[..len],
+        };
+        if from.ip() == ip && icmp.first() == Some(&0) {
+            return Ok(Some(started.elapsed()));
+        }
+    }
+    Ok(None)
+}
+
+/// Sends one ICMP echo request to every address of `targets` from a single unprivileged ICMP
+/// socket, returning the addresses that replied within `timeout` of the last request.
+///
+/// Fails like [`ping`] when the OS doesn't allow unprivileged ICMP sockets.
+pub async fn ping_sweep(targets: Targets, timeout: Duration) -> anyhow::Result<Vec<Ipv4Addr>> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4))
+        .context("Error opening ICMP socket")?;
+    socket.set_nonblocking(true)?;
+    let socket = tokio::net::UdpSocket::from_std(UdpSocket::from(socket))?;
+    let mut packet = [8, 0, 0, 0, 0, 0, 0, 1, b'r', b't', b'l', b's'];
+    let checksum = icmp_checksum(&packet);
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+    let sending = async {
+        for ip in targets {
+            if let Err(err) = socket.send_to(&packet, SocketAddr::from((ip, 0))).await {
+                log::trace!("Error sending ICMP echo request to {}: {}", ip, err);
+            }
+        }
+        tokio::time::sleep(timeout).await;
+    };
+    let mut replied = Vec::new();
+    let receiving = async {
+        let mut buffer = [0u8; 1500];
+        loop {
+            let (len, from) = match socket.recv_from(&mut buffer).await {
+                Ok(received) => received,
+                Err(err) => return err,
+            };
+            let icmp = match buffer[..len].first() {
+                Some(first) if first >> 4 == 4 => {
+                    &buffer[(usize::from(first & 0x0F) * 4).min(len)..len]
+                }
+                _ => &buffer[..len],
+            };
+            if let (Some(&0), std::net::IpAddr::V4(ip)) = (icmp.first(), from.ip()) {
+                if !replied.contains(&ip) {
+                    replied.push(ip);
+                }
+            }
+        }
+    };
+    tokio::select! {
+        _ = sending => {}
+        err = receiving => return Err(err).context("Error receiving ICMP echo reply"),
+    }
+    Ok(replied)
+}
+
+fn icmp_checksum(packet: &[u8]) -> u16 {
+    let mut sum: u32 = packet
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Finds how many hops away `ip` is by connecting to `port` with increasing ttls until a
+/// connection is accepted or refused.
+///
+/// Only the hop count is found, telling routers apart would need raw sockets.
+pub async fn hop_count(ip: Ipv4Addr, port: u16, max_hops: u8, timeout: Duration) -> Option<u8> {
+    for ttl in 1..=max_hops {
+        let socket = tokio::net::TcpSocket::new_v4().ok()?;
+        socket2::SockRef::from(&socket)
+            .set_ttl(u32::from(ttl))
+            .ok()?;
+        match tokio::time::timeout(timeout, socket.connect(SocketAddr::from((ip, port)))).await {
+            Ok(Ok(_)) => return Some(ttl),
+            Ok(Err(err)) if err.kind() == std::io::ErrorKind::ConnectionRefused => {
+                return Some(ttl)
+            }
+            _ => log::trace!("No answer from {} with ttl {}", ip, ttl),
+        }
+    }
+    None
+}
@@ -0,0 +1,236 @@
+use std::fmt::{self, Display};
+
+use serde_json::Value;
+
+/// A single difference between two json documents.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// Structural diff of two json documents.
+///
+/// Objects are compared key by key and arrays index by index, so only the leaves that differ are
+/// reported. Paths look like `scan.port` or `profiles.site-a.range` (`gateways[2].mac` for arrays).
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_at(String::new(), old, new, &mut changes);
+    changes
+}
+
+fn diff_at(path: String, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let path = join(&path, key);
+                match new.get(key) {
+                    Some(new_value) => diff_at(path, old_value, new_value, changes),
+                    None => changes.push(Change::Removed {
+                        path,
+                        value: old_value.clone(),
+                    }),
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    changes.push(Change::Added {
+                        path: join(&path, key),
+                        value: new_value.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for i in 0..old.len().max(new.len()) {
+                let path = format!("{}[{}]", path, i);
+                match (old.get(i), new.get(i)) {
+                    (Some(old), Some(new)) => diff_at(path, old, new, changes),
+                    (Some(old), None) => changes.push(Change::Removed {
+                        path,
+                        value: old.clone(),
+                    }),
+                    (None, Some(new)) => changes.push(Change::Added {
+                        path,
+                        value: new.clone(),
+                    }),
+                    (None, None) => unreachable!("Index must be in one of the arrays"),
+                }
+            }
+        }
+        (old, new) if old != new => changes.push(Change::Changed {
+            path,
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Renders changes one per line as `+ path = value`, `- path = value` and `~ path: old -> new`,
+/// optionally colored with ansi escapes (green, red and yellow).
+pub struct Rendered<'a> {
+    pub changes: &'a [Change],
+    pub color: bool,
+}
+
+impl Display for Rendered<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let paint = |code: &str| {
+            if self.color {
+                code.to_string()
+            } else {
+                String::new()
+            }
+        };
+        let reset = paint("\x1b[0m");
+        for change in self.changes {
+            match change {
+                Change::Added { path, value } => {
+                    writeln!(f, "{}+ {} = {}{}", paint("\x1b[32m"), path, value, reset)?
+                }
+                Change::Removed { path, value } => {
+                    writeln!(f, "{}- {} = {}{}", paint("\x1b[31m"), path, value, reset)?
+                }
+                Change::Changed { path, old, new } => writeln!(
+                    f,
+                    "{}~ {}: {} -> {}{}",
+                    paint("\x1b[33m"),
+                    path,
+                    old,
+                    new,
+                    reset
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn equal_documents_have_no_changes() {
+        let doc = json!({ "scan": { "port": 80, "ranges": ["10.0.0.0/24"] } });
+        assert!(diff(&doc, &doc).is_empty());
+    }
+
+    #[test]
+    fn nested_objects_report_leaf_paths() {
+        let old = json!({
+            "scan": { "port": 80, "range": "10.0.0.0/24" },
+            "profiles": { "site-a": { "port": 8080 } },
+        });
+        let new = json!({
+            "scan": { "port": 8080, "timeout": "2s" },
+            "profiles": { "site-a": { "port": 8080 }, "site-b": { "port": 80 } },
+        });
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                Change::Added {
+                    path: "profiles.site-b".to_string(),
+                    value: json!({ "port": 80 }),
+                },
+                Change::Changed {
+                    path: "scan.port".to_string(),
+                    old: json!(80),
+                    new: json!(8080),
+                },
+                Change::Removed {
+                    path: "scan.range".to_string(),
+                    value: json!("10.0.0.0/24"),
+                },
+                Change::Added {
+                    path: "scan.timeout".to_string(),
+                    value: json!("2s"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn arrays_are_compared_by_index() {
+        let old = json!({ "gateways": [{ "mac": "a" }, { "mac": "b" }, { "mac": "c" }] });
+        let shorter = json!({ "gateways": [{ "mac": "a" }, { "mac": "x" }] });
+        assert_eq!(
+            diff(&old, &shorter),
+            vec![
+                Change::Changed {
+                    path: "gateways[1].mac".to_string(),
+                    old: json!("b"),
+                    new: json!("x"),
+                },
+                Change::Removed {
+                    path: "gateways[2]".to_string(),
+                    value: json!({ "mac": "c" }),
+                },
+            ]
+        );
+        assert_eq!(
+            diff(&shorter, &old)[1],
+            Change::Added {
+                path: "gateways[2]".to_string(),
+                value: json!({ "mac": "c" }),
+            }
+        );
+    }
+
+    #[test]
+    fn type_changes_replace_the_whole_value() {
+        let old = json!({ "range": ["10.0.0.1"] });
+        let new = json!({ "range": "10.0.0.1" });
+        assert_eq!(
+            diff(&old, &new),
+            vec![Change::Changed {
+                path: "range".to_string(),
+                old: json!(["10.0.0.1"]),
+                new: json!("10.0.0.1"),
+            }]
+        );
+    }
+
+    #[test]
+    fn rendered_changes() {
+        let changes = diff(&json!({ "a": 1, "b": 2 }), &json!({ "a": 3, "c": 4 }));
+        let text = Rendered {
+            changes: &changes,
+            color: false,
+        }
+        .to_string();
+        assert_eq!(text, "~ a: 1 -> 3\n- b = 2\n+ c = 4\n");
+    }
+}
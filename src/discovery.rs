@@ -0,0 +1,533 @@
+//! Finding gateways without fingerprinting every address: ARP sweeps, mDNS, G1 discovery
+//! broadcasts and listening for announcements.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::Context;
+use rand::Rng;
+
+use crate::{
+    target::Targets,
+    types::{GatewayDetection, GatewayType, Mac},
+};
+
+/// Port the ARP sweep sends its datagrams to, the discard service
+const DISCARD_PORT: u16 = 9;
+
+/// Port G1 gateways answer discovery broadcasts on
+pub const G1_DISCOVERY_PORT: u16 = 30303;
+/// Query of the discovery protocol of the G1's network stack
+const G1_DISCOVERY_QUERY: &[u8] = b"Discovery: Who is out there?\0\n";
+
+/// Service type MG3 gateways advertise over mDNS
+pub const MDNS_SERVICE: &str = "_rtls._tcp";
+const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+const DNS_TYPE_A: u16 = 1;
+const DNS_TYPE_PTR: u16 = 12;
+const DNS_TYPE_TXT: u16 = 16;
+const DNS_TYPE_SRV: u16 = 33;
+const DNS_CLASS_IN: u16 = 1;
+/// Class IN with the bit asking responders to answer unicast
+const DNS_CLASS_IN_UNICAST: u16 = 0x8001;
+/// Class IN with the bit telling caches to replace older records of the name, the same bit
+const DNS_CLASS_IN_FLUSH: u16 = 0x8001;
+/// Service type detected gateways are announced as
+pub const ANNOUNCE_SERVICE: &str = "_rtls-gw._tcp";
+/// Seconds announced records stay cached, the usual mDNS ttl for service records
+const ANNOUNCE_TTL: u32 = 4500;
+
+/// The addresses of `targets` that answer ARP, with their macs.
+///
+/// Sends an empty udp datagram to every target on a local subnet so the OS ARPs for it, then
+/// reads the resolved entries of the neighbor table (`/proc/net/arp`, or GetIpNetTable2 on
+/// windows) after `wait`. This needs no privileges but only finds hosts on subnets attached to a
+/// local interface. Sweeps larger than the neighbor table (on linux
+/// `net.ipv4.neigh.default.gc_thresh3`) lose entries.
+#[cfg(any(target_os = "linux", windows))]
+pub async fn arp_sweep(targets: Targets, wait: Duration) -> anyhow::Result<Vec<(Ipv4Addr, Mac)>> {
+    let subnets: Vec<_> = crate::interfaces::list()?
+        .into_iter()
+        .filter(|interface| interface.up && !interface.loopback)
+        .map(|interface| interface.subnet)
+        .collect();
+    let socket = tokio::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .context("Error binding udp socket")?;
+    let mut candidates = Vec::new();
+    for ip in targets {
+        if !subnets.iter().any(|subnet| subnet.contains(ip)) {
+            continue;
+        }
+        // Only the ARP request matters, sending fails for hosts already known to be unreachable
+        if let Err(err) = socket.send_to(&[], (ip, DISCARD_PORT)).await {
+            log::trace!("Error sending to {}: {}", ip, err);
+        }
+        candidates.push(ip);
+    }
+    if candidates.is_empty() {
+        log::warn!("No target is on a local subnet, ARP discovery cannot find any host");
+        return Ok(Vec::new());
+    }
+    tokio::time::sleep(wait).await;
+    let neighbors = tokio::task::spawn_blocking(crate::diagnostics::neighbors)
+        .await
+        .expect("Reading the neighbor table must not panic")?;
+    Ok(neighbors
+        .into_iter()
+        .filter(|(ip, _)| candidates.contains(ip))
+        .collect())
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub async fn arp_sweep(_targets: Targets, _wait: Duration) -> anyhow::Result<Vec<(Ipv4Addr, Mac)>> {
+    anyhow::bail!("ARP discovery is only supported on linux and windows")
+}
+
+/// The G1 gateways answering a discovery broadcast to `broadcast`, with their macs, collected
+/// for `wait`.
+///
+/// Gateways answer over udp, so this finds them even when a firewall blocks inbound tcp.
+pub async fn g1_broadcast(
+    broadcast: Ipv4Addr,
+    wait: Duration,
+) -> anyhow::Result<Vec<(Ipv4Addr, Mac)>> {
+    let socket = tokio::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .context("Error binding udp socket")?;
+    socket.set_broadcast(true)?;
+    socket
+        .send_to(G1_DISCOVERY_QUERY, (broadcast, G1_DISCOVERY_PORT))
+        .await
+        .with_context(|| format!("Error sending discovery broadcast to {}", broadcast))?;
+    let mut gateways = Vec::new();
+    let mut buf = [0; 1500];
+    let deadline = tokio::time::Instant::now() + wait;
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        let (len, from) = received.context("Error receiving discovery answer")?;
+        let SocketAddr::V4(from) = from else {
+            continue;
+        };
+        match crate::parse::g1_announcement(&buf[..len]) {
+            Some(mac) if !gateways.iter().any(|(ip, _)| ip == from.ip()) => {
+                gateways.push((*from.ip(), mac))
+            }
+            Some(_) => {}
+            None => log::debug!("Ignoring discovery answer without a mac from {}", from),
+        }
+    }
+    Ok(gateways)
+}
+
+/// The addresses of `targets` with tcp `port` open, from a single sweep of raw SYN packets that
+/// collects SYN-ACKs until `wait` after the last SYN.
+///
+/// Connections are never completed: the kernel answers the SYN-ACKs with a reset as it knows
+/// nothing of them. Needs a raw socket, so root or `CAP_NET_RAW`.
+pub async fn syn_scan(
+    targets: Targets,
+    port: u16,
+    wait: Duration,
+) -> anyhow::Result<Vec<Ipv4Addr>> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::TCP))
+        .context("Error opening raw socket, SYN scans need root or CAP_NET_RAW")?;
+    socket.set_nonblocking(true)?;
+    // Only used for sendto and recvfrom, which work the same on raw sockets
+    let socket = tokio::net::UdpSocket::from_std(std::net::UdpSocket::from(socket))?;
+    let source_port: u16 = rand::thread_rng().gen_range(40000..60000);
+    let sequence: u32 = rand::random();
+
+    let sending = async {
+        for ip in targets {
+            let source = match local_address(ip) {
+                Ok(source) => source,
+                Err(err) => {
+                    log::trace!("No route to {}: {}", ip, err);
+                    continue;
+                }
+            };
+            let segment = syn_segment(source, ip, source_port, port, sequence);
+            if let Err(err) = socket.send_to(&segment, SocketAddr::from((ip, 0))).await {
+                log::trace!("Error sending SYN to {}: {}", ip, err);
+            }
+        }
+        tokio::time::sleep(wait).await;
+    };
+    let mut open = Vec::new();
+    let receiving = async {
+        let mut buffer = [0u8; 1500];
+        loop {
+            // Raw sockets receive every tcp packet with its ip header
+            let len = match socket.recv(&mut buffer).await {
+                Ok(len) => len,
+                Err(err) => return err,
+            };
+            let packet = &buffer[..len];
+            let header_len = usize::from(packet.first().map_or(0, |first| first & 0x0F)) * 4;
+            let (Some(source), Some(tcp)) = (packet.get(12..16), packet.get(header_len..)) else {
+                continue;
+            };
+            let Some(tcp) = tcp.get(..14) else {
+                continue;
+            };
+            let ack = u32::from_be_bytes([tcp[8], tcp[9], tcp[10], tcp[11]]);
+            if u16::from_be_bytes([tcp[0], tcp[1]]) == port
+                && u16::from_be_bytes([tcp[2], tcp[3]]) == source_port
+                && tcp[13] & 0x12 == 0x12
+                && ack == sequence.wrapping_add(1)
+            {
+                let ip = Ipv4Addr::new(source[0], source[1], source[2], source[3]);
+                if !open.contains(&ip) {
+                    open.push(ip);
+                }
+            }
+        }
+    };
+    tokio::select! {
+        _ = sending => {}
+        err = receiving => return Err(err).context("Error receiving SYN-ACKs"),
+    }
+    Ok(open)
+}
+
+/// The local address the routing table sends packets for `ip` from.
+fn local_address(ip: Ipv4Addr) -> std::io::Result<Ipv4Addr> {
+    let socket = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect((ip, DISCARD_PORT))?;
+    match socket.local_addr()? {
+        SocketAddr::V4(addr) => Ok(*addr.ip()),
+        SocketAddr::V6(_) => unreachable!("Ipv4 sockets have ipv4 addresses"),
+    }
+}
+
+/// A tcp SYN segment without options, checksummed for the given addresses.
+fn syn_segment(
+    source: Ipv4Addr,
+    destination: Ipv4Addr,
+    source_port: u16,
+    port: u16,
+    sequence: u32,
+) -> [u8; 20] {
+    let mut segment = [0u8; 20];
+    segment[0..2].copy_from_slice(&source_port.to_be_bytes());
+    segment[2..4].copy_from_slice(&port.to_be_bytes());
+    segment[4..8].copy_from_slice(&sequence.to_be_bytes());
+    // Header length of 5 words and the SYN flag
+    segment[12] = 5 << 4;
+    segment[13] = 0x02;
+    segment[14..16].copy_from_slice(&1024u16.to_be_bytes());
+    // Pseudo header: addresses, protocol and tcp length
+    let mut sum: u32 = [source.octets(), destination.octets()]
+        .iter()
+        .flat_map(|octets| octets.chunks(2))
+        .chain(segment.chunks(2))
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], pair[1]])))
+        .sum::<u32>()
+        + 6
+        + 20;
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    segment[16..18].copy_from_slice(&(!(sum as u16)).to_be_bytes());
+    segment
+}
+
+/// The addresses of the hosts advertising any of `services` (like `_rtls._tcp`) over mDNS,
+/// collected for `wait` after a single query.
+///
+/// The query is sent from an ephemeral port, so responders answer it directly instead of on the
+/// multicast group and nothing else listening on port 5353 is disturbed.
+pub async fn mdns_browse(services: &[String], wait: Duration) -> anyhow::Result<Vec<Ipv4Addr>> {
+    let socket = tokio::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .context("Error binding udp socket")?;
+    socket
+        .send_to(&mdns_query(services), (MDNS_GROUP, MDNS_PORT))
+        .await
+        .context("Error sending mDNS query")?;
+    let mut hosts = Vec::new();
+    let mut buf = [0; 9000];
+    let deadline = tokio::time::Instant::now() + wait;
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        let (len, from) = received.context("Error receiving mDNS response")?;
+        let Some(records) = MdnsRecords::parse(&buf[..len]) else {
+            log::debug!("Ignoring malformed mDNS response from {}", from);
+            continue;
+        };
+        for ip in records.hosts(from) {
+            if !hosts.contains(&ip) {
+                hosts.push(ip);
+            }
+        }
+    }
+    Ok(hosts)
+}
+
+/// The fully qualified name of a service type, `_rtls._tcp` and `_rtls._tcp.local.` both
+/// become `_rtls._tcp.local`.
+fn service_name(service: &str) -> String {
+    format!(
+        "{}.local",
+        service
+            .trim_end_matches('.')
+            .trim_end_matches(".local")
+            .to_ascii_lowercase()
+    )
+}
+
+/// A DNS query with one PTR question per service.
+fn mdns_query(services: &[String]) -> Vec<u8> {
+    let mut packet = vec![0; 12];
+    packet[4..6].copy_from_slice(&(services.len() as u16).to_be_bytes());
+    for service in services {
+        packet.extend(encode_name(&service_name(service)));
+        packet.extend_from_slice(&DNS_TYPE_PTR.to_be_bytes());
+        packet.extend_from_slice(&DNS_CLASS_IN_UNICAST.to_be_bytes());
+    }
+    packet
+}
+
+/// A dot separated name as DNS labels, without compression.
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(name.len() + 2);
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        encoded.push(label.len() as u8);
+        encoded.extend_from_slice(label.as_bytes());
+    }
+    encoded.push(0);
+    encoded
+}
+
+/// A resource record of an mDNS response.
+fn dns_record(name: &str, kind: u16, class: u16, data: &[u8]) -> Vec<u8> {
+    let mut record = encode_name(name);
+    record.extend_from_slice(&kind.to_be_bytes());
+    record.extend_from_slice(&class.to_be_bytes());
+    record.extend_from_slice(&ANNOUNCE_TTL.to_be_bytes());
+    record.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    record.extend_from_slice(data);
+    record
+}
+
+/// An unsolicited mDNS response advertising `gateway` as an instance of [`ANNOUNCE_SERVICE`]:
+/// PTR, SRV and TXT records of the instance and the A record of its host.
+fn announcement(gateway: &GatewayDetection) -> Vec<u8> {
+    let id = hex::encode_upper(gateway.mac.bytes);
+    let service = service_name(ANNOUNCE_SERVICE);
+    let instance = format!("{}-{}.{}", gateway.gateway.as_str(), id, service);
+    let host = format!("rtls-{}.local", id.to_ascii_lowercase());
+
+    let mut srv = Vec::new();
+    // Priority and weight
+    srv.extend_from_slice(&[0, 0, 0, 0]);
+    srv.extend_from_slice(&gateway.port.unwrap_or(80).to_be_bytes());
+    srv.extend(encode_name(&host));
+    let mut txt = Vec::new();
+    for entry in [
+        format!("type={}", gateway.gateway.as_str()),
+        format!("mac={}", gateway.mac),
+    ] {
+        txt.push(entry.len() as u8);
+        txt.extend_from_slice(entry.as_bytes());
+    }
+    let records = [
+        dns_record(
+            &service,
+            DNS_TYPE_PTR,
+            DNS_CLASS_IN,
+            &encode_name(&instance),
+        ),
+        dns_record(&instance, DNS_TYPE_SRV, DNS_CLASS_IN_FLUSH, &srv),
+        dns_record(&instance, DNS_TYPE_TXT, DNS_CLASS_IN_FLUSH, &txt),
+        dns_record(&host, DNS_TYPE_A, DNS_CLASS_IN_FLUSH, &gateway.ip.octets()),
+    ];
+    // Authoritative response with an answer per record
+    let mut packet = vec![0, 0, 0x84, 0, 0, 0, 0, records.len() as u8, 0, 0, 0, 0];
+    packet.extend(records.concat());
+    packet
+}
+
+/// What a gateway discovery needs from an mDNS message.
+#[derive(Debug, Default)]
+struct MdnsRecords {
+    /// Whether the message is a response rather than a query
+    response: bool,
+    /// Lowercased owner names of the PTR records, the advertised service types
+    services: Vec<String>,
+    /// Addresses of the A records
+    addresses: Vec<Ipv4Addr>,
+}
+
+impl MdnsRecords {
+    /// `None` when the message is malformed.
+    fn parse(packet: &[u8]) -> Option<Self> {
+        let count = |idx: usize| -> Option<usize> {
+            Some(u16::from_be_bytes(packet.get(idx..idx + 2)?.try_into().ok()?).into())
+        };
+        let mut parsed = Self {
+            response: count(2)? & 0x8000 != 0,
+            ..Self::default()
+        };
+        let questions = count(4)?;
+        let records = count(6)? + count(8)? + count(10)?;
+        let mut pos = 12;
+        for _ in 0..questions {
+            pos = read_name(packet, pos)?.1 + 4;
+        }
+        for _ in 0..records {
+            let (name, end) = read_name(packet, pos)?;
+            pos = end;
+            let kind = count(pos)?;
+            let len = count(pos + 8)?;
+            let data = packet.get(pos + 10..pos + 10 + len)?;
+            if kind == usize::from(DNS_TYPE_A) && len == 4 {
+                let ip = Ipv4Addr::new(data[0], data[1], data[2], data[3]);
+                if !parsed.addresses.contains(&ip) {
+                    parsed.addresses.push(ip);
+                }
+            } else if kind == usize::from(DNS_TYPE_PTR) && !parsed.services.contains(&name) {
+                parsed.services.push(name);
+            }
+            pos += 10 + len;
+        }
+        Some(parsed)
+    }
+
+    /// The advertising hosts. Responders usually include their A record, otherwise the sender
+    /// is the host.
+    fn hosts(self, from: SocketAddr) -> Vec<Ipv4Addr> {
+        match (self.addresses.is_empty(), from) {
+            (true, SocketAddr::V4(from)) => vec![*from.ip()],
+            _ => self.addresses,
+        }
+    }
+}
+
+/// The lowercased, dot separated name starting at `pos` and the position after it, following
+/// compression pointers.
+fn read_name(packet: &[u8], mut pos: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut end = None;
+    // Pointers only lead backwards in valid messages, bounding the jumps prevents loops
+    for _ in 0..128 {
+        let len = *packet.get(pos)?;
+        match len {
+            0 => {
+                return Some((labels.join("."), end.unwrap_or(pos + 1)));
+            }
+            len if len & 0xC0 == 0xC0 => {
+                let target = usize::from(len & 0x3F) << 8 | usize::from(*packet.get(pos + 1)?);
+                end.get_or_insert(pos + 2);
+                pos = target;
+            }
+            len => {
+                let label = packet.get(pos + 1..pos + 1 + usize::from(len))?;
+                labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+                pos += 1 + usize::from(len);
+            }
+        }
+    }
+    None
+}
+
+/// Listens for gateways announcing themselves, passing each announcement to `on_gateway` until
+/// an error occurs. Sends nothing.
+///
+/// G1 gateways broadcast their mac to the discovery port when they come up. MG3 gateways
+/// advertise `services` on the mDNS group, their mac is taken from the neighbor table and
+/// announcements from hosts not in it yet are skipped.
+pub async fn listen(
+    services: &[String],
+    mut on_gateway: impl FnMut(GatewayDetection),
+) -> anyhow::Result<()> {
+    let g1 = bind_shared(G1_DISCOVERY_PORT, None)?;
+    let mdns = bind_shared(MDNS_PORT, Some(MDNS_GROUP))?;
+    let services: Vec<String> = services
+        .iter()
+        .map(|service| service_name(service))
+        .collect();
+    let mut g1_buf = [0; 1500];
+    let mut mdns_buf = [0; 9000];
+    loop {
+        tokio::select! {
+            received = g1.recv_from(&mut g1_buf) => {
+                let (len, from) = received.context("Error receiving G1 announcement")?;
+                let (SocketAddr::V4(from), Some(mac)) =
+                    (from, crate::parse::g1_announcement(&g1_buf[..len]))
+                else {
+                    continue;
+                };
+                on_gateway(GatewayDetection::new(*from.ip(), GatewayType::G1, mac));
+            }
+            received = mdns.recv_from(&mut mdns_buf) => {
+                let (len, from) = received.context("Error receiving mDNS announcement")?;
+                let Some(records) = MdnsRecords::parse(&mdns_buf[..len]) else {
+                    continue;
+                };
+                if !records.response
+                    || !records.services.iter().any(|service| services.contains(service))
+                {
+                    continue;
+                }
+                for ip in records.hosts(from) {
+                    match crate::diagnostics::neighbor_mac(ip) {
+                        Ok(Some(mac)) => {
+                            on_gateway(GatewayDetection::new(ip, GatewayType::MG3, mac))
+                        }
+                        Ok(None) => log::info!("Skipping {}, its mac is not known yet", ip),
+                        Err(err) => log::info!("Skipping {}: {:#}", ip, err),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A udp socket on `port` that other listeners (like an mDNS responder) can bind as well.
+fn bind_shared(port: u16, group: Option<Ipv4Addr>) -> anyhow::Result<tokio::net::UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.set_broadcast(true)?;
+    socket
+        .bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)).into())
+        .with_context(|| format!("Error binding udp port {}", port))?;
+    if let Some(group) = group {
+        socket
+            .join_multicast_v4(&group, &Ipv4Addr::UNSPECIFIED)
+            .with_context(|| format!("Error joining multicast group {}", group))?;
+    }
+    socket.set_nonblocking(true)?;
+    Ok(tokio::net::UdpSocket::from_std(socket.into())?)
+}
+
+/// Announces every gateway as an instance of [`ANNOUNCE_SERVICE`] on the mDNS group, twice one
+/// second apart as mDNS asks for.
+///
+/// Nothing answers queries for the records afterwards, they stay in the caches of the hosts on the
+/// link until their ttl of 75 minutes runs out.
+pub async fn announce(gateways: &[GatewayDetection]) -> anyhow::Result<()> {
+    // Responders only accept responses sent from the mDNS port with a ttl of 255
+    let socket = bind_shared(MDNS_PORT, None)?;
+    socket.set_multicast_ttl_v4(255)?;
+    for round in 0..2 {
+        if round > 0 {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        for gateway in gateways {
+            socket
+                .send_to(&announcement(gateway), (MDNS_GROUP, MDNS_PORT))
+                .await
+                .context("Error sending mDNS announcement")?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+//! Name lookups for hostname targets and to enrich detections.
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::error::ResolveError;
+
+/// The ipv4 addresses `host` resolves to, in the order the resolver returns them.
+pub async fn resolve(host: &str) -> Result<Vec<Ipv4Addr>, ResolveError> {
+    let addrs =
+        tokio::net::lookup_host((host, 0))
+            .await
+            .map_err(|source| ResolveError::Lookup {
+                host: host.to_string(),
+                source,
+            })?;
+    let mut ips = Vec::new();
+    for addr in addrs {
+        if let IpAddr::V4(ip) = addr.ip() {
+            if !ips.contains(&ip) {
+                ips.push(ip);
+            }
+        }
+    }
+    if ips.is_empty() {
+        return Err(ResolveError::NoIpv4 {
+            host: host.to_string(),
+        });
+    }
+    Ok(ips)
+}
+
+/// The name a PTR record gives `ip`, or `None` when there is none or the lookup fails.
+///
+/// Uses the system resolver, so `/etc/hosts` and search domains apply as for any other tool.
+pub async fn reverse_lookup(ip: Ipv4Addr) -> Option<String> {
+    let result = tokio::task::spawn_blocking(move || dns_lookup::lookup_addr(&IpAddr::V4(ip)))
+        .await
+        .expect("Reverse lookups must not panic");
+    match result {
+        // Without a PTR record the resolver hands back the address itself
+        Ok(name) if name != ip.to_string() => Some(name),
+        Ok(_) => None,
+        Err(err) => {
+            log::debug!("Reverse lookup of {} failed: {}", ip, err);
+            None
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+/// Parses a human friendly duration such as `500ms`, `10s` or `2m`.
+///
+/// Shared by every timing flag so they all accept the same syntax and report
+/// errors the same way. The error is a plain string so it can be used directly
+/// as a clap `value_parser`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    humantime::parse_duration(s.trim()).map_err(|err| {
+        format!(
+            "invalid duration '{}': {} (expected e.g. 500ms, 10s, 2m)",
+            s, err
+        )
+    })
+}
+
+/// Serde helpers for `Option<Duration>` fields written as human friendly strings.
+pub mod option {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(duration) => serializer.collect_str(&humantime::format_duration(*duration)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| super::parse_duration(&s).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
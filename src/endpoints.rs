@@ -0,0 +1,94 @@
+use std::net::Ipv4Addr;
+
+use reqwest::Url;
+
+use crate::types::GatewayType;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scheme {
+    #[default]
+    Http,
+    Https,
+}
+
+impl Scheme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scheme::Http => "http",
+            Scheme::Https => "https",
+        }
+    }
+}
+
+/// Builds the urls of a gateway's http api from its scheme, host and port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+    base: Url,
+}
+
+impl Endpoints {
+    /// Endpoints on `host` using plain http on the default port.
+    pub fn new(host: &str) -> Result<Self, url::ParseError> {
+        Ok(Self {
+            base: Url::parse(&format!("http://{}/", host))?,
+        })
+    }
+
+    pub fn for_ip(ip: Ipv4Addr) -> Self {
+        Self::new(&ip.to_string()).expect("Ip addresses must be valid url hosts")
+    }
+
+    pub fn with_scheme(mut self, scheme: Scheme) -> Self {
+        self.base
+            .set_scheme(scheme.as_str())
+            .expect("Http urls must accept http schemes");
+        self
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.base
+            .set_port(Some(port))
+            .expect("Http urls must accept a port");
+        self
+    }
+
+    pub fn scheme(&self) -> &str {
+        self.base.scheme()
+    }
+
+    pub fn host(&self) -> &str {
+        self.base.host_str().unwrap_or_default()
+    }
+
+    /// The port used for connections, falling back to the scheme's default.
+    pub fn port(&self) -> u16 {
+        self.base.port_or_known_default().unwrap_or(80)
+    }
+
+    pub fn url(&self, path: &str) -> Url {
+        self.base
+            .join(path.trim_start_matches('/'))
+            .expect("Endpoint paths must be valid")
+    }
+
+    /// The url used to detect (and identify) a gateway of the given type.
+    pub fn detection(&self, gateway: GatewayType) -> Url {
+        match gateway {
+            GatewayType::G1 => self.g1_status(),
+            GatewayType::MG3 => self.mg3_hello(),
+        }
+    }
+
+    pub fn g1_status(&self) -> Url {
+        self.url("/cgi-bin/cgic-statusget")
+    }
+
+    pub fn mg3_hello(&self) -> Url {
+        self.url("/hello")
+    }
+
+    /// Accepts the MG3's `{"action": ...}` commands (get/set config, reboot)
+    pub fn mg3_set(&self) -> Url {
+        self.url("/set")
+    }
+}
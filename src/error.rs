@@ -0,0 +1,464 @@
+use std::{fmt::Display, net::Ipv4Addr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{parse::ResponseError, scanner::InvalidScanConfig};
+
+/// Stable, machine readable classification of probe and management failures.
+///
+/// The serialized names (`E_TIMEOUT`, ...) are part of the output format and must not change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ErrorCode {
+    /// No response within the timeout
+    #[serde(rename = "E_TIMEOUT")]
+    Timeout,
+    /// The tcp connection could not be established (refused, unreachable, ...)
+    #[serde(rename = "E_CONNECT")]
+    Connect,
+    /// The gateway rejected the credentials
+    #[serde(rename = "E_AUTH")]
+    Auth,
+    /// The server answered with an unexpected http status
+    #[serde(rename = "E_HTTP_STATUS")]
+    HttpStatus,
+    /// The response did not have the expected structure
+    #[serde(rename = "E_UNEXPECTED_RESPONSE")]
+    UnexpectedResponse,
+    /// The response contained an invalid mac address
+    #[serde(rename = "E_PARSE_MAC")]
+    ParseMac,
+    /// The operation is not available for this gateway type
+    #[serde(rename = "E_UNSUPPORTED")]
+    Unsupported,
+    /// A target hostname has no ipv4 address
+    #[serde(rename = "E_RESOLVE")]
+    Resolve,
+    /// Anything not covered by a more specific code
+    #[serde(rename = "E_OTHER")]
+    Other,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::Timeout => "E_TIMEOUT",
+            ErrorCode::Connect => "E_CONNECT",
+            ErrorCode::Auth => "E_AUTH",
+            ErrorCode::HttpStatus => "E_HTTP_STATUS",
+            ErrorCode::UnexpectedResponse => "E_UNEXPECTED_RESPONSE",
+            ErrorCode::ParseMac => "E_PARSE_MAC",
+            ErrorCode::Unsupported => "E_UNSUPPORTED",
+            ErrorCode::Resolve => "E_RESOLVE",
+            ErrorCode::Other => "E_OTHER",
+        }
+    }
+
+    /// Code for an unsuccessful http status.
+    pub fn for_status(status: reqwest::StatusCode) -> Self {
+        match status {
+            reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => ErrorCode::Auth,
+            _ => ErrorCode::HttpStatus,
+        }
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// An error explicitly tagged with an [`ErrorCode`].
+#[derive(Debug, Clone)]
+pub struct CodedError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl CodedError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for CodedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CodedError {}
+
+/// Why a connection or request to a gateway got no response.
+#[derive(Debug)]
+pub enum TransportError {
+    /// No answer within the timeout
+    Timeout,
+    /// Nothing listens on the port
+    ConnectionRefused,
+    /// The connection failed otherwise, e.g. with the host unreachable or refused by the proxy
+    Connect { message: String },
+    /// The request failed after connecting, e.g. with the connection reset before the response
+    Request { message: String },
+}
+
+impl TransportError {
+    /// A failure replayed from a cassette, which keeps only its code and message.
+    pub fn recorded(code: ErrorCode, message: String) -> Self {
+        match code {
+            ErrorCode::Timeout => Self::Timeout,
+            ErrorCode::Connect => Self::Connect { message },
+            _ => Self::Request { message },
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Timeout => ErrorCode::Timeout,
+            Self::ConnectionRefused | Self::Connect { .. } => ErrorCode::Connect,
+            Self::Request { .. } => ErrorCode::Other,
+        }
+    }
+}
+
+impl Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "Timeout waiting for the gateway"),
+            Self::ConnectionRefused => write!(f, "Connection refused"),
+            Self::Connect { message } | Self::Request { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+impl From<std::io::Error> for TransportError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::TimedOut => Self::Timeout,
+            std::io::ErrorKind::ConnectionRefused => Self::ConnectionRefused,
+            _ => Self::Connect {
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+impl From<tokio::time::error::Elapsed> for TransportError {
+    fn from(_: tokio::time::error::Elapsed) -> Self {
+        Self::Timeout
+    }
+}
+
+impl From<reqwest::Error> for TransportError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            return Self::Timeout;
+        }
+        let mut message = err.to_string();
+        let mut refused = false;
+        let mut source = std::error::Error::source(&err);
+        while let Some(cause) = source {
+            refused |= cause
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|err| err.kind() == std::io::ErrorKind::ConnectionRefused);
+            message = format!("{}: {}", message, cause);
+            source = cause.source();
+        }
+        if refused {
+            Self::ConnectionRefused
+        } else if err.is_connect() {
+            Self::Connect { message }
+        } else {
+            Self::Request { message }
+        }
+    }
+}
+
+/// Why probing a host found no gateway.
+#[derive(Debug)]
+pub enum DetectError {
+    /// No answer within the timeout
+    Timeout,
+    /// Nothing listens on the port
+    ConnectionRefused,
+    /// The tcp connection failed otherwise, e.g. with the host unreachable
+    Connect { message: String },
+    /// The gateway rejected the credentials with this http status
+    AuthFailed { status: u16 },
+    /// The server answered with an unsuccessful http status
+    HttpStatus { status: u16 },
+    /// The response didn't have the structure of a gateway api
+    UnexpectedResponse { message: String, body: String },
+    /// The response had an invalid mac address
+    ParseMac { message: String, body: String },
+    /// The request failed after connecting
+    Request { message: String },
+}
+
+impl DetectError {
+    /// A response whose body couldn't be parsed as a detection.
+    pub fn response(err: ResponseError, body: &str) -> Self {
+        let message = err.to_string();
+        let body = body.to_string();
+        match err {
+            ResponseError::InvalidMac { .. } => Self::ParseMac { message, body },
+            _ => Self::UnexpectedResponse { message, body },
+        }
+    }
+
+    /// A response with an unsuccessful status.
+    pub fn status(status: reqwest::StatusCode) -> Self {
+        match ErrorCode::for_status(status) {
+            ErrorCode::Auth => Self::AuthFailed {
+                status: status.as_u16(),
+            },
+            _ => Self::HttpStatus {
+                status: status.as_u16(),
+            },
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Timeout => ErrorCode::Timeout,
+            Self::ConnectionRefused | Self::Connect { .. } => ErrorCode::Connect,
+            Self::AuthFailed { .. } => ErrorCode::Auth,
+            Self::HttpStatus { .. } => ErrorCode::HttpStatus,
+            Self::UnexpectedResponse { .. } => ErrorCode::UnexpectedResponse,
+            Self::ParseMac { .. } => ErrorCode::ParseMac,
+            Self::Request { .. } => ErrorCode::Other,
+        }
+    }
+}
+
+impl Display for DetectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "Timeout trying to get gateway response"),
+            Self::ConnectionRefused => write!(f, "Connection refused"),
+            Self::Connect { message } => write!(f, "{}", message),
+            Self::AuthFailed { status } => {
+                write!(f, "Credentials rejected with http status {}", status)
+            }
+            Self::HttpStatus { status } => write!(f, "Unexpected http status {}", status),
+            Self::UnexpectedResponse { message, .. }
+            | Self::ParseMac { message, .. }
+            | Self::Request { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for DetectError {}
+
+impl From<TransportError> for DetectError {
+    fn from(err: TransportError) -> Self {
+        match err {
+            TransportError::Timeout => Self::Timeout,
+            TransportError::ConnectionRefused => Self::ConnectionRefused,
+            TransportError::Connect { message } => Self::Connect { message },
+            TransportError::Request { message } => Self::Request { message },
+        }
+    }
+}
+
+/// Why a request to the management api of a gateway failed.
+#[derive(Debug)]
+pub enum ClientError {
+    /// The request got no response
+    Transport(TransportError),
+    /// The gateway rejected the credentials with this http status
+    AuthFailed { status: u16 },
+    /// The gateway answered with an unsuccessful http status
+    HttpStatus { status: u16 },
+    /// The response isn't json with the structure of the api
+    Json(serde_json::Error),
+    /// The gateway answered the action without the success code, 200
+    Rejected {
+        action: String,
+        code: Option<i64>,
+        message: Option<String>,
+    },
+    /// The gateway type has no api for the action
+    Unsupported { action: &'static str },
+}
+
+impl ClientError {
+    /// A response with an unsuccessful status.
+    pub fn status(status: reqwest::StatusCode) -> Self {
+        match ErrorCode::for_status(status) {
+            ErrorCode::Auth => Self::AuthFailed {
+                status: status.as_u16(),
+            },
+            _ => Self::HttpStatus {
+                status: status.as_u16(),
+            },
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Transport(err) => err.code(),
+            Self::AuthFailed { .. } => ErrorCode::Auth,
+            Self::HttpStatus { .. } => ErrorCode::HttpStatus,
+            Self::Json(_) | Self::Rejected { .. } => ErrorCode::UnexpectedResponse,
+            Self::Unsupported { .. } => ErrorCode::Unsupported,
+        }
+    }
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transport(err) => write!(f, "{}", err),
+            Self::AuthFailed { status } => {
+                write!(f, "Credentials rejected with http status {}", status)
+            }
+            Self::HttpStatus { status } => write!(f, "Unexpected http status {}", status),
+            Self::Json(err) => write!(f, "Invalid gateway response: {}", err),
+            Self::Rejected {
+                action,
+                code,
+                message,
+            } => {
+                write!(f, "{} rejected", action)?;
+                if let Some(code) = code {
+                    write!(f, " with code {}", code)?;
+                }
+                match message {
+                    Some(message) => write!(f, ": {}", message),
+                    None => Ok(()),
+                }
+            }
+            Self::Unsupported { action } => {
+                write!(f, "{} is not supported by this gateway", action)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Transport(err) => Some(err),
+            Self::Json(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<TransportError> for ClientError {
+    fn from(err: TransportError) -> Self {
+        Self::Transport(err)
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+/// Why a scan couldn't run at all.
+#[derive(Debug)]
+pub enum ScanError {
+    InvalidConfig(InvalidScanConfig),
+    /// The http client couldn't be built, e.g. without a tls backend
+    Client(reqwest::Error),
+    /// The runtime of a blocking scan couldn't be started
+    Runtime(std::io::Error),
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidConfig(_) => write!(f, "Invalid scan config"),
+            Self::Client(_) => write!(f, "Error building http client"),
+            Self::Runtime(_) => write!(f, "Error starting tokio runtime"),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::InvalidConfig(err) => Some(err),
+            Self::Client(err) => Some(err),
+            Self::Runtime(err) => Some(err),
+        }
+    }
+}
+
+impl From<InvalidScanConfig> for ScanError {
+    fn from(err: InvalidScanConfig) -> Self {
+        Self::InvalidConfig(err)
+    }
+}
+
+/// Why a hostname target has no address to probe.
+#[derive(Debug)]
+pub enum ResolveError {
+    /// The resolver failed, e.g. for an unknown name
+    Lookup {
+        host: String,
+        source: std::io::Error,
+    },
+    /// The name only has ipv6 addresses
+    NoIpv4 { host: String },
+}
+
+impl Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lookup { host, source } => write!(f, "Error resolving {}: {}", host, source),
+            Self::NoIpv4 { host } => write!(f, "{} has no ipv4 address", host),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Lookup { source, .. } => Some(source),
+            Self::NoIpv4 { .. } => None,
+        }
+    }
+}
+
+/// A failed probe of a single host, as included in scan output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ProbeFailure {
+    pub ip: Ipv4Addr,
+    pub code: ErrorCode,
+    pub message: String,
+    /// The target hostname, for hostnames that could not be resolved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+}
+
+impl ProbeFailure {
+    /// A failed detection of the gateway at `ip`.
+    pub fn new(ip: Ipv4Addr, err: &DetectError) -> Self {
+        Self {
+            ip,
+            code: err.code(),
+            message: err.to_string(),
+            hostname: None,
+        }
+    }
+
+    /// A target hostname that never got an address to probe. The ip is `0.0.0.0`.
+    pub fn unresolved(hostname: &str, err: &ResolveError) -> Self {
+        Self {
+            ip: Ipv4Addr::UNSPECIFIED,
+            code: ErrorCode::Resolve,
+            message: err.to_string(),
+            hostname: Some(hostname.to_string()),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! The host's network interfaces and the ipv4 subnets attached to them.
+
+use std::{fmt::Display, net::Ipv4Addr};
+
+use crate::target::Subnet;
+
+/// An ipv4 address assigned to a local interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    pub name: String,
+    pub ip: Ipv4Addr,
+    /// The attached subnet, from the address and its netmask
+    pub subnet: Subnet,
+    pub up: bool,
+    pub loopback: bool,
+}
+
+impl Interface {
+    /// The VLAN id of a tagged sub-interface named like `eth0.10` or `vlan10`.
+    pub fn vlan(&self) -> Option<u16> {
+        let id = match self.name.rsplit_once('.') {
+            Some((_, id)) => id,
+            None => self.name.strip_prefix("vlan")?,
+        };
+        id.parse().ok().filter(|id| (1..=4094).contains(id))
+    }
+}
+
+/// Error returned when the OS can't list its network interfaces.
+#[derive(Debug)]
+pub struct ListInterfacesError(std::io::Error);
+
+impl Display for ListInterfacesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Error listing network interfaces")
+    }
+}
+
+impl std::error::Error for ListInterfacesError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Lists every ipv4 address of every interface, in the order the OS reports them.
+#[cfg(unix)]
+pub fn list() -> Result<Vec<Interface>, ListInterfacesError> {
+    use std::ffi::CStr;
+
+    let mut addrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: getifaddrs only writes the list head, which is freed below.
+    if unsafe { libc::getifaddrs(&mut addrs) } != 0 {
+        return Err(ListInterfacesError(std::io::Error::last_os_error()));
+    }
+    let mut interfaces = Vec::new();
+    let mut cursor = addrs;
+    while !cursor.is_null() {
+        // SAFETY: every entry of the list stays valid until freeifaddrs.
+        let entry = unsafe { &*cursor };
+        cursor = entry.ifa_next;
+        if entry.ifa_addr.is_null()
+            || entry.ifa_netmask.is_null()
+            // SAFETY: checked for null above.
+            || i32::from(unsafe { (*entry.ifa_addr).sa_family }) != libc::AF_INET
+        {
+            continue;
+        }
+        // SAFETY: both are AF_INET addresses, so they are sockaddr_in.
+        let (ip, netmask) = unsafe { (ipv4(entry.ifa_addr), ipv4(entry.ifa_netmask)) };
+        let prefix = u32::from(netmask).leading_ones() as u8;
+        interfaces.push(Interface {
+            // SAFETY: interface names are nul terminated.
+            name: unsafe { CStr::from_ptr(entry.ifa_name) }
+                .to_string_lossy()
+                .into_owned(),
+            ip,
+            subnet: Subnet::new(ip, prefix).expect("Netmask prefixes are at most 32"),
+            up: entry.ifa_flags & libc::IFF_UP as libc::c_uint != 0,
+            loopback: entry.ifa_flags & libc::IFF_LOOPBACK as libc::c_uint != 0,
+        });
+    }
+    // SAFETY: `addrs` came from getifaddrs and no entry is used past this point.
+    unsafe { libc::freeifaddrs(addrs) };
+    Ok(interfaces)
+}
+
+/// # Safety
+///
+/// `addr` must point to a valid `sockaddr_in`.
+#[cfg(unix)]
+unsafe fn ipv4(addr: *const libc::sockaddr) -> Ipv4Addr {
+    let addr = &*(addr as *const libc::sockaddr_in);
+    Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr))
+}
+
+/// Lists every ipv4 address of every interface, in the order the OS reports them. Adapters are
+/// named by their friendly name, like `Ethernet 2`, when they have one.
+#[cfg(not(unix))]
+pub fn list() -> Result<Vec<Interface>, ListInterfacesError> {
+    let mut interfaces = Vec::new();
+    for adapter in netdev::get_interfaces() {
+        let name = adapter.friendly_name.as_ref().unwrap_or(&adapter.name);
+        for net in &adapter.ipv4 {
+            interfaces.push(Interface {
+                name: name.clone(),
+                ip: net.addr(),
+                subnet: Subnet::new(net.addr(), net.prefix_len())
+                    .expect("Netmask prefixes are at most 32"),
+                up: adapter.is_up(),
+                loopback: adapter.is_loopback(),
+            });
+        }
+    }
+    Ok(interfaces)
+}
+
+/// Up, non-loopback interfaces that are VLAN sub-interfaces.
+pub fn vlans() -> Result<Vec<Interface>, ListInterfacesError> {
+    Ok(list()?
+        .into_iter()
+        .filter(|interface| interface.up && !interface.loopback && interface.vlan().is_some())
+        .collect())
+}
@@ -0,0 +1,55 @@
+use anyhow::Context;
+
+/// Service name all secrets are stored under in the platform keyring.
+pub const SERVICE: &str = "rtls-ctl";
+
+/// Secrets that can be kept in the platform keyring instead of the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Secret {
+    /// Admin password of G1 gateways
+    G1Password,
+    /// Password MG3 gateways use for the MQTT broker
+    MqttPassword,
+}
+
+impl Secret {
+    /// Keyring account of the secret, scoped to a profile so each site can have its own.
+    pub fn account(self, profile: Option<&str>) -> String {
+        let name = match self {
+            Secret::G1Password => "g1",
+            Secret::MqttPassword => "mqtt",
+        };
+        match profile {
+            Some(profile) => format!("{}/{}", profile, name),
+            None => name.to_string(),
+        }
+    }
+
+    fn entry(self, profile: Option<&str>) -> anyhow::Result<keyring::Entry> {
+        keyring::Entry::new(SERVICE, &self.account(profile)).context("Error opening keyring")
+    }
+
+    /// Returns the stored secret, or `None` if there is none.
+    pub fn get(self, profile: Option<&str>) -> anyhow::Result<Option<String>> {
+        match self.entry(profile)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(err).context("Error reading from keyring"),
+        }
+    }
+
+    pub fn set(self, profile: Option<&str>, secret: &str) -> anyhow::Result<()> {
+        self.entry(profile)?
+            .set_password(secret)
+            .context("Error writing to keyring")
+    }
+
+    /// Deletes the stored secret, returning whether there was one.
+    pub fn delete(self, profile: Option<&str>) -> anyhow::Result<bool> {
+        match self.entry(profile)?.delete_password() {
+            Ok(()) => Ok(true),
+            Err(keyring::Error::NoEntry) => Ok(false),
+            Err(err) => Err(err).context("Error deleting from keyring"),
+        }
+    }
+}
@@ -1 +1,31 @@
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod cassette;
+pub mod client;
+pub mod config;
+pub mod diagnostics;
+pub mod diff;
+pub mod discovery;
+#[cfg(feature = "dns")]
+pub mod dns;
+pub mod duration;
+pub mod endpoints;
+pub mod error;
+pub mod interfaces;
+#[cfg(feature = "keychain")]
+pub mod keychain;
+pub mod limits;
+pub mod oui;
+pub mod parse;
+pub mod protocol;
+pub mod report;
+pub mod scanner;
+pub mod schema;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+pub mod snmp;
+pub mod target;
+pub mod transport;
 pub mod types;
+pub mod validation;
+pub mod wol;
@@ -0,0 +1,77 @@
+use std::{fmt::Display, future::Future, time::Duration};
+
+use crate::error::{ClientError, DetectError, TransportError};
+use tokio::{
+    sync::Mutex,
+    time::{Interval, MissedTickBehavior},
+};
+
+/// Spaces out the start of requests to at most `per_second` a second.
+pub struct RateLimiter {
+    interval: Option<Mutex<Interval>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: Option<u32>) -> Self {
+        Self {
+            interval: per_second.map(|per_second| {
+                let mut interval =
+                    tokio::time::interval(Duration::from_secs(1) / per_second.max(1));
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                Mutex::new(interval)
+            }),
+        }
+    }
+
+    pub async fn wait(&self) {
+        if let Some(interval) = &self.interval {
+            interval.lock().await.tick().await;
+        }
+    }
+}
+
+/// Runs `attempt` up to `retries` more times while it fails with a timeout or connection error.
+///
+/// A refused connection means nothing is listening, so it is never retried.
+pub async fn retry<T, E, F, Fut>(
+    retries: u32,
+    limiter: &RateLimiter,
+    mut attempt: F,
+) -> Result<T, E>
+where
+    E: Retryable + Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut remaining = retries;
+    loop {
+        limiter.wait().await;
+        match attempt().await {
+            Err(err) if remaining > 0 && err.is_retryable() => {
+                log::debug!("Retrying after error: {:#}", err);
+                remaining -= 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Errors that tell whether trying again could succeed.
+pub trait Retryable {
+    fn is_retryable(&self) -> bool;
+}
+
+impl Retryable for DetectError {
+    fn is_retryable(&self) -> bool {
+        matches!(self, DetectError::Timeout | DetectError::Connect { .. })
+    }
+}
+
+impl Retryable for ClientError {
+    fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ClientError::Transport(TransportError::Timeout | TransportError::Connect { .. })
+        )
+    }
+}
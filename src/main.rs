@@ -1,72 +1,155 @@
-use anyhow::Context;
-use clap::Parser;
-use log::info;
-use rtls_ctl::types::{GatewayDetection, GatewayType, Mac};
-use serde_json::{json, Value};
-use std::net::IpAddr;
-use std::str::FromStr;
-use std::{net::Ipv4Addr, ops::Range, time::Duration};
-use tokio::{net::TcpStream, time::timeout};
+mod cli;
 
-use futures::StreamExt;
-use futures::TryFutureExt;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use rtls_ctl::transport::{Remap, Socks5Proxy};
+use std::path::PathBuf;
 
-struct RangeWrapper {
-    start: Ipv4Addr,
-    end: Ipv4Addr,
-}
-
-struct RangeWrapperIter {
-    range: Range<u32>,
-}
-
-impl Iterator for RangeWrapperIter {
-    type Item = Ipv4Addr;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        self.range.next().map(Ipv4Addr::from)
-    }
-}
-
-impl IntoIterator for RangeWrapper {
-    type Item = Ipv4Addr;
-
-    type IntoIter = RangeWrapperIter;
-
-    fn into_iter(self) -> Self::IntoIter {
-        RangeWrapperIter {
-            range: Range {
-                start: u32::from(self.start),
-                end: u32::from(self.end),
-            },
-        }
-    }
-}
-
-const CONCURRENCY: usize = 512;
-const TIMEOUT: Duration = Duration::from_secs(3);
+use cli::global::ColorChoice;
+use cli::{
+    bench::BenchArgs, config::ConfigArgs, creds::CredsArgs, diff::DiffArgs, find::FindArgs,
+    gen_docs::GenDocsArgs, gen_fixture::GenFixtureArgs, global::GlobalOptions, jump::SshJump,
+    listen::ListenArgs, progress::ProgressFormat, reboot::RebootArgs, scan::ScanArgs,
+    settings::LoadedConfig, simulate::SimulateArgs, trace::TraceArgs, validate::ValidateArgs,
+    wake::WakeArgs,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
-struct ScanArgs {
-    /// Name of the person to greet
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    scan: ScanArgs,
+    /// Config file to use instead of ~/.config/rtls-ctl/config.toml
+    #[arg(long, global = true, env = "RTLS_CTL_CONFIG")]
+    config: Option<PathBuf>,
+    /// Named profile from the config file (`[profiles.<name>]`) to apply
+    #[arg(long, global = true, env = "RTLS_CTL_PROFILE")]
+    profile: Option<String>,
+    /// Stable tab separated output for scripts instead of json
+    #[arg(long, global = true)]
+    porcelain: bool,
+    /// Never contact anything but the targets, failing features that would need to
+    #[arg(long, global = true, env = "RTLS_CTL_OFFLINE")]
+    offline: bool,
+    /// Sort results canonically and leave out timing dependent fields, for byte stable output
+    #[arg(long, global = true)]
+    deterministic: bool,
+    /// Record every gateway request and response to this cassette file
+    #[arg(
+        long,
+        global = true,
+        value_name = "CASSETTE",
+        conflicts_with = "replay"
+    )]
+    record: Option<PathBuf>,
+    /// Answer gateway requests from a recorded cassette instead of the network
+    #[arg(long, global = true, value_name = "CASSETTE")]
+    replay: Option<PathBuf>,
+    /// Send requests for `host[:port]` to another `host[:port]` (for tests)
+    #[arg(
+        long = "remap-endpoint",
+        global = true,
+        hide = true,
+        env = "RTLS_CTL_REMAP_ENDPOINTS",
+        value_delimiter = ','
+    )]
+    remap: Vec<Remap>,
+    /// PEM file with extra root certificates for gateways using https, overriding `tls.ca_bundle`
+    #[arg(long, global = true, env = "RTLS_CTL_CA_BUNDLE")]
+    ca_bundle: Option<PathBuf>,
+    /// Tunnel tcp probes and gateway requests through a SOCKS5 proxy, like socks5://bastion:1080
+    #[arg(
+        long,
+        global = true,
+        env = "RTLS_CTL_PROXY",
+        conflicts_with_all = ["offline", "ssh_jump"]
+    )]
+    proxy: Option<Socks5Proxy>,
+    /// Tunnel tcp probes and gateway requests through `ssh -D` to this host, like user@bastion
+    #[arg(
+        long,
+        global = true,
+        env = "RTLS_CTL_SSH_JUMP",
+        conflicts_with = "offline"
+    )]
+    ssh_jump: Option<String>,
+    /// Color tables and diffs
     #[arg(
-        help = "Ip range to scan (e.g. 192.168.1.1..192.168.1.20). Default will be chosen based on local ip."
+        long,
+        global = true,
+        value_enum,
+        default_value_t = ColorChoice::Auto,
+        env = "RTLS_CTL_COLOR"
     )]
-    range: Option<String>,
-    #[arg(short, long, default_value_t = CONCURRENCY)]
-    concurrency: usize,
-    #[arg(short, long, action = clap::ArgAction::Count)]
+    color: ColorChoice,
+    /// Report progress of long running commands on stderr
+    #[arg(long, global = true, value_enum)]
+    progress: Option<ProgressFormat>,
+    /// Threads running probes and requests [default: one per 256 scan targets, up to one per cpu]
+    #[arg(
+        long,
+        global = true,
+        env = "RTLS_CTL_WORKER_THREADS",
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    worker_threads: Option<u16>,
+    /// Threads for blocking work like dns lookups and file access [default: 64]
+    #[arg(
+        long,
+        global = true,
+        env = "RTLS_CTL_MAX_BLOCKING_THREADS",
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    max_blocking_threads: Option<u16>,
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
     verbose: u8,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let args = ScanArgs::parse();
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Scan for gateways (the default when no command is given)
+    Scan(Box<ScanArgs>),
+    /// Check a config file for invalid values without running anything
+    Validate(ValidateArgs),
+    /// Inspect the active configuration
+    Config(ConfigArgs),
+    /// Manage passwords stored in the platform keyring
+    Creds(CredsArgs),
+    /// Reboot the gateways of a scan report
+    Reboot(RebootArgs),
+    /// Find the current ip of a gateway from its mac
+    Find(FindArgs),
+    /// Compare two scan reports: added, removed and moved gateways by mac
+    Diff(DiffArgs),
+    /// Send a Wake-on-LAN magic packet to a gateway
+    Wake(WakeArgs),
+    /// Print gateways as they announce themselves, without sending anything
+    Listen(ListenArgs),
+    /// Find out at which layer (arp, icmp, tcp, http, auth) a gateway becomes unreachable
+    Trace(TraceArgs),
+    /// Serve fake G1/MG3 gateways on local addresses for testing
+    Simulate(SimulateArgs),
+    /// Measure scan throughput against simulated gateways
+    Bench(BenchArgs),
+    /// Generate a realistic fake scan report for testing consumers
+    GenFixture(GenFixtureArgs),
+    /// Print the JSON Schema of the scan report
+    Schema,
+    /// Generate man pages from the argument definitions
+    GenDocs(GenDocsArgs),
+}
+
+fn main() -> anyhow::Result<()> {
+    let command = Cli::command();
+    let builtin: Vec<&str> = command.get_subcommands().map(|c| c.get_name()).collect();
+    let args = cli::aliases::expand(std::env::args_os().collect(), &builtin);
+    let matches = command.clone().get_matches_from(args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
 
     env_logger::builder()
         .parse_default_env()
-        .filter_level(match args.verbose {
+        .filter_level(match cli.verbose {
             0 => log::LevelFilter::Warn,
             1 => log::LevelFilter::Info,
             2 => log::LevelFilter::Debug,
@@ -74,133 +157,65 @@ async fn main() -> anyhow::Result<()> {
         })
         .init();
 
-    let (start, end): (Ipv4Addr, Ipv4Addr) = match args.range {
-        Some(s) => {
-            let (s1, s2) = s
-                .split_once("..")
-                .context("Range argument must contain '..'")?;
-            (
-                s1.parse().context(
-                    "Error parsing start ip address. Expected ip v4 address like '192.168.1.1'",
-                )?,
-                s2.parse().context(
-                    "Error parsing end ip address. Expected ip v4 address like '192.168.1.2'",
-                )?,
-            )
-        }
-        None => match local_ip_address::local_ip().context("Error getting local ip address")? {
-            IpAddr::V4(ip) => (
-                Ipv4Addr::new(ip.octets()[0], ip.octets()[1], ip.octets()[2], 1),
-                Ipv4Addr::new(ip.octets()[0], ip.octets()[1], ip.octets()[2], 255),
-            ),
-            IpAddr::V6(_) => {
-                anyhow::bail!(
-                    "Cannot extract a local ipv4 address. Please specify start and end ip range"
-                )
-            }
-        },
+    let targets = match &cli.command {
+        Some(Command::Scan(args)) => args.target_count(),
+        None => cli.scan.target_count(),
+        Some(_) => None,
     };
-
-    info!("Scanning range {}..{}...", start, end);
-
-    let results: Vec<GatewayDetection> = futures::stream::iter(RangeWrapper { start, end })
-        .map(filter_addr)
-        .buffer_unordered(args.concurrency)
-        .filter_map(|v| async move {
-            if let Err(err) = &v {
-                log::trace!("Error: {}", err);
-            }
-            v.ok()
-        })
-        .collect()
-        .await;
-    info!("Scan ended finding {} gateways", results.len());
-
-    println!(
-        "{}",
-        serde_json::to_string_pretty(&results).expect("Gateways must be serializable"),
-    );
-
-    Ok(())
+    cli::runtime::build(cli.worker_threads, cli.max_blocking_threads, targets)?
+        .block_on(run(cli, matches))
 }
 
-async fn filter_addr(ip: Ipv4Addr) -> anyhow::Result<GatewayDetection> {
-    filter_addr_tcp(ip)
-        .await
-        .ok()
-        .context(format!("Error getting tcp connection to {}", ip))?;
-
-    tokio::select! {
-        res = filter_addr_g1(ip).or_else(|_| futures::future::pending()) => {
-                res
+async fn run(cli: Cli, matches: ArgMatches) -> anyhow::Result<()> {
+    // Kept until the command is done, stopping ssh when dropped
+    let jump = match &cli.ssh_jump {
+        Some(destination) => Some(SshJump::start(destination).await?),
+        None => None,
+    };
+    let global = GlobalOptions {
+        porcelain: cli.porcelain,
+        progress: cli.progress,
+        offline: cli.offline,
+        deterministic: cli.deterministic,
+        record: cli.record,
+        replay: cli.replay,
+        remap: cli.remap,
+        ca_bundle: cli.ca_bundle,
+        proxy: cli
+            .proxy
+            .or_else(|| jump.as_ref().map(|jump| jump.proxy.clone())),
+        color: cli.color,
+    };
+    let config_path = cli.config.as_deref();
+    let load_config = || LoadedConfig::load(config_path, cli.profile.as_deref());
+    match (cli.command, matches.subcommand()) {
+        (Some(Command::Scan(args)), Some((_, matches))) => {
+            cli::scan::run(*args, matches, &load_config()?, global).await
         }
-        res = filter_addr_mg3(ip).or_else(|_| futures::future::pending()) => {
-            res
+        (Some(Command::Validate(args)), _) => cli::validate::run(args, config_path),
+        (Some(Command::Config(args)), Some((_, matches))) => {
+            cli::config::run(args, matches, &load_config()?, global)
         }
-        _ = tokio::time::sleep(TIMEOUT) => {
-            Err(anyhow::anyhow!("Timeout trying to get gateway response"))
+        (Some(Command::Creds(args)), _) => cli::creds::run(args, cli.profile.as_deref()),
+        (Some(Command::Reboot(args)), _) => cli::reboot::run(args, &load_config()?, global).await,
+        (Some(Command::Find(args)), _) => cli::find::run(args, &load_config()?, global).await,
+        (Some(Command::Diff(args)), _) => cli::diff::run(args, global),
+        (Some(Command::Wake(args)), _) => cli::wake::run(args, &load_config()?, global).await,
+        (Some(Command::Listen(args)), _) => cli::listen::run(args, global).await,
+        (Some(Command::Trace(args)), _) => cli::trace::run(args, &load_config()?, global).await,
+        (Some(Command::Simulate(args)), _) => cli::simulate::run(args, global).await,
+        (Some(Command::Bench(args)), _) => cli::bench::run(args, global).await,
+        (Some(Command::GenFixture(args)), _) => cli::gen_fixture::run(args),
+        (Some(Command::Schema), _) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&rtls_ctl::schema::scan_report())
+                    .expect("Schemas must be serializable")
+            );
+            Ok(())
         }
-    }
-}
-
-async fn filter_addr_tcp(ip: Ipv4Addr) -> anyhow::Result<()> {
-    timeout(TIMEOUT, TcpStream::connect((ip, 80))).await??;
-    Ok(())
-}
-
-async fn filter_addr_g1(ip: Ipv4Addr) -> anyhow::Result<GatewayDetection> {
-    let response: Value = reqwest::Client::new()
-        .post(format!("http://{}/cgi-bin/cgic-statusget", ip))
-        .header("Authorization", "Basic YWRtaW46")
-        .json(&json! {{
-            "header": {
-                "version": 1,
-            },
-        }})
-        .send()
-        .await?
-        .json()
-        .await?;
-
-    if response["header"]["code"] == json!(200) {
-        Ok(GatewayDetection {
-            ip,
-            gateway: GatewayType::G1,
-            mac: Mac::from_str(
-                response["body"]["gateway"]["status"]["mac"]
-                    .as_str()
-                    .ok_or_else(|| {
-                        anyhow::anyhow!("Error parsing mac address from response {:?}", response)
-                    })?,
-            )?,
-        })
-    } else {
-        Err(anyhow::anyhow!(
-            "Error mac not found in response {:?}",
-            response
-        ))
-    }
-}
-
-async fn filter_addr_mg3(ip: Ipv4Addr) -> anyhow::Result<GatewayDetection> {
-    let response: Value = reqwest::get(format!("http://{}/hello", ip))
-        .await?
-        .json()
-        .await?;
-
-    if let Some(mac) = response["mac"].as_str() {
-        Ok(GatewayDetection {
-            ip,
-            gateway: GatewayType::MG3,
-            mac: Mac::from_str(mac).context(format!(
-                "Error parsing mac address from response {:?}",
-                response
-            ))?,
-        })
-    } else {
-        Err(anyhow::anyhow!(
-            "Error mac not found in response {:?}",
-            response
-        ))
+        (Some(Command::GenDocs(args)), _) => cli::gen_docs::run(args, Cli::command()),
+        (None, _) => cli::scan::run(cli.scan, &matches, &load_config()?, global).await,
+        (Some(_), None) => unreachable!("Parsed subcommands must have matches"),
     }
 }
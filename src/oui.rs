@@ -0,0 +1,55 @@
+//! Manufacturers of mac addresses from their OUI, the first three bytes, to tell gateways apart
+//! from other devices answering on their port.
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Context;
+
+use crate::{parse, types::Mac};
+
+/// Vendors of gateways and of devices often found next to them on RTLS networks
+const BUILTIN: &[([u8; 3], &str)] = &[
+    ([0xAC, 0x23, 0x3F], "Shenzhen Minew Technologies"),
+    ([0xB8, 0x27, 0xEB], "Raspberry Pi Foundation"),
+    ([0xDC, 0xA6, 0x32], "Raspberry Pi Trading"),
+    ([0x00, 0x50, 0x56], "VMware"),
+    ([0x00, 0x00, 0x0C], "Cisco Systems"),
+];
+
+/// Vendor names by OUI.
+#[derive(Debug, Clone)]
+pub struct OuiTable {
+    vendors: HashMap<[u8; 3], String>,
+}
+
+impl OuiTable {
+    /// The table bundled with rtls-ctl, only covering the vendors most relevant to gateways.
+    pub fn builtin() -> Self {
+        Self {
+            vendors: BUILTIN
+                .iter()
+                .map(|(oui, vendor)| (*oui, vendor.to_string()))
+                .collect(),
+        }
+    }
+
+    /// The bundled table extended with the entries of an OUI file, like the IEEE `oui.txt` or
+    /// Wireshark's `manuf`. Entries of the file take precedence.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Error reading OUI file {}", path.display()))?;
+        let mut table = Self::builtin();
+        let entries = parse::oui_table(&contents);
+        if entries.is_empty() {
+            anyhow::bail!("No OUI entries found in {}", path.display());
+        }
+        log::debug!("Loaded {} OUIs from {}", entries.len(), path.display());
+        table.vendors.extend(entries);
+        Ok(table)
+    }
+
+    pub fn vendor(&self, mac: Mac) -> Option<&str> {
+        let oui = [mac.bytes[0], mac.bytes[1], mac.bytes[2]];
+        self.vendors.get(&oui).map(String::as_str)
+    }
+}
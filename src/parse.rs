@@ -0,0 +1,442 @@
+//! Parsers for everything read from users and gateways, kept free of I/O (and mostly of
+//! allocations) so they can be fuzzed and property tested directly.
+
+use std::fmt::Display;
+
+use serde_json::{Map, Value};
+
+use crate::{
+    error::ErrorCode,
+    protocol::{G1StatusResponse, Mg3Hello},
+    target::{IpRange, ParseTargetError, Subnet, Targets},
+    types::Mac,
+};
+
+/// Parses `AA:BB:CC:DD:EE:FF` or `aabbccddeeff`, ignoring colons anywhere.
+pub fn mac(s: &str) -> Result<Mac, hex::FromHexError> {
+    let mut bytes = [0u8; 6];
+    let mut digits = 0;
+    for (index, c) in s.char_indices() {
+        if c == ':' {
+            continue;
+        }
+        let value = c
+            .to_digit(16)
+            .ok_or(hex::FromHexError::InvalidHexCharacter { c, index })?;
+        if digits == 12 {
+            return Err(hex::FromHexError::InvalidStringLength);
+        }
+        bytes[digits / 2] = bytes[digits / 2] << 4 | value as u8;
+        digits += 1;
+    }
+    match digits {
+        12 => Ok(Mac { bytes }),
+        digits if digits % 2 == 1 => Err(hex::FromHexError::OddLength),
+        _ => Err(hex::FromHexError::InvalidStringLength),
+    }
+}
+
+/// Parses `start..end`, see [`IpRange`].
+pub fn ip_range(s: &str) -> Result<IpRange, ParseTargetError> {
+    s.parse()
+}
+
+/// Parses `network/prefix`, see [`Subnet`].
+pub fn subnet(s: &str) -> Result<Subnet, ParseTargetError> {
+    s.parse()
+}
+
+/// Parses a comma separated list of ranges, subnets and addresses, see [`Targets`].
+pub fn targets(s: &str) -> Result<Targets, ParseTargetError> {
+    s.parse()
+}
+
+/// Why a gateway response gave no mac address.
+#[derive(Debug)]
+pub enum ResponseError {
+    /// The body isn't json with the structure of the api
+    Json(serde_json::Error),
+    /// The response reports a failure or leaves out the mac, in debug formatting
+    MissingMac { response: String },
+    /// The response's mac is not a mac address
+    InvalidMac {
+        mac: String,
+        source: hex::FromHexError,
+    },
+}
+
+impl ResponseError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Json(_) | Self::MissingMac { .. } => ErrorCode::UnexpectedResponse,
+            Self::InvalidMac { .. } => ErrorCode::ParseMac,
+        }
+    }
+}
+
+impl Display for ResponseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Json(err) => write!(f, "Invalid gateway response: {}", err),
+            Self::MissingMac { response } => {
+                write!(f, "Error mac not found in response {}", response)
+            }
+            Self::InvalidMac { mac, source } => {
+                write!(f, "Error parsing mac address '{}': {}", mac, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ResponseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Json(err) => Some(err),
+            Self::MissingMac { .. } => None,
+            Self::InvalidMac { source, .. } => Some(source),
+        }
+    }
+}
+
+/// The mac address from the body of a G1 `cgic-statusget` response.
+pub fn g1_status(body: &str) -> Result<Mac, ResponseError> {
+    let response: G1StatusResponse = serde_json::from_str(body).map_err(ResponseError::Json)?;
+    let address = Some(&response)
+        .filter(|response| response.header.code == Some(200))
+        .and_then(G1StatusResponse::status)
+        .and_then(|status| status.mac.as_deref())
+        .ok_or_else(|| ResponseError::MissingMac {
+            response: format!("{:?}", response),
+        })?;
+    response_mac(address)
+}
+
+/// The mac address from the body of an MG3 `/hello` response.
+pub fn mg3_hello(body: &str) -> Result<Mac, ResponseError> {
+    let response: Mg3Hello = serde_json::from_str(body).map_err(ResponseError::Json)?;
+    let address = response
+        .mac
+        .as_deref()
+        .ok_or_else(|| ResponseError::MissingMac {
+            response: format!("{:?}", response),
+        })?;
+    response_mac(address)
+}
+
+fn response_mac(address: &str) -> Result<Mac, ResponseError> {
+    mac(address).map_err(|source| ResponseError::InvalidMac {
+        mac: address.to_string(),
+        source,
+    })
+}
+
+/// Firmware version and hardware model a gateway reports next to its mac, when it does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FirmwareInfo {
+    pub firmware: Option<String>,
+    pub model: Option<String>,
+}
+
+/// The firmware info of a G1 `cgic-statusget` response, from the status holding the mac.
+pub fn g1_firmware(body: &str) -> FirmwareInfo {
+    serde_json::from_str::<G1StatusResponse>(body)
+        .ok()
+        .and_then(|response| response.status().map(|status| firmware_info(&status.other)))
+        .unwrap_or_default()
+}
+
+/// The firmware info of an MG3 `/hello` response.
+pub fn mg3_firmware(body: &str) -> FirmwareInfo {
+    serde_json::from_str::<Mg3Hello>(body)
+        .map(|response| firmware_info(&response.other))
+        .unwrap_or_default()
+}
+
+/// Firmware versions differ in how they name the fields, the first one present is used.
+fn firmware_info(fields: &Map<String, Value>) -> FirmwareInfo {
+    let field = |names: &[&str]| {
+        names.iter().find_map(|name| match fields.get(*name)? {
+            Value::String(value) if !value.is_empty() => Some(value.clone()),
+            Value::Number(value) => Some(value.to_string()),
+            _ => None,
+        })
+    };
+    FirmwareInfo {
+        firmware: field(&[
+            "firmware",
+            "firmware_version",
+            "firmwareVersion",
+            "fw_version",
+            "version",
+        ]),
+        model: field(&["model", "hardware", "product"]),
+    }
+}
+
+/// The gateways of an inventory, a mac per line optionally followed by a name. Empty lines and
+/// lines starting with '#' are skipped.
+pub fn inventory(contents: &str) -> Result<Vec<(Mac, Option<String>)>, InvalidInventoryLine> {
+    contents
+        .lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(idx, line)| {
+            let (address, name) = match line.split_once(char::is_whitespace) {
+                Some((address, name)) => (address, Some(name.trim().to_string())),
+                None => (line, None),
+            };
+            let mac = mac(&address.replace('-', ":")).map_err(|source| InvalidInventoryLine {
+                line: idx + 1,
+                mac: address.to_string(),
+                source,
+            })?;
+            Ok((mac, name))
+        })
+        .collect()
+}
+
+/// A line of an inventory that doesn't start with a mac address.
+#[derive(Debug)]
+pub struct InvalidInventoryLine {
+    pub line: usize,
+    pub mac: String,
+    pub source: hex::FromHexError,
+}
+
+impl Display for InvalidInventoryLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid mac '{}' on line {}", self.mac, self.line)
+    }
+}
+
+impl std::error::Error for InvalidInventoryLine {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// The OUIs and vendor names of an IEEE `oui.txt` (`AC-23-3F   (hex)  Vendor` lines) or of a
+/// table with an OUI and a vendor per line, like Wireshark's tab separated `manuf` where the
+/// last field is the full name. Lines that don't start with an OUI are skipped.
+pub fn oui_table(contents: &str) -> Vec<([u8; 3], String)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (oui, vendor) = match line.split_once("(hex)") {
+                Some((oui, vendor)) => (oui.trim(), vendor),
+                None => line
+                    .trim()
+                    .split_once(|c: char| c.is_whitespace() || c == ',')?,
+            };
+            let digits: String = oui
+                .chars()
+                .filter(|c| !matches!(c, ':' | '-' | '.'))
+                .collect();
+            let bytes: [u8; 3] = hex::decode(digits).ok()?.try_into().ok()?;
+            let vendor = vendor.rsplit('\t').next()?.trim().trim_matches('"');
+            (!vendor.is_empty()).then(|| (bytes, vendor.to_string()))
+        })
+        .collect()
+}
+
+/// The mac address from a G1 answer to the udp discovery broadcast, text lines holding the
+/// hostname and a mac like `00-04-A3-12-34-56`.
+pub fn g1_announcement(payload: &[u8]) -> Option<Mac> {
+    String::from_utf8_lossy(payload)
+        .lines()
+        .find_map(|line| mac(&line.trim().replace('-', ":")).ok())
+}
+
+/// The complete entries of linux's `/proc/net/arp` neighbor table as ip and mac pairs.
+pub fn proc_net_arp(table: &str) -> Vec<(std::net::Ipv4Addr, Mac)> {
+    // Neighbor entries with a resolved mac have the ATF_COM flag
+    const COMPLETE: u32 = 0x2;
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [ip, _, flags, address, ..] = fields[..] else {
+                return None;
+            };
+            let flags = u32::from_str_radix(flags.trim_start_matches("0x"), 16).ok()?;
+            if flags & COMPLETE == 0 {
+                return None;
+            }
+            Some((ip.parse().ok()?, mac(address).ok()?))
+        })
+        .collect()
+}
+
+/// The leases of an ISC dhcpd `dhcpd.leases` or a dnsmasq `dnsmasq.leases` file as ip and mac
+/// pairs, detecting the format from the contents.
+///
+/// ISC files are a log where later blocks replace earlier ones for the same address, leases not
+/// in the `active` binding state are left out.
+pub fn dhcp_leases(contents: &str) -> Vec<(std::net::Ipv4Addr, Mac)> {
+    if contents.contains('{') {
+        isc_dhcpd_leases(contents)
+    } else {
+        // <expiry> <mac> <ip> <hostname> <client id>
+        contents
+            .lines()
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let [_, address, ip, ..] = fields[..] else {
+                    return None;
+                };
+                Some((ip.parse().ok()?, mac(address).ok()?))
+            })
+            .collect()
+    }
+}
+
+fn isc_dhcpd_leases(contents: &str) -> Vec<(std::net::Ipv4Addr, Mac)> {
+    let mut leases: Vec<(std::net::Ipv4Addr, Option<Mac>)> = Vec::new();
+    let mut current: Option<(std::net::Ipv4Addr, Option<Mac>, bool)> = None;
+    for line in contents.lines().map(str::trim) {
+        let statement = line.trim_end_matches(';');
+        if let Some(ip) = line
+            .strip_prefix("lease ")
+            .and_then(|rest| rest.trim_end_matches('{').trim().parse().ok())
+        {
+            current = Some((ip, None, true));
+        } else if let Some((_, address, active)) = current.as_mut() {
+            if let Some(hardware) = statement.strip_prefix("hardware ethernet ") {
+                *address = mac(hardware.trim()).ok();
+            } else if let Some(state) = statement.strip_prefix("binding state ") {
+                *active = state.trim() == "active";
+            } else if line == "}" {
+                let (ip, address, active) = current.take().expect("Checked by the match");
+                leases.retain(|(leased, _)| *leased != ip);
+                if active {
+                    leases.push((ip, address));
+                }
+            }
+        }
+    }
+    leases
+        .into_iter()
+        .filter_map(|(ip, address)| Some((ip, address?)))
+        .collect()
+}
+
+/// The ipv4 hosts of an nmap xml report (`nmap -oX`) with their open tcp ports.
+///
+/// Only the elements and attributes needed are read, with the layout nmap writes: one `<host>`
+/// element per host holding `<address>` and `<port>` elements.
+pub fn nmap_xml(xml: &str) -> Vec<(std::net::Ipv4Addr, Vec<u16>)> {
+    xml.split("<host ")
+        .skip(1)
+        .filter_map(|host| {
+            let host = &host[..host.find("</host>").unwrap_or(host.len())];
+            let ip = xml_elements(host, "address")
+                .find(|address| xml_attribute(address, "addrtype") == Some("ipv4"))
+                .and_then(|address| xml_attribute(address, "addr")?.parse().ok())?;
+            let ports = host
+                .split("<port ")
+                .skip(1)
+                .filter(|port| {
+                    xml_attribute(port, "protocol") == Some("tcp")
+                        && xml_elements(port, "state")
+                            .next()
+                            .and_then(|state| xml_attribute(state, "state"))
+                            == Some("open")
+                })
+                .filter_map(|port| xml_attribute(port, "portid")?.parse().ok())
+                .collect();
+            Some((ip, ports))
+        })
+        .collect()
+}
+
+/// The attribute lists of every `<name ...>` tag in `xml`.
+fn xml_elements<'a>(xml: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    xml.split('<').filter_map(move |tag| {
+        let attributes = tag.strip_prefix(name)?;
+        attributes
+            .starts_with(char::is_whitespace)
+            .then(|| &attributes[..attributes.find('>').unwrap_or(attributes.len())])
+    })
+}
+
+/// The value of a double quoted attribute in the text of a tag.
+fn xml_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+    tag.split_whitespace().find_map(|attribute| {
+        attribute
+            .strip_prefix(name)?
+            .strip_prefix("=\"")?
+            .split('"')
+            .next()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn ip_range_strategy() -> impl Strategy<Value = IpRange> {
+        (any::<u32>(), any::<u32>())
+            .prop_map(|(start, end)| IpRange::new(Ipv4Addr::from(start), Ipv4Addr::from(end)))
+    }
+
+    proptest! {
+        #[test]
+        fn mac_round_trips(bytes in any::<[u8; 6]>()) {
+            let address = Mac { bytes };
+            prop_assert_eq!(mac(&address.to_string()).unwrap(), address);
+            prop_assert_eq!(mac(&hex::encode(bytes)).unwrap(), address);
+        }
+
+        #[test]
+        fn mac_never_panics(s in "\\PC*") {
+            let _ = mac(&s);
+        }
+
+        #[test]
+        fn ip_range_round_trips(range in ip_range_strategy()) {
+            prop_assert_eq!(ip_range(&range.to_string()).unwrap(), range);
+        }
+
+        #[test]
+        fn ip_range_never_panics(s in "\\PC*") {
+            let _ = ip_range(&s);
+        }
+
+        #[test]
+        fn subnet_round_trips(ip in any::<u32>(), prefix in 0u8..=32) {
+            let parsed = Subnet::new(Ipv4Addr::from(ip), prefix).unwrap();
+            prop_assert_eq!(subnet(&parsed.to_string()).unwrap(), parsed);
+            prop_assert!(parsed.contains(Ipv4Addr::from(ip)));
+        }
+
+        #[test]
+        fn subnet_rejects_long_prefixes(ip in any::<u32>(), prefix in 33u8..) {
+            let s = format!("{}/{}", Ipv4Addr::from(ip), prefix);
+            prop_assert!(subnet(&s).is_err());
+        }
+
+        #[test]
+        fn subnet_never_panics(s in "\\PC*") {
+            let _ = subnet(&s);
+        }
+
+        #[test]
+        fn targets_round_trip(ranges in prop::collection::vec(ip_range_strategy(), 1..8)) {
+            let expected = Targets::new(ranges);
+            prop_assume!(!expected.is_empty());
+            prop_assert_eq!(targets(&expected.to_string()).unwrap(), expected);
+        }
+
+        #[test]
+        fn targets_never_panic(s in "[0-9a-z./,: -]*") {
+            let _ = targets(&s);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! The json bodies of the gateway apis, for tools parsing the same payloads.
+//!
+//! Only the fields detection and management rely on are typed, firmware versions differ in the
+//! rest so those are kept as they are.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Body of a G1 `cgic-statusget` response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct G1StatusResponse {
+    pub header: G1Header,
+    /// Missing from responses with an error code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<G1StatusBody>,
+}
+
+impl G1StatusResponse {
+    /// The status of the gateway, holding its mac.
+    pub fn status(&self) -> Option<&G1Status> {
+        self.body.as_ref().map(|body| &body.gateway.status)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct G1Header {
+    /// 200 when the request succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<i64>,
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct G1StatusBody {
+    pub gateway: G1Gateway,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct G1Gateway {
+    pub status: G1Status,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct G1Status {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    /// Firmware, model and the other fields of the status
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// Body of an MG3 `/hello` response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mg3Hello {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    /// Firmware, model and the other fields of the response
+    #[serde(flatten)]
+    pub other: Map<String, Value>,
+}
+
+/// Body of an MG3 `/set` response, with the config for `getConfig`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mg3SetResponse {
+    /// 200 when the action succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(flatten)]
+    pub config: Mg3Config,
+}
+
+/// The config of an MG3, read with `getConfig` and written with `SetConfig`. Sections and
+/// fields left out of a `SetConfig` keep their value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Mg3Config {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<Mg3MqttConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan: Option<Mg3ScanConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub common: Option<Mg3CommonConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub other: Option<Mg3OtherConfig>,
+}
+
+/// The broker the gateway publishes to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Mg3MqttConfig {
+    /// Like `mqtts://broker:8833`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mqtt_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keepalive: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qos: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish_topic: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscribe_topic: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_topic: Option<String>,
+    /// 1 to use tls
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub use_ssl: Option<u8>,
+    /// The other fields of the section
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// How the gateway scans for BLE advertisements.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Mg3ScanConfig {
+    /// Scan interval
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub itvl: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<u32>,
+    /// 1 for passive scanning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passive: Option<u8>,
+    /// 1 to report each device once
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter_duplicates: Option<u8>,
+    /// The other fields of the section
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// What the gateway uploads and how.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Mg3CommonConfig {
+    /// Like `mqtt`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<String>,
+    /// Seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload_interval: Option<u32>,
+    /// Devices weaker than this are left out, in dBm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex_mac: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex_raw: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac_list: Option<String>,
+    /// The other fields of the section
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Mg3OtherConfig {
+    /// 1 to turn the led on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub led_on: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeserver: Option<String>,
+    /// Like `UTC`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    /// The other fields of the section
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
@@ -0,0 +1,377 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    io::Read,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    error::ProbeFailure,
+    target::Targets,
+    types::{GatewayDetection, GatewayType, Mac},
+};
+
+/// Version of the [`ScanReport`] structure written by this build.
+///
+/// Version 1 was a bare JSON array of detections, version 2 introduced the envelope.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Output envelope of a scan.
+///
+/// Like [`GatewayDetection`] the field order and names are stable, new fields are appended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ScanReport {
+    pub schema_version: u32,
+    pub gateways: Vec<GatewayDetection>,
+    /// Failed probes, only present when requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<ProbeFailure>,
+    /// What was scanned and when, missing from reports of older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan: Option<ScanInfo>,
+}
+
+/// Describes the scan a report came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ScanInfo {
+    /// Version of rtls-ctl that ran the scan
+    pub tool_version: String,
+    /// Addresses probed, as a comma separated target list
+    pub targets: String,
+    /// Http port probed
+    pub port: u16,
+    /// RFC 3339 time the scan started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    /// How long the scan took
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+impl ScanInfo {
+    pub fn new(targets: String, port: u16) -> Self {
+        Self {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            targets,
+            port,
+            started_at: None,
+            duration_ms: None,
+        }
+    }
+
+    /// When the scan finished, unknown for canonical reports and reports of older versions.
+    pub fn finished_at(&self) -> Option<SystemTime> {
+        let started = humantime::parse_rfc3339(self.started_at.as_deref()?).ok()?;
+        Some(started + Duration::from_millis(self.duration_ms?))
+    }
+}
+
+impl ScanReport {
+    pub fn new(gateways: Vec<GatewayDetection>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            gateways,
+            errors: Vec::new(),
+            scan: None,
+        }
+    }
+
+    /// Puts the report in canonical form so repeated scans of the same network are byte for byte
+    /// identical: gateways and errors sorted by ip. Fields that depend on timing must be
+    /// cleared here as they are added.
+    pub fn canonicalize(&mut self) {
+        self.gateways
+            .sort_by_key(|gateway| (gateway.ip, gateway.mac));
+        for gateway in &mut self.gateways {
+            gateway.rtt_ms = None;
+        }
+        if let Some(scan) = &mut self.scan {
+            scan.started_at = None;
+            scan.duration_ms = None;
+        }
+        self.errors.sort_by_key(|failure| failure.ip);
+    }
+
+    /// Reads a report written by any supported version, upgrading it to the current structure.
+    pub fn from_value(value: Value) -> Result<Self, ReportError> {
+        match value {
+            Value::Array(_) => {
+                let gateways = serde_json::from_value(value)
+                    .map_err(|source| ReportError::Invalid { version: 1, source })?;
+                Ok(Self::new(gateways))
+            }
+            Value::Object(ref object) => {
+                let version = object
+                    .get("schema_version")
+                    .and_then(Value::as_u64)
+                    .ok_or(ReportError::MissingSchemaVersion)?;
+                if version > u64::from(SCHEMA_VERSION) {
+                    return Err(ReportError::UnsupportedSchemaVersion { version });
+                }
+                let mut report: Self = serde_json::from_value(value)
+                    .map_err(|source| ReportError::Invalid { version, source })?;
+                report.schema_version = SCHEMA_VERSION;
+                Ok(report)
+            }
+            _ => Err(ReportError::NotAReport),
+        }
+    }
+
+    /// How the gateways changed since the `old` report, matched by mac: gateways only in the old
+    /// report are removed, gateways only in this one added and gateways with another ip moved.
+    pub fn changes_since(&self, old: &ScanReport) -> Vec<GatewayChange> {
+        let current: HashMap<Mac, &GatewayDetection> = self
+            .gateways
+            .iter()
+            .map(|gateway| (gateway.mac, gateway))
+            .collect();
+        let previous: HashMap<Mac, &GatewayDetection> = old
+            .gateways
+            .iter()
+            .map(|gateway| (gateway.mac, gateway))
+            .collect();
+        let mut changes = Vec::new();
+        for gateway in &old.gateways {
+            match current.get(&gateway.mac) {
+                None => changes.push(GatewayChange::Removed {
+                    gateway: gateway.clone(),
+                }),
+                Some(now) if now.ip != gateway.ip => changes.push(GatewayChange::Moved {
+                    mac: gateway.mac,
+                    gateway: now.gateway,
+                    old_ip: gateway.ip,
+                    new_ip: now.ip,
+                }),
+                Some(_) => {}
+            }
+        }
+        for gateway in &self.gateways {
+            if !previous.contains_key(&gateway.mac) {
+                changes.push(GatewayChange::Added {
+                    gateway: gateway.clone(),
+                });
+            }
+        }
+        changes
+    }
+
+    /// The targets of the scan, missing from reports of older versions.
+    pub fn targets(&self) -> Option<Targets> {
+        self.scan.as_ref()?.targets.parse().ok()
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, ReportError> {
+        let value = serde_json::from_reader(reader).map_err(ReportError::Json)?;
+        Self::from_value(value)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReportError> {
+        let path = path.as_ref();
+        std::fs::File::open(path)
+            .map_err(ReportError::Open)
+            .and_then(|file| Self::from_reader(std::io::BufReader::new(file)))
+            .map_err(|err| ReportError::File {
+                path: path.to_path_buf(),
+                source: Box::new(err),
+            })
+    }
+}
+
+/// Why a scan report couldn't be read.
+#[derive(Debug)]
+pub enum ReportError {
+    /// The report file couldn't be opened
+    Open(std::io::Error),
+    /// The report isn't valid JSON
+    Json(serde_json::Error),
+    /// The report is an object without a `schema_version`
+    MissingSchemaVersion,
+    /// The report was written by a newer version of the tool
+    UnsupportedSchemaVersion { version: u64 },
+    /// The report doesn't have the structure of its schema version
+    Invalid {
+        version: u64,
+        source: serde_json::Error,
+    },
+    /// The report is neither a JSON array (version 1) nor an object
+    NotAReport,
+    /// The report file at `path` couldn't be loaded
+    File {
+        path: PathBuf,
+        source: Box<ReportError>,
+    },
+}
+
+impl Display for ReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Open(_) => write!(f, "Error opening scan report"),
+            Self::Json(_) => write!(f, "Scan report is not valid JSON"),
+            Self::MissingSchemaVersion => write!(f, "Scan report is missing 'schema_version'"),
+            Self::UnsupportedSchemaVersion { version } => write!(
+                f,
+                "Scan report schema version {} is newer than the supported version {}",
+                version, SCHEMA_VERSION
+            ),
+            Self::Invalid { version, .. } => {
+                write!(f, "Error parsing version {} scan report", version)
+            }
+            Self::NotAReport => write!(f, "Scan report must be a JSON array or object"),
+            Self::File { path, .. } => write!(f, "Error loading scan report {}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Open(err) => Some(err),
+            Self::Json(err) | Self::Invalid { source: err, .. } => Some(err),
+            Self::File { source, .. } => Some(source),
+            Self::MissingSchemaVersion
+            | Self::UnsupportedSchemaVersion { .. }
+            | Self::NotAReport => None,
+        }
+    }
+}
+
+/// A difference between the gateways of two scans, see [`ScanReport::changes_since`].
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum GatewayChange {
+    Added {
+        gateway: GatewayDetection,
+    },
+    Removed {
+        gateway: GatewayDetection,
+    },
+    Moved {
+        mac: Mac,
+        gateway: GatewayType,
+        old_ip: Ipv4Addr,
+        new_ip: Ipv4Addr,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{error::ErrorCode, parse};
+
+    /// A report with every optional field set, so renaming or reordering any of them shows up.
+    fn report() -> ScanReport {
+        let mut gateway = GatewayDetection::new(
+            Ipv4Addr::new(192, 168, 1, 20),
+            GatewayType::MG3,
+            parse::mac("AC:23:3F:00:00:01").unwrap(),
+        );
+        gateway.interface = Some("eth0.10".to_string());
+        gateway.vlan = Some(10);
+        gateway.hostname = Some("gw-1.local".to_string());
+        gateway.port = Some(8080);
+        gateway.rtt_ms = Some(12.5);
+        gateway.firmware = Some("1.2.3".to_string());
+        gateway.model = Some("MG3".to_string());
+        gateway.vendor = Some("Minew".to_string());
+        gateway.unconfirmed = true;
+        let mut report = ScanReport::new(vec![gateway]);
+        report.errors = vec![ProbeFailure {
+            ip: Ipv4Addr::UNSPECIFIED,
+            code: ErrorCode::Resolve,
+            message: "gw-2.local did not resolve".to_string(),
+            hostname: Some("gw-2.local".to_string()),
+        }];
+        report.scan = Some(ScanInfo {
+            // Pinned so version bumps don't touch the fixture
+            tool_version: "0.1.0".to_string(),
+            started_at: Some("2026-01-01T00:00:00Z".to_string()),
+            duration_ms: Some(2500),
+            ..ScanInfo::new("192.168.1.1..192.168.1.255,gw-2.local".to_string(), 8080)
+        });
+        report
+    }
+
+    #[test]
+    fn schema_v2_matches_fixture() {
+        assert_eq!(report().schema_version, 2);
+        assert_eq!(
+            serde_json::to_string_pretty(&report()).unwrap(),
+            include_str!("../tests/fixtures/report_v2.json").trim_end()
+        );
+    }
+
+    #[test]
+    fn schema_v2_fixture_loads() {
+        let loaded =
+            ScanReport::from_reader(include_str!("../tests/fixtures/report_v2.json").as_bytes())
+                .unwrap();
+        assert_eq!(
+            serde_json::to_value(loaded).unwrap(),
+            serde_json::to_value(report()).unwrap()
+        );
+    }
+
+    #[test]
+    fn v1_array_loads_as_current_schema() {
+        let report = ScanReport::from_value(json!([
+            { "ip": "192.168.1.20", "gateway": "MG3", "mac": "AC:23:3F:00:00:01" },
+            { "ip": "192.168.1.21", "gateway": "G1", "mac": "AC:23:3F:00:00:02" },
+        ]))
+        .unwrap();
+        assert_eq!(report.schema_version, SCHEMA_VERSION);
+        assert!(report.errors.is_empty());
+        assert!(report.scan.is_none());
+        let gateways: Vec<_> = report
+            .gateways
+            .iter()
+            .map(|gateway| {
+                (
+                    gateway.ip.to_string(),
+                    gateway.gateway,
+                    gateway.mac.to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            gateways,
+            vec![
+                (
+                    "192.168.1.20".to_string(),
+                    GatewayType::MG3,
+                    "AC:23:3F:00:00:01".to_string()
+                ),
+                (
+                    "192.168.1.21".to_string(),
+                    GatewayType::G1,
+                    "AC:23:3F:00:00:02".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn newer_schema_is_rejected() {
+        let err =
+            ScanReport::from_value(json!({ "schema_version": 3, "gateways": [] })).unwrap_err();
+        assert!(
+            matches!(err, ReportError::UnsupportedSchemaVersion { version: 3 }),
+            "{:?}",
+            err
+        );
+        assert!(
+            err.to_string()
+                .contains("newer than the supported version 2"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,693 @@
+//! Scanning addresses for gateways, for embedding the scan in other programs.
+
+use std::{
+    fmt::Display,
+    net::Ipv4Addr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{FutureExt, Stream, StreamExt};
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    endpoints::{Endpoints, Scheme},
+    error::{DetectError, ScanError, TransportError},
+    limits::{retry, RateLimiter},
+    parse,
+    target::Targets,
+    transport::{HttpRequest, ReqwestTransport, SocketOptions, Transport},
+    types::{GatewayDetection, GatewayType},
+};
+
+pub const PORT: u16 = 80;
+pub const CONCURRENCY: usize = 512;
+pub const TIMEOUT: Duration = Duration::from_secs(3);
+pub const RETRIES: u32 = 0;
+pub const G1_USERNAME: &str = "admin";
+
+/// What a [`Scanner`] probes and how.
+#[derive(Debug, Clone)]
+pub struct ScanConfig {
+    /// Addresses probed by [`Scanner::scan`]
+    pub targets: Targets,
+    /// Http port of the gateways
+    pub port: u16,
+    /// Ports tried besides `port`, in the order given, recording the one each gateway answered on
+    pub extra_ports: Vec<u16>,
+    /// Probes in flight at once
+    pub concurrency: usize,
+    /// Time allowed for the connection and for the detection requests of a probe
+    pub timeout: Duration,
+    /// Attempts repeated after a timeout or connection error
+    pub retries: u32,
+    /// Most probes started a second
+    pub rate_limit: Option<u32>,
+    pub g1_username: String,
+    pub g1_password: String,
+    /// Options for the sockets of tcp probes, used by [`Scanner::new`]
+    pub sockets: SocketOptions,
+    /// Gateway types probed for on every host
+    pub detectors: Vec<GatewayType>,
+}
+
+impl ScanConfig {
+    /// Probing `targets` with the defaults of the command line.
+    pub fn new(targets: Targets) -> Self {
+        Self {
+            targets,
+            ..Self::default()
+        }
+    }
+
+    pub fn builder() -> ScanConfigBuilder {
+        ScanConfigBuilder::default()
+    }
+
+    /// Checks that the options work together, as done by [`ScanConfigBuilder::build`] and the
+    /// constructors of [`Scanner`].
+    pub fn validate(&self) -> Result<(), InvalidScanConfig> {
+        let invalid = |message: String| Err(InvalidScanConfig(message));
+        let ports = std::iter::once(self.port).chain(self.extra_ports.iter().copied());
+        for (idx, port) in ports.clone().enumerate() {
+            if port == 0 {
+                return invalid("port 0 can't be scanned".into());
+            }
+            if ports.clone().take(idx).any(|earlier| earlier == port) {
+                return invalid(format!("port {} is given twice", port));
+            }
+        }
+        if self.concurrency == 0 {
+            return invalid("concurrency must be at least 1".into());
+        }
+        if self.timeout.is_zero() {
+            return invalid("timeout must be longer than 0s".into());
+        }
+        if self.rate_limit == Some(0) {
+            return invalid("rate limit must be at least 1 a second".into());
+        }
+        if self.detectors.is_empty() {
+            return invalid("at least one gateway type must be detected".into());
+        }
+        if self.sockets.tcp_user_timeout.is_some()
+            && !cfg!(any(target_os = "linux", target_os = "android"))
+        {
+            return invalid("tcp user timeout is only supported on linux".into());
+        }
+        Ok(())
+    }
+}
+
+impl Default for ScanConfig {
+    fn default() -> Self {
+        Self {
+            targets: Targets::default(),
+            port: PORT,
+            extra_ports: Vec::new(),
+            concurrency: CONCURRENCY,
+            timeout: TIMEOUT,
+            retries: RETRIES,
+            rate_limit: None,
+            g1_username: G1_USERNAME.to_string(),
+            g1_password: String::new(),
+            sockets: SocketOptions::default(),
+            detectors: vec![GatewayType::G1, GatewayType::MG3],
+        }
+    }
+}
+
+/// Error returned when building a [`ScanConfig`] from options that don't work together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidScanConfig(String);
+
+impl Display for InvalidScanConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidScanConfig {}
+
+/// A [`ScanConfig`] checked when it is built, starting from the defaults of the command line.
+#[derive(Debug, Clone, Default)]
+pub struct ScanConfigBuilder {
+    config: ScanConfig,
+}
+
+impl ScanConfigBuilder {
+    /// Adds addresses to scan, merged with the ones added before.
+    pub fn range(mut self, targets: Targets) -> Self {
+        self.config.targets = [std::mem::take(&mut self.config.targets), targets]
+            .into_iter()
+            .collect();
+        self
+    }
+
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+
+    /// Ports tried on every host besides the port, in this order.
+    pub fn extra_ports(mut self, ports: Vec<u16>) -> Self {
+        self.config.extra_ports = ports;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.config.concurrency = concurrency;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.config.retries = retries;
+        self
+    }
+
+    pub fn rate_limit(mut self, per_second: Option<u32>) -> Self {
+        self.config.rate_limit = per_second;
+        self
+    }
+
+    pub fn g1_credentials(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.config.g1_username = username.into();
+        self.config.g1_password = password.into();
+        self
+    }
+
+    pub fn sockets(mut self, sockets: SocketOptions) -> Self {
+        self.config.sockets = sockets;
+        self
+    }
+
+    pub fn detectors(mut self, detectors: &[GatewayType]) -> Self {
+        self.config.detectors = detectors.to_vec();
+        self
+    }
+
+    pub fn build(self) -> Result<ScanConfig, InvalidScanConfig> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// A tcp connection or detection request made while probing a host.
+#[derive(Debug)]
+pub struct ProbeAttempt<'a> {
+    pub ip: Ipv4Addr,
+    /// `tcp`, `g1` or `mg3`
+    pub stage: &'a str,
+    pub started: Instant,
+    pub elapsed: Duration,
+    /// Status and body of the response, 200 without a body for connections
+    pub response: Result<(u16, &'a str), &'a TransportError>,
+}
+
+/// What happened while scanning, for reporting live progress.
+#[derive(Debug)]
+pub enum ScanEvent<'a> {
+    /// Probing of a host began
+    HostStarted { ip: Ipv4Addr },
+    /// A target left out of the scan, like a hostname that was never resolved
+    HostSkipped { host: &'a str, reason: &'a str },
+    /// A host answered as a gateway
+    Detection(&'a GatewayDetection),
+    /// A host was probed without finding a gateway
+    ProbeError {
+        ip: Ipv4Addr,
+        error: &'a DetectError,
+    },
+    /// Hosts probed so far, out of the addresses of the targets
+    Progress { done: u64, total: u64 },
+}
+
+impl ScanEvent<'_> {
+    /// How much of the scan is done, from 0 to 100, for [`ScanEvent::Progress`].
+    pub fn percent(&self) -> Option<f64> {
+        match self {
+            Self::Progress { total: 0, .. } => Some(100.0),
+            Self::Progress { done, total } => Some(*done as f64 * 100.0 / *total as f64),
+            _ => None,
+        }
+    }
+}
+
+type Observer = Box<dyn Fn(&ProbeAttempt) + Send + Sync>;
+type Listener = Box<dyn Fn(&ScanEvent) + Send + Sync>;
+
+/// Detects G1 and MG3 gateways by their http apis.
+pub struct Scanner {
+    config: ScanConfig,
+    transport: Arc<dyn Transport>,
+    limiter: RateLimiter,
+    observer: Option<Observer>,
+    listener: Option<Listener>,
+    /// Hosts probed since the scan started
+    done: AtomicU64,
+    cancel: CancellationToken,
+}
+
+impl Scanner {
+    /// A scanner sending requests with a default http client.
+    pub fn new(config: ScanConfig) -> Result<Self, ScanError> {
+        config.validate()?;
+        let client = reqwest::Client::builder()
+            .build()
+            .map_err(ScanError::Client)?;
+        Self::with_client(config, client)
+    }
+
+    /// A scanner sending requests with `client`, sharing its connection pool, tls and proxy
+    /// settings. Probe connections are still opened with the socket options of the config.
+    pub fn with_client(config: ScanConfig, client: reqwest::Client) -> Result<Self, ScanError> {
+        let transport = ReqwestTransport::with_socket_options(client, config.sockets.clone());
+        Self::with_transport(config, Arc::new(transport))
+    }
+
+    /// A scanner sending requests through `transport`, e.g. a replayed cassette.
+    pub fn with_transport(
+        config: ScanConfig,
+        transport: Arc<dyn Transport>,
+    ) -> Result<Self, ScanError> {
+        config.validate()?;
+        Ok(Self {
+            limiter: RateLimiter::new(config.rate_limit),
+            config,
+            transport,
+            observer: None,
+            listener: None,
+            done: AtomicU64::new(0),
+            cancel: CancellationToken::new(),
+        })
+    }
+
+    /// Calls `observer` after every connection and request of a probe.
+    pub fn on_attempt(mut self, observer: impl Fn(&ProbeAttempt) + Send + Sync + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Calls `listener` with every event of the scan, from the tasks probing the hosts.
+    pub fn on_event(mut self, listener: impl Fn(&ScanEvent) + Send + Sync + 'static) -> Self {
+        self.listener = Some(Box::new(listener));
+        self
+    }
+
+    /// Stops scanning once `token` is cancelled. Probes in flight are dropped and the scan ends
+    /// with the gateways found until then.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    pub fn config(&self) -> &ScanConfig {
+        &self.config
+    }
+
+    pub fn transport(&self) -> &dyn Transport {
+        &*self.transport
+    }
+
+    /// Probes every target, returning the gateways found in the order they answered.
+    pub async fn scan(&self) -> Vec<GatewayDetection> {
+        self.scan_stream().collect().await
+    }
+
+    /// Probes every target, yielding each gateway as soon as it answers. Progress starts over
+    /// from 0 for every scan.
+    pub fn scan_stream(&self) -> impl Stream<Item = GatewayDetection> + '_ {
+        self.done.store(0, Ordering::Relaxed);
+        for host in self.config.targets.hostnames() {
+            self.emit(ScanEvent::HostSkipped {
+                host,
+                reason: "hostnames must be resolved before scanning",
+            });
+        }
+        self.probe(self.config.targets.clone(), self.config.concurrency)
+            .filter_map(|(_, result, _)| async move { result.ok() })
+    }
+
+    /// Probes `addresses` in order, `concurrency` at a time, yielding each result with how long
+    /// the probe took as it finishes. Progress counts every address probed by the scanner against
+    /// the targets of the config. The stream ends early when the scan is cancelled.
+    pub fn probe<'a>(
+        &'a self,
+        addresses: impl IntoIterator<Item = Ipv4Addr> + 'a,
+        concurrency: usize,
+    ) -> impl Stream<Item = (Ipv4Addr, Result<GatewayDetection, DetectError>, Duration)> + 'a {
+        futures::stream::iter(addresses)
+            .map(move |ip| async move {
+                self.emit(ScanEvent::HostStarted { ip });
+                let started = Instant::now();
+                let result = retry(self.config.retries, &self.limiter, || self.detect(ip)).await;
+                match &result {
+                    Ok(detection) => self.emit(ScanEvent::Detection(detection)),
+                    Err(error) => self.emit(ScanEvent::ProbeError { ip, error }),
+                }
+                self.emit(ScanEvent::Progress {
+                    done: self.done.fetch_add(1, Ordering::Relaxed) + 1,
+                    total: self.config.targets.len(),
+                });
+                (ip, result, started.elapsed())
+            })
+            .buffer_unordered(concurrency.max(1))
+            .take_until(self.cancel.cancelled())
+    }
+
+    /// Detects the gateway at `ip`, trying every port at once.
+    pub async fn detect(&self, ip: Ipv4Addr) -> Result<GatewayDetection, DetectError> {
+        if self.config.extra_ports.is_empty() {
+            return self.detect_port(ip, self.config.port).await;
+        }
+        let probes = std::iter::once(self.config.port)
+            .chain(self.config.extra_ports.iter().copied())
+            .map(|port| {
+                async move {
+                    let mut detection = self.detect_port(ip, port).await?;
+                    detection.port = Some(port);
+                    Ok::<_, DetectError>(detection)
+                }
+                .boxed()
+            });
+        let (detection, _) = futures::future::select_ok(probes).await?;
+        Ok(detection)
+    }
+
+    /// The authenticated G1 status request that identifies a G1 gateway.
+    pub fn g1_status_request(&self, endpoints: &Endpoints) -> HttpRequest {
+        HttpRequest::post(
+            endpoints.g1_status(),
+            json! {{
+                "header": {
+                    "version": 1,
+                },
+            }},
+        )
+        .basic_auth(&self.config.g1_username, &self.config.g1_password)
+    }
+
+    /// Detects a gateway on a single port, over https for port 443.
+    async fn detect_port(&self, ip: Ipv4Addr, port: u16) -> Result<GatewayDetection, DetectError> {
+        let scheme = if port == 443 {
+            Scheme::Https
+        } else {
+            Scheme::Http
+        };
+        let endpoints = Endpoints::for_ip(ip).with_scheme(scheme).with_port(port);
+        let started = Instant::now();
+        let connected = self
+            .transport
+            .connect(endpoints.host(), endpoints.port(), self.config.timeout)
+            .await;
+        match &connected {
+            Ok(()) => self.observe(ip, "tcp", started, Ok((200, ""))),
+            Err(err) => self.observe(ip, "tcp", started, Err(err)),
+        }
+        connected?;
+
+        let detectors = self.config.detectors.iter().map(|kind| match kind {
+            GatewayType::G1 => self.detect_g1(ip, &endpoints).boxed(),
+            GatewayType::MG3 => self.detect_mg3(ip, &endpoints).boxed(),
+        });
+        let detected =
+            tokio::time::timeout(self.config.timeout, futures::future::select_ok(detectors)).await;
+        match detected {
+            Ok(Ok((detection, _))) => Ok(detection),
+            Ok(Err(err)) => Err(err),
+            Err(_) => Err(DetectError::Timeout),
+        }
+    }
+
+    /// Sends a detection request and returns the body of a successful response.
+    async fn fetch(
+        &self,
+        ip: Ipv4Addr,
+        stage: &str,
+        request: HttpRequest,
+    ) -> Result<String, DetectError> {
+        let started = Instant::now();
+        let response = self.transport.send(request).await;
+        match &response {
+            Ok(response) => self.observe(
+                ip,
+                stage,
+                started,
+                Ok((response.status.as_u16(), &response.body)),
+            ),
+            Err(err) => self.observe(ip, stage, started, Err(err)),
+        }
+        let response = response?;
+        if !response.status.is_success() {
+            return Err(DetectError::status(response.status));
+        }
+        Ok(response.body)
+    }
+
+    async fn detect_g1(
+        &self,
+        ip: Ipv4Addr,
+        endpoints: &Endpoints,
+    ) -> Result<GatewayDetection, DetectError> {
+        let started = Instant::now();
+        let body = self
+            .fetch(ip, "g1", self.g1_status_request(endpoints))
+            .await?;
+        let mac = parse::g1_status(&body).map_err(|err| DetectError::response(err, &body))?;
+        let mut detection = GatewayDetection::new(ip, GatewayType::G1, mac);
+        detection.rtt_ms = Some(milliseconds(started.elapsed()));
+        let info = parse::g1_firmware(&body);
+        detection.firmware = info.firmware;
+        detection.model = info.model;
+        Ok(detection)
+    }
+
+    async fn detect_mg3(
+        &self,
+        ip: Ipv4Addr,
+        endpoints: &Endpoints,
+    ) -> Result<GatewayDetection, DetectError> {
+        let request = HttpRequest::get(endpoints.mg3_hello());
+        let started = Instant::now();
+        let body = self.fetch(ip, "mg3", request).await?;
+        let mac = parse::mg3_hello(&body).map_err(|err| DetectError::response(err, &body))?;
+        let mut detection = GatewayDetection::new(ip, GatewayType::MG3, mac);
+        detection.rtt_ms = Some(milliseconds(started.elapsed()));
+        let info = parse::mg3_firmware(&body);
+        detection.firmware = info.firmware;
+        detection.model = info.model;
+        Ok(detection)
+    }
+
+    fn emit(&self, event: ScanEvent) {
+        if let Some(listener) = &self.listener {
+            listener(&event);
+        }
+    }
+
+    fn observe(
+        &self,
+        ip: Ipv4Addr,
+        stage: &str,
+        started: Instant,
+        response: Result<(u16, &str), &TransportError>,
+    ) {
+        if let Some(observer) = &self.observer {
+            observer(&ProbeAttempt {
+                ip,
+                stage,
+                started,
+                elapsed: started.elapsed(),
+                response,
+            });
+        }
+    }
+}
+
+/// `elapsed` in milliseconds, to the microsecond.
+fn milliseconds(elapsed: Duration) -> f64 {
+    elapsed.as_micros() as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::BoxFuture;
+
+    use super::*;
+    use crate::{
+        cassette::{Cassette, Interaction, Outcome},
+        error::{ErrorCode, TransportError},
+        transport::HttpResponse,
+    };
+
+    const G1_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 5);
+    const MG3_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 6);
+
+    fn g1_status(status: u16, body: &str) -> Interaction {
+        Interaction {
+            method: "POST".to_string(),
+            url: format!("http://{}/cgi-bin/cgic-statusget", G1_IP),
+            request: Some(json!({"header": {"version": 1}})),
+            outcome: Outcome::Response {
+                status,
+                body: body.to_string(),
+            },
+        }
+    }
+
+    fn mg3_hello(body: &str) -> Interaction {
+        Interaction {
+            method: "GET".to_string(),
+            url: format!("http://{}/hello", MG3_IP),
+            request: None,
+            outcome: Outcome::Response {
+                status: 200,
+                body: body.to_string(),
+            },
+        }
+    }
+
+    fn scanner(targets: &str, interactions: Vec<Interaction>) -> Scanner {
+        let config = ScanConfig::new(targets.parse().unwrap());
+        Scanner::with_transport(config, Arc::new(Cassette::from_interactions(interactions)))
+            .unwrap()
+    }
+
+    /// Accepts connections and never answers requests.
+    struct Unresponsive;
+
+    impl Transport for Unresponsive {
+        fn connect<'a>(
+            &'a self,
+            _host: &'a str,
+            _port: u16,
+            _timeout: Duration,
+        ) -> BoxFuture<'a, Result<(), TransportError>> {
+            futures::future::ready(Ok(())).boxed()
+        }
+
+        fn send(
+            &self,
+            _request: HttpRequest,
+        ) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+            futures::future::pending().boxed()
+        }
+    }
+
+    #[tokio::test]
+    async fn detects_g1() {
+        let scanner = scanner(
+            "10.0.0.1..10.0.0.10",
+            vec![g1_status(
+                200,
+                r#"{"header": {"code": 200}, "body": {"gateway": {"status": {"mac": "AC:23:3F:00:00:01", "firmware": "3.1.0"}}}}"#,
+            )],
+        );
+        let gateways = scanner.scan().await;
+        assert_eq!(gateways.len(), 1);
+        assert_eq!(gateways[0].ip, G1_IP);
+        assert_eq!(gateways[0].gateway, GatewayType::G1);
+        assert_eq!(gateways[0].mac.to_string(), "AC:23:3F:00:00:01");
+        assert_eq!(gateways[0].firmware.as_deref(), Some("3.1.0"));
+    }
+
+    #[tokio::test]
+    async fn detects_mg3() {
+        let scanner = scanner(
+            "10.0.0.1..10.0.0.10",
+            vec![mg3_hello(r#"{"mac": "ac233f000002", "model": "MG3"}"#)],
+        );
+        let gateways = scanner.scan().await;
+        assert_eq!(gateways.len(), 1);
+        assert_eq!(gateways[0].ip, MG3_IP);
+        assert_eq!(gateways[0].gateway, GatewayType::MG3);
+        assert_eq!(gateways[0].mac.to_string(), "AC:23:3F:00:00:02");
+        assert_eq!(gateways[0].model.as_deref(), Some("MG3"));
+    }
+
+    #[tokio::test]
+    async fn unanswered_requests_time_out() {
+        let config = ScanConfig {
+            timeout: Duration::from_millis(50),
+            ..ScanConfig::new(G1_IP.to_string().parse().unwrap())
+        };
+        let scanner = Scanner::with_transport(config, Arc::new(Unresponsive)).unwrap();
+        let err = scanner.detect(G1_IP).await.unwrap_err();
+        assert!(matches!(err, DetectError::Timeout));
+        assert_eq!(err.code(), ErrorCode::Timeout);
+    }
+
+    #[tokio::test]
+    async fn rejected_credentials_are_auth_failures() {
+        let config = ScanConfig {
+            detectors: vec![GatewayType::G1],
+            ..ScanConfig::new(G1_IP.to_string().parse().unwrap())
+        };
+        let transport = Cassette::from_interactions([g1_status(401, "Unauthorized")]);
+        let scanner = Scanner::with_transport(config, Arc::new(transport)).unwrap();
+        let err = scanner.detect(G1_IP).await.unwrap_err();
+        assert!(matches!(err, DetectError::AuthFailed { status: 401 }));
+        assert_eq!(err.code(), ErrorCode::Auth);
+    }
+
+    #[tokio::test]
+    async fn unsuccessful_statuses_are_http_errors() {
+        let config = ScanConfig {
+            detectors: vec![GatewayType::G1],
+            ..ScanConfig::new(G1_IP.to_string().parse().unwrap())
+        };
+        let transport = Cassette::from_interactions([g1_status(500, "")]);
+        let scanner = Scanner::with_transport(config, Arc::new(transport)).unwrap();
+        let err = scanner.detect(G1_IP).await.unwrap_err();
+        assert!(matches!(err, DetectError::HttpStatus { status: 500 }));
+        assert_eq!(err.code(), ErrorCode::HttpStatus);
+    }
+
+    #[test]
+    fn constructors_reject_invalid_configs() {
+        let config = ScanConfig {
+            concurrency: 0,
+            ..ScanConfig::default()
+        };
+        let transport = Arc::new(Cassette::from_interactions([]));
+        assert!(matches!(
+            Scanner::with_transport(config.clone(), transport),
+            Err(ScanError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            Scanner::new(config),
+            Err(ScanError::InvalidConfig(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn progress_starts_over_for_every_scan() {
+        let config = ScanConfig::new("10.0.0.1,10.0.0.2".parse().unwrap());
+        let progress = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let events = progress.clone();
+        let scanner = Scanner::with_transport(config, Arc::new(Cassette::from_interactions([])))
+            .unwrap()
+            .on_event(move |event| {
+                if let ScanEvent::Progress { done, total } = event {
+                    events.lock().unwrap().push((*done, *total));
+                }
+            });
+        scanner.scan().await;
+        scanner.scan().await;
+        let mut progress = progress.lock().unwrap().clone();
+        progress.sort();
+        assert_eq!(progress, [(1, 2), (1, 2), (2, 2), (2, 2)]);
+    }
+}
@@ -0,0 +1,83 @@
+//! JSON Schema of the scan output, for consumers to validate reports and notice breaking changes.
+
+use serde_json::{json, Value};
+
+use crate::report::SCHEMA_VERSION;
+
+/// The JSON Schema (draft 2020-12) of a [`ScanReport`](crate::report::ScanReport) of the current
+/// [`SCHEMA_VERSION`].
+///
+/// Kept next to the types it describes, fields added to them must be added here.
+pub fn scan_report() -> Value {
+    let optional_string = json!({"type": "string"});
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("https://github.com/msdrigg/rtls-ctl/schema/scan-report-v{}.json", SCHEMA_VERSION),
+        "title": "rtls-ctl scan report",
+        "type": "object",
+        "required": ["schema_version", "gateways"],
+        "properties": {
+            "schema_version": {"const": SCHEMA_VERSION},
+            "gateways": {"type": "array", "items": {"$ref": "#/$defs/gateway"}},
+            "errors": {"type": "array", "items": {"$ref": "#/$defs/failure"}},
+            "scan": {"$ref": "#/$defs/scan"},
+        },
+        "$defs": {
+            "ip": {"type": "string", "format": "ipv4"},
+            "mac": {
+                "type": "string",
+                "pattern": "^[0-9A-F]{2}(:[0-9A-F]{2}){5}$",
+            },
+            "gateway": {
+                "type": "object",
+                "required": ["ip", "gateway", "mac"],
+                "properties": {
+                    "ip": {"$ref": "#/$defs/ip"},
+                    "gateway": {"enum": ["G1", "MG3"]},
+                    "mac": {"$ref": "#/$defs/mac"},
+                    "interface": optional_string,
+                    "vlan": {"type": "integer", "minimum": 1, "maximum": 4094},
+                    "hostname": optional_string,
+                    "port": {"type": "integer", "minimum": 1, "maximum": 65535},
+                    "rtt_ms": {"type": "number", "minimum": 0},
+                    "firmware": optional_string,
+                    "model": optional_string,
+                    "vendor": optional_string,
+                },
+            },
+            "failure": {
+                "type": "object",
+                "required": ["ip", "code", "message"],
+                "properties": {
+                    "ip": {"$ref": "#/$defs/ip"},
+                    "code": {
+                        "enum": [
+                            "E_TIMEOUT",
+                            "E_CONNECT",
+                            "E_AUTH",
+                            "E_HTTP_STATUS",
+                            "E_UNEXPECTED_RESPONSE",
+                            "E_PARSE_MAC",
+                            "E_UNSUPPORTED",
+                            "E_RESOLVE",
+                            "E_OTHER",
+                        ],
+                    },
+                    "message": {"type": "string"},
+                    "hostname": optional_string,
+                },
+            },
+            "scan": {
+                "type": "object",
+                "required": ["tool_version", "targets", "port"],
+                "properties": {
+                    "tool_version": {"type": "string"},
+                    "targets": {"type": "string"},
+                    "port": {"type": "integer", "minimum": 1, "maximum": 65535},
+                    "started_at": {"type": "string", "format": "date-time"},
+                    "duration_ms": {"type": "integer", "minimum": 0},
+                },
+            },
+        },
+    })
+}
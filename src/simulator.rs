@@ -0,0 +1,231 @@
+//! Fake G1 and MG3 gateways serving the same http endpoints as the real devices, for exercising
+//! the scanner and management commands without hardware.
+
+use std::{
+    convert::Infallible,
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use base64::Engine;
+use futures::{future::BoxFuture, FutureExt};
+use hyper::{
+    header::AUTHORIZATION,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    target::Subnet,
+    types::{GatewayDetection, GatewayType, Mac},
+    validation::{Validate, Validator},
+};
+
+/// Vendor prefix of the MG3 and G1 gateways.
+pub const MINEW_OUI: [u8; 3] = [0xAC, 0x23, 0x3F];
+
+/// One simulated device.
+#[derive(Debug, Clone)]
+pub struct SimulatedGateway {
+    pub ip: Ipv4Addr,
+    pub port: u16,
+    pub gateway: GatewayType,
+    pub mac: Mac,
+    pub faults: Faults,
+}
+
+/// Misbehavior injected into a simulated gateway's responses.
+///
+/// Rates are probabilities from 0 to 1 rolled for every request, checked in the order auth
+/// failure, server error, wrong schema, truncated body.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Faults {
+    /// Delay before every response
+    #[serde(
+        with = "crate::duration::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub latency: Option<Duration>,
+    /// Answer with 401 as if the credentials were wrong
+    pub auth_failure_rate: f64,
+    /// Answer with 500
+    pub error_rate: f64,
+    /// Answer 200 with json that has none of the expected fields
+    pub wrong_schema_rate: f64,
+    /// Cut the body off halfway
+    pub truncate_rate: f64,
+}
+
+impl Validate for Faults {
+    fn validate_fields(&self, v: &mut Validator) {
+        v.range("auth_failure_rate", Some(self.auth_failure_rate), 0.0..=1.0);
+        v.range("error_rate", Some(self.error_rate), 0.0..=1.0);
+        v.range("wrong_schema_rate", Some(self.wrong_schema_rate), 0.0..=1.0);
+        v.range("truncate_rate", Some(self.truncate_rate), 0.0..=1.0);
+    }
+}
+
+fn roll(rate: f64) -> bool {
+    rate > 0.0 && rand::random::<f64>() < rate
+}
+
+impl SimulatedGateway {
+    /// What a scan of this gateway should report.
+    pub fn detection(&self) -> GatewayDetection {
+        GatewayDetection::new(self.ip, self.gateway, self.mac)
+    }
+}
+
+/// Basic auth credentials the simulated G1 gateways accept.
+#[derive(Debug, Clone)]
+pub struct SimulatedCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// Assigns `g1` G1 gateways followed by `mg3` MG3 gateways to consecutive hosts of `bind`.
+pub fn layout(
+    g1: usize,
+    mg3: usize,
+    bind: &Subnet,
+    port: u16,
+) -> anyhow::Result<Vec<SimulatedGateway>> {
+    let kinds =
+        std::iter::repeat_n(GatewayType::G1, g1).chain(std::iter::repeat_n(GatewayType::MG3, mg3));
+    let mut hosts = bind.hosts().into_iter();
+    kinds
+        .map(|gateway| {
+            let ip = hosts.next().with_context(|| {
+                format!("{} has room for fewer than {} gateways", bind, g1 + mg3)
+            })?;
+            Ok(SimulatedGateway {
+                ip,
+                port,
+                gateway,
+                mac: Mac::from_oui(MINEW_OUI),
+                faults: Faults::default(),
+            })
+        })
+        .collect()
+}
+
+/// Serves every gateway on its own address until one of the servers fails.
+pub async fn serve(
+    gateways: Vec<SimulatedGateway>,
+    credentials: SimulatedCredentials,
+) -> anyhow::Result<()> {
+    bind(gateways, credentials)?.await
+}
+
+/// Binds every gateway's address right away and returns the future that serves them, so
+/// callers know the gateways are reachable before they start probing.
+pub fn bind(
+    gateways: Vec<SimulatedGateway>,
+    credentials: SimulatedCredentials,
+) -> anyhow::Result<BoxFuture<'static, anyhow::Result<()>>> {
+    let credentials = Arc::new(credentials);
+    let servers = gateways
+        .into_iter()
+        .map(|gateway| {
+            let addr = SocketAddr::from((gateway.ip, gateway.port));
+            let gateway = Arc::new(gateway);
+            let credentials = credentials.clone();
+            let make_service = make_service_fn(move |_| {
+                let gateway = gateway.clone();
+                let credentials = credentials.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| {
+                        respond(gateway.clone(), credentials.clone(), request)
+                    }))
+                }
+            });
+            let server = Server::try_bind(&addr)
+                .with_context(|| format!("Error binding simulated gateway to {}", addr))?
+                .serve(make_service);
+            Ok(async move {
+                server
+                    .await
+                    .with_context(|| format!("Simulated gateway {} failed", addr))
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(async move {
+        futures::future::try_join_all(servers).await?;
+        Ok(())
+    }
+    .boxed())
+}
+
+async fn respond(
+    gateway: Arc<SimulatedGateway>,
+    credentials: Arc<SimulatedCredentials>,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let faults = &gateway.faults;
+    if let Some(latency) = faults.latency {
+        tokio::time::sleep(latency).await;
+    }
+    if roll(faults.auth_failure_rate) {
+        return Ok(json_response(StatusCode::UNAUTHORIZED, json!({})));
+    }
+    if roll(faults.error_rate) {
+        return Ok(json_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({"error": "simulated failure"}),
+        ));
+    }
+
+    let (status, body) = match (gateway.gateway, request.method(), request.uri().path()) {
+        (GatewayType::G1, &Method::POST, "/cgi-bin/cgic-statusget") => {
+            if is_authorized(&request, &credentials) {
+                (
+                    StatusCode::OK,
+                    json!({
+                        "header": {"version": 1, "code": 200},
+                        "body": {"gateway": {"status": {"mac": gateway.mac.to_string().to_lowercase()}}},
+                    }),
+                )
+            } else {
+                (StatusCode::UNAUTHORIZED, json!({}))
+            }
+        }
+        (GatewayType::MG3, &Method::GET, "/hello") => {
+            (StatusCode::OK, json!({"mac": gateway.mac.to_string()}))
+        }
+        (GatewayType::MG3, &Method::POST, "/set") => (StatusCode::OK, json!({"code": 200})),
+        _ => (StatusCode::NOT_FOUND, json!({})),
+    };
+    if status == StatusCode::OK && roll(faults.wrong_schema_rate) {
+        return Ok(json_response(status, json!({"result": ["unexpected"]})));
+    }
+    let mut body = body.to_string();
+    if roll(faults.truncate_rate) {
+        body.truncate(body.len() / 2);
+    }
+    Ok(json_response(status, body))
+}
+
+fn is_authorized(request: &Request<Body>, credentials: &SimulatedCredentials) -> bool {
+    let expected = format!(
+        "Basic {}",
+        base64::engine::general_purpose::STANDARD
+            .encode(format!("{}:{}", credentials.username, credentials.password))
+    );
+    request
+        .headers()
+        .get(AUTHORIZATION)
+        .is_some_and(|header| header.as_bytes() == expected.as_bytes())
+}
+
+fn json_response(status: StatusCode, body: impl ToString) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("Static response parts must be valid")
+}
@@ -0,0 +1,240 @@
+//! A minimal SNMPv2c client, just enough to walk the ARP table of a switch or router.
+
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::Context;
+
+use crate::types::Mac;
+
+pub const PORT: u16 = 161;
+/// `ipNetToMediaPhysAddress`, the mac column of the ARP table, indexed by interface and ip
+const IP_NET_TO_MEDIA_PHYS_ADDRESS: &[u32] = &[1, 3, 6, 1, 2, 1, 4, 22, 1, 2];
+/// Rows requested per GetBulk request
+const MAX_REPETITIONS: u32 = 25;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_GET_BULK: u8 = 0xA5;
+const TAG_RESPONSE: u8 = 0xA2;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+
+/// The ip and mac of every entry of the agent's ARP table.
+pub async fn arp_table(
+    agent: SocketAddr,
+    community: &str,
+    timeout: Duration,
+) -> anyhow::Result<Vec<(Ipv4Addr, Mac)>> {
+    let rows = walk(agent, community, IP_NET_TO_MEDIA_PHYS_ADDRESS, timeout).await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(oid, value)| {
+            // The index is the interface followed by the four octets of the ip
+            let [.., a, b, c, d] = oid[IP_NET_TO_MEDIA_PHYS_ADDRESS.len()..] else {
+                return None;
+            };
+            let ip = Ipv4Addr::new(
+                u8::try_from(a).ok()?,
+                u8::try_from(b).ok()?,
+                u8::try_from(c).ok()?,
+                u8::try_from(d).ok()?,
+            );
+            let mac: [u8; 6] = value.try_into().ok()?;
+            Some((ip, Mac::from(mac)))
+        })
+        .collect())
+}
+
+/// Every object below `base` with its value, from GetBulk requests of `MAX_REPETITIONS` rows.
+///
+/// Values are the raw contents of their encoding, e.g. the bytes of an octet string.
+pub async fn walk(
+    agent: SocketAddr,
+    community: &str,
+    base: &[u32],
+    timeout: Duration,
+) -> anyhow::Result<Vec<(Vec<u32>, Vec<u8>)>> {
+    let socket = tokio::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .context("Error binding udp socket")?;
+    socket
+        .connect(agent)
+        .await
+        .with_context(|| format!("Error connecting to snmp agent {}", agent))?;
+    let mut rows = Vec::new();
+    let mut next = base.to_vec();
+    let mut buf = vec![0; 65535];
+    for request_id in 1.. {
+        socket
+            .send(&get_bulk(community, request_id, &next))
+            .await
+            .context("Error sending snmp request")?;
+        let len = tokio::time::timeout(timeout, socket.recv(&mut buf))
+            .await
+            .with_context(|| format!("Timeout waiting for snmp agent {}", agent))?
+            .context("Error receiving snmp response")?;
+        let varbinds = response(&buf[..len], request_id)
+            .with_context(|| format!("Invalid snmp response from {}", agent))?;
+        if varbinds.is_empty() {
+            break;
+        }
+        for Varbind { oid, tag, value } in varbinds {
+            if tag == TAG_END_OF_MIB_VIEW || !oid.starts_with(base) || oid <= next {
+                return Ok(rows);
+            }
+            next = oid.clone();
+            rows.push((oid, value.to_vec()));
+        }
+    }
+    Ok(rows)
+}
+
+/// A v2c GetBulk request for the objects after `oid`.
+fn get_bulk(community: &str, request_id: u32, oid: &[u32]) -> Vec<u8> {
+    let varbind = tlv(
+        TAG_SEQUENCE,
+        &[tlv(TAG_OID, &encode_oid(oid)), tlv(TAG_NULL, &[])].concat(),
+    );
+    let pdu = [
+        integer(request_id),
+        // Non-repeaters
+        integer(0),
+        integer(MAX_REPETITIONS),
+        tlv(TAG_SEQUENCE, &varbind),
+    ]
+    .concat();
+    let message = [
+        // Version 2c
+        integer(1),
+        tlv(TAG_OCTET_STRING, community.as_bytes()),
+        tlv(TAG_GET_BULK, &pdu),
+    ]
+    .concat();
+    tlv(TAG_SEQUENCE, &message)
+}
+
+/// An object of a response with the tag and contents of its value.
+struct Varbind<'a> {
+    oid: Vec<u32>,
+    tag: u8,
+    value: &'a [u8],
+}
+
+/// Every varbind of a response, `None` when it is malformed, answers another request or reports
+/// an error.
+fn response(packet: &[u8], request_id: u32) -> Option<Vec<Varbind<'_>>> {
+    let (TAG_SEQUENCE, message, _) = read_tlv(packet)? else {
+        return None;
+    };
+    let (_version, rest) = expect(message, TAG_INTEGER)?;
+    let (_community, rest) = expect(rest, TAG_OCTET_STRING)?;
+    let (pdu, _) = expect(rest, TAG_RESPONSE)?;
+    let (id, rest) = expect(pdu, TAG_INTEGER)?;
+    let (status, rest) = expect(rest, TAG_INTEGER)?;
+    let (_index, rest) = expect(rest, TAG_INTEGER)?;
+    if decode_integer(id) != u64::from(request_id) || decode_integer(status) != 0 {
+        return None;
+    }
+    let (mut list, _) = expect(rest, TAG_SEQUENCE)?;
+    let mut varbinds = Vec::new();
+    while !list.is_empty() {
+        let (varbind, rest) = expect(list, TAG_SEQUENCE)?;
+        list = rest;
+        let (oid, rest) = expect(varbind, TAG_OID)?;
+        let (tag, value, _) = read_tlv(rest)?;
+        varbinds.push(Varbind {
+            oid: decode_oid(oid)?,
+            tag,
+            value,
+        });
+    }
+    Some(varbinds)
+}
+
+fn tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    match contents.len() {
+        len @ 0..=0x7F => encoded.push(len as u8),
+        len @ 0x80..=0xFF => encoded.extend([0x81, len as u8]),
+        len => {
+            encoded.push(0x82);
+            encoded.extend((len as u16).to_be_bytes());
+        }
+    }
+    encoded.extend_from_slice(contents);
+    encoded
+}
+
+fn integer(value: u32) -> Vec<u8> {
+    let bytes = u64::from(value).to_be_bytes();
+    // Keep a leading zero when the highest bit is set, so the value stays positive
+    let start = (0..7)
+        .find(|&idx| bytes[idx] != 0 || bytes[idx + 1] & 0x80 != 0)
+        .unwrap_or(7);
+    tlv(TAG_INTEGER, &bytes[start..])
+}
+
+fn encode_oid(oid: &[u32]) -> Vec<u8> {
+    let mut encoded = vec![(oid[0] * 40 + oid[1]) as u8];
+    for &component in &oid[2..] {
+        let mut chunk = vec![(component & 0x7F) as u8];
+        let mut rest = component >> 7;
+        while rest > 0 {
+            chunk.push((rest & 0x7F) as u8 | 0x80);
+            rest >>= 7;
+        }
+        encoded.extend(chunk.into_iter().rev());
+    }
+    encoded
+}
+
+fn decode_oid(encoded: &[u8]) -> Option<Vec<u32>> {
+    let (first, rest) = encoded.split_first()?;
+    let mut oid = vec![u32::from(first / 40), u32::from(first % 40)];
+    let mut component: u32 = 0;
+    for byte in rest {
+        component = component.checked_mul(128)? | u32::from(byte & 0x7F);
+        if byte & 0x80 == 0 {
+            oid.push(component);
+            component = 0;
+        }
+    }
+    Some(oid)
+}
+
+fn decode_integer(contents: &[u8]) -> u64 {
+    contents
+        .iter()
+        .fold(0, |value, byte| value << 8 | u64::from(*byte))
+}
+
+/// The contents of the element at the start of `data` if it has the tag, and the data after it.
+fn expect(data: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    match read_tlv(data)? {
+        (found, contents, rest) if found == tag => Some((contents, rest)),
+        _ => None,
+    }
+}
+
+/// The tag and contents of the element at the start of `data`, and the data after it.
+fn read_tlv(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)?;
+    let (len, header) = if first & 0x80 == 0 {
+        (usize::from(first), 2)
+    } else {
+        let octets = usize::from(first & 0x7F);
+        let len = data
+            .get(2..2 + octets)?
+            .iter()
+            .fold(0usize, |len, byte| len << 8 | usize::from(*byte));
+        (len, 2 + octets)
+    };
+    let contents = data.get(header..header + len)?;
+    Some((tag, contents, &data[header + len..]))
+}
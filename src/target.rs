@@ -0,0 +1,402 @@
+use std::{fmt::Display, net::Ipv4Addr, ops::Range, str::FromStr};
+
+/// Error returned when parsing an ip range or subnet fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTargetError(String);
+
+impl Display for ParseTargetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ParseTargetError {}
+
+/// A range of ipv4 addresses. Like rust ranges the end address is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpRange {
+    pub start: Ipv4Addr,
+    pub end: Ipv4Addr,
+}
+
+impl IpRange {
+    pub fn new(start: Ipv4Addr, end: Ipv4Addr) -> Self {
+        Self { start, end }
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        self.start <= ip && ip < self.end
+    }
+
+    pub fn len(&self) -> u64 {
+        u64::from(u32::from(self.end).saturating_sub(u32::from(self.start)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl From<Ipv4Addr> for IpRange {
+    /// The range of the single address `ip`.
+    fn from(ip: Ipv4Addr) -> Self {
+        Self::new(ip, Ipv4Addr::from(u32::from(ip).saturating_add(1)))
+    }
+}
+
+impl Display for IpRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = ParseTargetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once("..")
+            .ok_or_else(|| ParseTargetError("Range argument must contain '..'".into()))?;
+        Ok(Self {
+            start: start.trim().parse().map_err(|_| {
+                ParseTargetError(
+                    "Error parsing start ip address. Expected ip v4 address like '192.168.1.1'"
+                        .into(),
+                )
+            })?,
+            end: end.trim().parse().map_err(|_| {
+                ParseTargetError(
+                    "Error parsing end ip address. Expected ip v4 address like '192.168.1.2'"
+                        .into(),
+                )
+            })?,
+        })
+    }
+}
+
+pub struct IpRangeIter {
+    range: Range<u32>,
+}
+
+impl Iterator for IpRangeIter {
+    type Item = Ipv4Addr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next().map(Ipv4Addr::from)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl IntoIterator for IpRange {
+    type Item = Ipv4Addr;
+
+    type IntoIter = IpRangeIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IpRangeIter {
+            range: Range {
+                start: u32::from(self.start),
+                end: u32::from(self.end),
+            },
+        }
+    }
+}
+
+/// An ipv4 subnet in CIDR form, e.g. `192.168.1.0/24`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Subnet {
+    network: Ipv4Addr,
+    prefix: u8,
+}
+
+impl Subnet {
+    /// Creates the subnet of `ip` with the given prefix length, clearing host bits.
+    pub fn new(ip: Ipv4Addr, prefix: u8) -> Result<Self, ParseTargetError> {
+        if prefix > 32 {
+            return Err(ParseTargetError(format!(
+                "Prefix length {} is larger than 32",
+                prefix
+            )));
+        }
+        Ok(Self {
+            network: Ipv4Addr::from(u32::from(ip) & mask(prefix)),
+            prefix,
+        })
+    }
+
+    pub fn network(&self) -> Ipv4Addr {
+        self.network
+    }
+
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    pub fn netmask(&self) -> Ipv4Addr {
+        Ipv4Addr::from(mask(self.prefix))
+    }
+
+    pub fn broadcast(&self) -> Ipv4Addr {
+        Ipv4Addr::from(u32::from(self.network) | !mask(self.prefix))
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        u32::from(ip) & mask(self.prefix) == u32::from(self.network)
+    }
+
+    /// Number of addresses in the subnet, including network and broadcast addresses.
+    pub fn size(&self) -> u64 {
+        1 << (32 - u32::from(self.prefix))
+    }
+
+    /// All addresses of the subnet, including network and broadcast addresses.
+    ///
+    /// The last address of `0.0.0.0/0` is not included because ranges are end exclusive.
+    pub fn range(&self) -> IpRange {
+        IpRange::new(
+            self.network,
+            Ipv4Addr::from(u32::from(self.broadcast()).saturating_add(1)),
+        )
+    }
+
+    /// Addresses usable by hosts, excluding the network and broadcast addresses.
+    ///
+    /// `/31` and `/32` subnets have no such addresses so all of their addresses are returned.
+    pub fn hosts(&self) -> IpRange {
+        if self.prefix >= 31 {
+            return self.range();
+        }
+        IpRange::new(
+            Ipv4Addr::from(u32::from(self.network) + 1),
+            self.broadcast(),
+        )
+    }
+
+    /// Splits the subnet into subnets with the longer prefix length `prefix`.
+    pub fn split(&self, prefix: u8) -> Result<Vec<Subnet>, ParseTargetError> {
+        if prefix < self.prefix || prefix > 32 {
+            return Err(ParseTargetError(format!(
+                "Cannot split /{} into /{} subnets",
+                self.prefix, prefix
+            )));
+        }
+        let step = 1u64 << (32 - u32::from(prefix));
+        let start = u64::from(u32::from(self.network));
+        Ok((0..self.size() / step)
+            .map(|idx| Subnet {
+                network: Ipv4Addr::from((start + idx * step) as u32),
+                prefix,
+            })
+            .collect())
+    }
+}
+
+fn mask(prefix: u8) -> u32 {
+    u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0)
+}
+
+impl Display for Subnet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix)
+    }
+}
+
+impl FromStr for Subnet {
+    type Err = ParseTargetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, prefix) = s
+            .split_once('/')
+            .ok_or_else(|| ParseTargetError("Subnet must contain '/'".into()))?;
+        let ip = ip.trim().parse().map_err(|_| {
+            ParseTargetError(
+                "Error parsing subnet address. Expected ip v4 address like '192.168.1.0'".into(),
+            )
+        })?;
+        let prefix = prefix.trim().parse().map_err(|_| {
+            ParseTargetError("Error parsing prefix length. Expected a number like '24'".into())
+        })?;
+        Self::new(ip, prefix)
+    }
+}
+
+/// The addresses of a scan: a comma separated list of `start..end` ranges, CIDR subnets like
+/// `192.168.1.0/24` (without their network and broadcast addresses), single addresses and
+/// hostnames.
+///
+/// Hostnames are kept as given, resolving them is up to the scan. Iterating only yields the
+/// addresses.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Targets {
+    ranges: Vec<IpRange>,
+    hostnames: Vec<String>,
+}
+
+impl Targets {
+    /// Sorts the ranges and merges the ones that overlap or touch, so every address is probed
+    /// once and in ascending order.
+    pub fn new(mut ranges: Vec<IpRange>) -> Self {
+        ranges.retain(|range| !range.is_empty());
+        ranges.sort_by_key(|range| range.start);
+        let mut merged: Vec<IpRange> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        Self {
+            ranges: merged,
+            hostnames: Vec::new(),
+        }
+    }
+
+    pub fn ranges(&self) -> &[IpRange] {
+        &self.ranges
+    }
+
+    pub fn hostnames(&self) -> &[String] {
+        &self.hostnames
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        self.ranges.iter().any(|range| range.contains(ip))
+    }
+
+    /// Number of addresses, not counting the hostnames that are still to be resolved.
+    pub fn len(&self) -> u64 {
+        self.ranges.iter().map(IpRange::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0 && self.hostnames.is_empty()
+    }
+
+    /// The targets with every address of `excluded` removed.
+    pub fn without(&self, excluded: &Targets) -> Targets {
+        let mut ranges = Vec::new();
+        for range in &self.ranges {
+            let mut start = range.start;
+            // Both lists are sorted and merged, so one pass over the exclusions is enough
+            for skip in &excluded.ranges {
+                if skip.end <= start || skip.start >= range.end {
+                    continue;
+                }
+                if skip.start > start {
+                    ranges.push(IpRange::new(start, skip.start));
+                }
+                start = skip.end;
+            }
+            if start < range.end {
+                ranges.push(IpRange::new(start, range.end));
+            }
+        }
+        Self {
+            hostnames: self.hostnames.clone(),
+            ..Self::new(ranges)
+        }
+    }
+
+    /// The addresses in both `self` and `other`, keeping the hostnames of `self`.
+    pub fn intersect(&self, other: &Targets) -> Targets {
+        self.without(&self.without(other))
+    }
+}
+
+impl FromIterator<Targets> for Targets {
+    fn from_iter<T: IntoIterator<Item = Targets>>(iter: T) -> Self {
+        let mut ranges = Vec::new();
+        let mut hostnames = Vec::new();
+        for targets in iter {
+            ranges.extend(targets.ranges);
+            hostnames.extend(targets.hostnames);
+        }
+        Self {
+            hostnames,
+            ..Self::new(ranges)
+        }
+    }
+}
+
+impl From<IpRange> for Targets {
+    fn from(range: IpRange) -> Self {
+        Self::new(vec![range])
+    }
+}
+
+impl Display for Targets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ranges = self.ranges.iter().map(|range| range as &dyn Display);
+        let hostnames = self.hostnames.iter().map(|host| host as &dyn Display);
+        for (idx, target) in ranges.chain(hostnames).enumerate() {
+            if idx > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", target)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Targets {
+    type Err = ParseTargetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ranges = Vec::new();
+        let mut hostnames = Vec::new();
+        for target in s
+            .split(',')
+            .map(str::trim)
+            .filter(|target| !target.is_empty())
+        {
+            if target.contains("..") {
+                ranges.push(target.parse()?);
+            } else if target.contains('/') {
+                ranges.push(target.parse::<Subnet>()?.hosts());
+            } else if let Ok(ip) = target.parse::<Ipv4Addr>() {
+                ranges.push(IpRange::from(ip));
+            } else if is_hostname(target) {
+                hostnames.push(target.to_string());
+            } else {
+                return Err(ParseTargetError(format!(
+                    "'{}' is not a range (a..b), subnet (a/24), ipv4 address or hostname",
+                    target
+                )));
+            }
+        }
+        if ranges.is_empty() && hostnames.is_empty() {
+            return Err(ParseTargetError("No targets given".into()));
+        }
+        Ok(Self {
+            hostnames,
+            ..Self::new(ranges)
+        })
+    }
+}
+
+/// Whether `s` is a valid DNS name. All numeric names are rejected so a mistyped address like
+/// `192.168.1` is reported instead of being looked up.
+fn is_hostname(s: &str) -> bool {
+    s.len() <= 253
+        && !s.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && s.trim_end_matches('.').split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+impl IntoIterator for Targets {
+    type Item = Ipv4Addr;
+
+    type IntoIter = std::iter::Flatten<std::vec::IntoIter<IpRange>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ranges.into_iter().flatten()
+    }
+}
@@ -0,0 +1,506 @@
+//! How requests reach gateways, so probes and management actions can run against the network,
+//! a recorded [`Cassette`](crate::cassette::Cassette) or programmed in-memory responses alike.
+
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::{Method, StatusCode};
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use url::Url;
+
+use crate::error::{CodedError, ErrorCode, TransportError};
+
+/// A request to a gateway, kept as plain data so it can be recorded and replayed.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: Url,
+    pub basic_auth: Option<(String, String)>,
+    pub json: Option<Value>,
+}
+
+impl HttpRequest {
+    pub fn get(url: Url) -> Self {
+        Self {
+            method: Method::GET,
+            url,
+            basic_auth: None,
+            json: None,
+        }
+    }
+
+    pub fn post(url: Url, json: Value) -> Self {
+        Self {
+            method: Method::POST,
+            url,
+            basic_auth: None,
+            json: Some(json),
+        }
+    }
+
+    pub fn basic_auth(self, username: &str, password: &str) -> Self {
+        Self {
+            basic_auth: Some((username.to_string(), password.to_string())),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl HttpResponse {
+    /// Fails with [`ErrorCode::Auth`] or [`ErrorCode::HttpStatus`] unless the status is 2xx.
+    pub fn error_for_status(self) -> Result<Self, CodedError> {
+        if self.status.is_success() {
+            Ok(self)
+        } else {
+            Err(CodedError::new(
+                ErrorCode::for_status(self.status),
+                format!("Unexpected http status {}", self.status),
+            ))
+        }
+    }
+
+    pub fn json(&self) -> serde_json::Result<Value> {
+        serde_json::from_str(&self.body)
+    }
+}
+
+pub trait Transport: Send + Sync {
+    /// Checks that something accepts connections on `host:port`.
+    fn connect<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+        timeout: Duration,
+    ) -> BoxFuture<'a, Result<(), TransportError>>;
+
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>>;
+}
+
+/// Local ports for outgoing connections, `start-end` inclusive or a single port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn count(&self) -> u32 {
+        u32::from(self.end) - u32::from(self.start) + 1
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |port: &str| {
+            port.trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|port| *port > 0)
+                .ok_or_else(|| format!("invalid port '{}'", port))
+        };
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => (parse(s)?, parse(s)?),
+        };
+        if start > end {
+            return Err(format!("'{}' is not like 40000-40999", s));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// Options for the sockets of tcp probes, so scan traffic can be classified by QoS policies and
+/// picked out of captures.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// Local ports to connect from, used in turn
+    pub source_ports: Option<PortRange>,
+    /// Differentiated services code point (0-63) marked in the ip header
+    pub dscp: Option<u8>,
+    /// Drop connections whose data stays unacknowledged this long (linux only)
+    pub tcp_user_timeout: Option<Duration>,
+    /// Local address to connect ipv4 peers from, selecting the interface of that address
+    pub source_ip: Option<Ipv4Addr>,
+    /// Proxy to tunnel connections through, the other options are not applied when set
+    pub proxy: Option<Socks5Proxy>,
+}
+
+impl SocketOptions {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A SOCKS5 proxy without authentication, `socks5://host[:port]`, for reaching gateways through
+/// a bastion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Socks5Proxy {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Socks5Proxy {
+    pub const DEFAULT_PORT: u16 = 1080;
+
+    /// The proxy url for reqwest, letting the proxy resolve hostnames since gateway names often
+    /// only resolve behind it.
+    pub fn url(&self) -> String {
+        format!("socks5h://{}:{}", self.host, self.port)
+    }
+
+    /// Opens a connection to `host:port` through the proxy.
+    pub async fn connect(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> Result<tokio::net::TcpStream, TransportError> {
+        let mut stream = tokio::net::TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .map_err(|err| TransportError::Connect {
+                message: format!(
+                    "Error connecting to proxy {}:{}: {}",
+                    self.host, self.port, err
+                ),
+            })?;
+        // Version 5 offering a single method, no authentication
+        stream.write_all(&[5, 1, 0]).await?;
+        let mut method = [0; 2];
+        stream.read_exact(&mut method).await?;
+        if method != [5, 0] {
+            return Err(TransportError::Connect {
+                message: format!(
+                    "Proxy {}:{} does not accept connections without authentication",
+                    self.host, self.port
+                ),
+            });
+        }
+
+        // CONNECT to an ipv4 address, or a domain name for the proxy to resolve
+        let mut request = vec![5, 1, 0];
+        match host.parse::<Ipv4Addr>() {
+            Ok(ip) => {
+                request.push(1);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                let name = u8::try_from(host.len()).map_err(|_| TransportError::Connect {
+                    message: format!("Hostname {} is too long for SOCKS5", host),
+                })?;
+                request.push(3);
+                request.push(name);
+                request.extend_from_slice(host.as_bytes());
+            }
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0 {
+            let reason = match reply[1] {
+                2 => "not allowed by the proxy's rules",
+                3 => "network unreachable",
+                4 => "host unreachable",
+                5 => "connection refused",
+                6 => "ttl expired",
+                _ => "proxy failure",
+            };
+            if reply[1] == 5 {
+                return Err(TransportError::ConnectionRefused);
+            }
+            return Err(TransportError::Connect {
+                message: format!(
+                    "Error connecting to {}:{} through the proxy: {}",
+                    host, port, reason
+                ),
+            });
+        }
+        // Skip the address the proxy bound for the connection
+        let len = match reply[3] {
+            1 => 4,
+            4 => 16,
+            3 => usize::from(stream.read_u8().await?),
+            kind => {
+                return Err(TransportError::Connect {
+                    message: format!("Invalid SOCKS5 address type {}", kind),
+                })
+            }
+        };
+        let mut bound = vec![0; len + 2];
+        stream.read_exact(&mut bound).await?;
+        Ok(stream)
+    }
+}
+
+impl FromStr for Socks5Proxy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let address = s
+            .strip_prefix("socks5://")
+            .or_else(|| s.strip_prefix("socks5h://"))
+            .unwrap_or(s);
+        let (host, port) = split_host_port(address)?;
+        if host.is_empty() || address.contains('/') {
+            return Err(format!("'{}' is not like socks5://bastion:1080", s));
+        }
+        Ok(Self {
+            host,
+            port: port.unwrap_or(Self::DEFAULT_PORT),
+        })
+    }
+}
+
+/// Sends requests over the network.
+#[derive(Debug)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+    sockets: SocketOptions,
+    /// Index of the next port of `sockets.source_ports` to try
+    next_port: AtomicU32,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self::with_socket_options(client, SocketOptions::default())
+    }
+
+    pub fn with_socket_options(client: reqwest::Client, sockets: SocketOptions) -> Self {
+        Self {
+            client,
+            sockets,
+            next_port: AtomicU32::new(0),
+        }
+    }
+
+    /// Opens a connection to `addr` with the configured socket options, trying each source port
+    /// once until one is free.
+    async fn connect_with_options(&self, addr: SocketAddr) -> std::io::Result<()> {
+        let attempts = self.sockets.source_ports.map_or(1, |ports| ports.count());
+        let mut last_error = None;
+        for _ in 0..attempts {
+            let socket = match addr {
+                SocketAddr::V4(_) => tokio::net::TcpSocket::new_v4()?,
+                SocketAddr::V6(_) => tokio::net::TcpSocket::new_v6()?,
+            };
+            let sock_ref = socket2::SockRef::from(&socket);
+            if let Some(dscp) = self.sockets.dscp {
+                sock_ref.set_tos(u32::from(dscp) << 2)?;
+            }
+            if let Some(timeout) = self.sockets.tcp_user_timeout {
+                set_tcp_user_timeout(&sock_ref, timeout)?;
+            }
+            let port = match self.sockets.source_ports {
+                Some(ports) => {
+                    let offset = self.next_port.fetch_add(1, Ordering::Relaxed) % ports.count();
+                    // Ports in TIME_WAIT from earlier probes can still be bound for new peers
+                    socket.set_reuseaddr(true)?;
+                    Some((u32::from(ports.start) + offset) as u16)
+                }
+                None => None,
+            };
+            if port.is_some() || self.sockets.source_ip.is_some() {
+                let port = port.unwrap_or(0);
+                let local = match addr {
+                    SocketAddr::V4(_) => SocketAddr::from((
+                        self.sockets.source_ip.unwrap_or(Ipv4Addr::UNSPECIFIED),
+                        port,
+                    )),
+                    SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)),
+                };
+                if let Err(err) = socket.bind(local) {
+                    if err.kind() == std::io::ErrorKind::AddrInUse {
+                        last_error = Some(err);
+                        continue;
+                    }
+                    return Err(err);
+                }
+            }
+            match socket.connect(addr).await {
+                Err(err) if err.kind() == std::io::ErrorKind::AddrNotAvailable => {
+                    last_error = Some(err);
+                }
+                result => return result.map(drop),
+            }
+        }
+        Err(last_error.expect("Every attempt must fail with an error"))
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn set_tcp_user_timeout(socket: &socket2::SockRef, timeout: Duration) -> std::io::Result<()> {
+    socket.set_tcp_user_timeout(Some(timeout))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn set_tcp_user_timeout(_socket: &socket2::SockRef, _timeout: Duration) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "TCP user timeout is only supported on linux",
+    ))
+}
+
+impl Transport for ReqwestTransport {
+    fn connect<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+        timeout: Duration,
+    ) -> BoxFuture<'a, Result<(), TransportError>> {
+        async move {
+            if let Some(proxy) = &self.sockets.proxy {
+                tokio::time::timeout(timeout, proxy.connect(host, port)).await??;
+                return Ok(());
+            }
+            if self.sockets.is_default() {
+                tokio::time::timeout(timeout, tokio::net::TcpStream::connect((host, port)))
+                    .await??;
+                return Ok(());
+            }
+            let addr = tokio::net::lookup_host((host, port))
+                .await?
+                .next()
+                .ok_or_else(|| TransportError::Connect {
+                    message: format!("No address found for {}", host),
+                })?;
+            tokio::time::timeout(timeout, self.connect_with_options(addr)).await??;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+        async move {
+            let mut builder = self.client.request(request.method, request.url);
+            if let Some((username, password)) = &request.basic_auth {
+                builder = builder.basic_auth(username, Some(password));
+            }
+            if let Some(json) = &request.json {
+                builder = builder.json(json);
+            }
+            let response = builder.send().await?;
+            let status = response.status();
+            Ok(HttpResponse {
+                status,
+                body: response.text().await?,
+            })
+        }
+        .boxed()
+    }
+}
+
+/// One `--remap-endpoint` rule, `host[:port]=host[:port]`, for pointing requests meant for a
+/// gateway at a local test server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remap {
+    pub from_host: String,
+    /// Every port when unset
+    pub from_port: Option<u16>,
+    pub to_host: String,
+    /// The original port when unset
+    pub to_port: Option<u16>,
+}
+
+impl Remap {
+    fn apply(&self, host: &str, port: u16) -> Option<(&str, u16)> {
+        (self.from_host == host && self.from_port.is_none_or(|p| p == port))
+            .then(|| (self.to_host.as_str(), self.to_port.unwrap_or(port)))
+    }
+}
+
+fn split_host_port(s: &str) -> Result<(String, Option<u16>), String> {
+    match s.rsplit_once(':') {
+        Some((host, port)) => Ok((
+            host.to_string(),
+            Some(
+                port.parse()
+                    .map_err(|_| format!("invalid port '{}'", port))?,
+            ),
+        )),
+        None => Ok((s.to_string(), None)),
+    }
+}
+
+impl FromStr for Remap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' is not like 10.0.0.5:80=127.0.0.1:18080", s))?;
+        let (from_host, from_port) = split_host_port(from)?;
+        let (to_host, to_port) = split_host_port(to)?;
+        Ok(Self {
+            from_host,
+            from_port,
+            to_host,
+            to_port,
+        })
+    }
+}
+
+/// Sends requests to the address of the first matching [`Remap`] rule instead of their own.
+pub struct RemappingTransport {
+    inner: Box<dyn Transport>,
+    rules: Vec<Remap>,
+}
+
+impl RemappingTransport {
+    pub fn new(inner: Box<dyn Transport>, rules: Vec<Remap>) -> Self {
+        Self { inner, rules }
+    }
+
+    fn remap(&self, host: &str, port: u16) -> Option<(&str, u16)> {
+        self.rules.iter().find_map(|rule| rule.apply(host, port))
+    }
+}
+
+impl Transport for RemappingTransport {
+    fn connect<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+        timeout: Duration,
+    ) -> BoxFuture<'a, Result<(), TransportError>> {
+        let (host, port) = self.remap(host, port).unwrap_or((host, port));
+        self.inner.connect(host, port, timeout)
+    }
+
+    fn send(
+        &self,
+        mut request: HttpRequest,
+    ) -> BoxFuture<'_, Result<HttpResponse, TransportError>> {
+        let original = request
+            .url
+            .host_str()
+            .zip(request.url.port_or_known_default());
+        if let Some((host, port)) = original.and_then(|(host, port)| self.remap(host, port)) {
+            log::trace!("Remapping {} to {}:{}", request.url, host, port);
+            if let Err(err) = request.url.set_host(Some(host)) {
+                return futures::future::ready(Err(TransportError::Connect {
+                    message: format!("Invalid remapped host {}: {}", host, err),
+                }))
+                .boxed();
+            }
+            request
+                .url
+                .set_port(Some(port))
+                .expect("Urls with a host must accept a port");
+        }
+        self.inner.send(request)
+    }
+}
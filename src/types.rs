@@ -2,14 +2,83 @@ use std::{fmt::Display, net::Ipv4Addr, str::FromStr};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct Mac {
     pub bytes: [u8; 6],
 }
 
+impl Mac {
+    /// Generates a random locally administered, unicast MAC address.
+    pub fn random() -> Self {
+        let mut bytes: [u8; 6] = rand::random();
+        bytes[0] = (bytes[0] | 0b10) & !0b01;
+        Self { bytes }
+    }
+
+    /// Generates a random MAC address within the given vendor prefix (OUI).
+    pub fn from_oui(oui: [u8; 3]) -> Self {
+        let suffix: [u8; 3] = rand::random();
+        Self {
+            bytes: [oui[0], oui[1], oui[2], suffix[0], suffix[1], suffix[2]],
+        }
+    }
+
+    /// Iterates over sequential MAC addresses starting at (and including) this one.
+    pub fn sequence(self) -> MacSequence {
+        MacSequence {
+            next: Some(u64::from(self)),
+        }
+    }
+
+    /// Builds a mac from the lower 48 bits of `value`.
+    fn from_u48(value: u64) -> Self {
+        let mut bytes = [0u8; 6];
+        bytes.copy_from_slice(&value.to_be_bytes()[2..]);
+        Self { bytes }
+    }
+}
+
+impl From<[u8; 6]> for Mac {
+    fn from(bytes: [u8; 6]) -> Self {
+        Self { bytes }
+    }
+}
+
+impl From<Mac> for [u8; 6] {
+    fn from(mac: Mac) -> Self {
+        mac.bytes
+    }
+}
+
+/// The mac as a 48 bit big endian integer, e.g. `00:00:00:00:01:02` is `0x0102`.
+impl From<Mac> for u64 {
+    fn from(mac: Mac) -> Self {
+        let mut padded = [0u8; 8];
+        padded[2..].copy_from_slice(&mac.bytes);
+        u64::from_be_bytes(padded)
+    }
+}
+
+/// Iterator over sequential MAC addresses, see [`Mac::sequence`].
+/// Ends after `FF:FF:FF:FF:FF:FF`.
+#[derive(Debug, Clone)]
+pub struct MacSequence {
+    next: Option<u64>,
+}
+
+impl Iterator for MacSequence {
+    type Item = Mac;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next?;
+        self.next = Some(current + 1).filter(|next| *next < 1 << 48);
+        Some(Mac::from_u48(current))
+    }
+}
+
 impl Display for Mac {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let encoded = hex::encode_upper(&self.bytes);
+        let encoded = hex::encode_upper(self.bytes);
         let mut result = String::with_capacity(3 * 6);
 
         for (idx, char) in encoded.chars().enumerate() {
@@ -27,10 +96,7 @@ impl FromStr for Mac {
     type Err = hex::FromHexError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let no_colons = s.replace(':', "");
-        let mut slice = [0u8; 6];
-        hex::decode_to_slice(&no_colons, &mut slice)?;
-        Ok(Self { bytes: slice })
+        crate::parse::mac(s)
     }
 }
 
@@ -56,20 +122,240 @@ impl<'de> Deserialize<'de> for Mac {
     where
         D: serde::Deserializer<'de>,
     {
-        let deserialized: &str = <&str>::deserialize(deserializer)?;
-        Self::from_str(deserialized).map_err(serde::de::Error::custom)
+        let deserialized = String::deserialize(deserializer)?;
+        Self::from_str(&deserialized).map_err(serde::de::Error::custom)
     }
 }
 
-#[derive(Debug, Serialize)]
+/// Serialized as `"G1"` / `"MG3"`, these names are part of the output format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GatewayType {
+    #[serde(rename = "G1")]
     G1,
+    #[serde(rename = "MG3")]
     MG3,
 }
 
-#[derive(Debug, Serialize)]
+impl GatewayType {
+    /// The name used in reports, `G1` or `MG3`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GatewayType::G1 => "G1",
+            GatewayType::MG3 => "MG3",
+        }
+    }
+}
+
+impl FromStr for GatewayType {
+    type Err = String;
+
+    /// Parses `G1` or `MG3`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "G1" => Ok(GatewayType::G1),
+            "MG3" => Ok(GatewayType::MG3),
+            _ => Err(format!("'{}' is not a gateway type, G1 or MG3", s)),
+        }
+    }
+}
+
+/// A gateway found by a scan.
+///
+/// Serialized fields keep their declaration order and snake_case names. New fields
+/// must be appended at the end so existing consumers keep working.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub struct GatewayDetection {
     pub ip: Ipv4Addr,
     pub gateway: GatewayType,
     pub mac: Mac,
+    /// Local interface the gateway was found through, with `--all-vlans` or `--vlan`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>,
+    /// VLAN id of that interface
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vlan: Option<u16>,
+    /// Name from the PTR record of the ip, with `--rdns`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Http port the gateway answered on, when scanning several with `--ports`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// Milliseconds the detection request took to answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt_ms: Option<f64>,
+    /// Firmware version the gateway reported during detection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub firmware: Option<String>,
+    /// Hardware model the gateway reported during detection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Manufacturer of the mac's OUI, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor: Option<String>,
+    /// Only known from the gateway's discovery announcement, not confirmed over http
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unconfirmed: bool,
+}
+
+impl GatewayDetection {
+    /// A detection with only the required fields set.
+    pub fn new(ip: Ipv4Addr, gateway: GatewayType, mac: Mac) -> Self {
+        Self {
+            ip,
+            gateway,
+            mac,
+            interface: None,
+            vlan: None,
+            hostname: None,
+            port: None,
+            rtt_ms: None,
+            firmware: None,
+            model: None,
+            vendor: None,
+            unconfirmed: false,
+        }
+    }
+}
+
+/// Error returned when a value is outside the range allowed for its type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutOfRangeError {
+    pub kind: &'static str,
+    pub value: i16,
+    pub min: i16,
+    pub max: i16,
+}
+
+impl Display for OutOfRangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} out of range (expected {}..={})",
+            self.kind, self.value, self.min, self.max
+        )
+    }
+}
+
+impl std::error::Error for OutOfRangeError {}
+
+fn check_range(kind: &'static str, value: i16, min: i16, max: i16) -> Result<(), OutOfRangeError> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(OutOfRangeError {
+            kind,
+            value,
+            min,
+            max,
+        })
+    }
+}
+
+/// Received signal strength in dBm (-127..=20, as reported by BLE controllers).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "i8", into = "i8")]
+pub struct Rssi(i8);
+
+impl Rssi {
+    pub const MIN: i8 = -127;
+    pub const MAX: i8 = 20;
+
+    pub fn dbm(self) -> i8 {
+        self.0
+    }
+}
+
+impl TryFrom<i8> for Rssi {
+    type Error = OutOfRangeError;
+
+    fn try_from(value: i8) -> Result<Self, Self::Error> {
+        check_range("rssi", value.into(), Self::MIN.into(), Self::MAX.into())?;
+        Ok(Self(value))
+    }
+}
+
+impl From<Rssi> for i8 {
+    fn from(rssi: Rssi) -> Self {
+        rssi.0
+    }
+}
+
+impl Display for Rssi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} dBm", self.0)
+    }
+}
+
+/// Transmit power in dBm (-127..=20, the range of the BLE Tx Power Level field).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "i8", into = "i8")]
+pub struct TxPower(i8);
+
+impl TxPower {
+    pub const MIN: i8 = -127;
+    pub const MAX: i8 = 20;
+
+    pub fn dbm(self) -> i8 {
+        self.0
+    }
+}
+
+impl TryFrom<i8> for TxPower {
+    type Error = OutOfRangeError;
+
+    fn try_from(value: i8) -> Result<Self, Self::Error> {
+        check_range("tx power", value.into(), Self::MIN.into(), Self::MAX.into())?;
+        Ok(Self(value))
+    }
+}
+
+impl From<TxPower> for i8 {
+    fn from(power: TxPower) -> Self {
+        power.0
+    }
+}
+
+impl Display for TxPower {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} dBm", self.0)
+    }
+}
+
+/// BLE radio channel index (0..=39). Channels 37, 38 and 39 are advertising channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct BleChannel(u8);
+
+impl BleChannel {
+    pub const MAX: u8 = 39;
+
+    pub fn index(self) -> u8 {
+        self.0
+    }
+
+    pub fn is_advertising(self) -> bool {
+        self.0 >= 37
+    }
+}
+
+impl TryFrom<u8> for BleChannel {
+    type Error = OutOfRangeError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        check_range("ble channel", value.into(), 0, Self::MAX.into())?;
+        Ok(Self(value))
+    }
+}
+
+impl From<BleChannel> for u8 {
+    fn from(channel: BleChannel) -> Self {
+        channel.0
+    }
+}
+
+impl Display for BleChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
@@ -0,0 +1,144 @@
+use std::{fmt::Display, ops::RangeInclusive};
+
+use regex::Regex;
+
+/// A single failed constraint, identified by the dotted path of the field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// All constraint failures found while validating a value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationErrors(pub Vec<FieldError>);
+
+impl Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, error) in self.0.iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+/// Types that can check their own constraints.
+pub trait Validate {
+    fn validate_fields(&self, v: &mut Validator);
+
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut validator = Validator::default();
+        self.validate_fields(&mut validator);
+        validator.finish()
+    }
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate_fields(&self, v: &mut Validator) {
+        if let Some(value) = self {
+            value.validate_fields(v);
+        }
+    }
+}
+
+/// Collects field errors while walking a structure, keeping track of the current field path.
+#[derive(Debug, Default)]
+pub struct Validator {
+    path: Vec<String>,
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    fn field_path(&self, field: &str) -> String {
+        self.path
+            .iter()
+            .map(String::as_str)
+            .chain(std::iter::once(field))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    pub fn error(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: self.field_path(field),
+            message: message.into(),
+        });
+    }
+
+    /// Validates `value` with its fields reported under `field`.
+    pub fn nested(&mut self, field: &str, value: &impl Validate) {
+        self.path.push(field.to_string());
+        value.validate_fields(self);
+        self.path.pop();
+    }
+
+    pub fn range<T>(&mut self, field: &str, value: Option<T>, range: RangeInclusive<T>)
+    where
+        T: PartialOrd + Display,
+    {
+        if let Some(value) = value {
+            if !range.contains(&value) {
+                self.error(
+                    field,
+                    format!(
+                        "{} is out of range (expected {}..={})",
+                        value,
+                        range.start(),
+                        range.end()
+                    ),
+                );
+            }
+        }
+    }
+
+    pub fn matches(&mut self, field: &str, value: Option<&str>, regex: &Regex, expected: &str) {
+        if let Some(value) = value {
+            if !regex.is_match(value) {
+                self.error(field, format!("'{}' is not {}", value, expected));
+            }
+        }
+    }
+
+    pub fn required<T>(&mut self, field: &str, value: Option<&T>) {
+        if value.is_none() {
+            self.error(field, "is required");
+        }
+    }
+
+    /// Requires that either all or none of the given fields are set.
+    pub fn together(&mut self, fields: &[(&str, bool)]) {
+        let set = fields.iter().filter(|(_, is_set)| *is_set).count();
+        if set == fields.len() || set == 0 {
+            return;
+        }
+        let names = fields
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        for (name, is_set) in fields {
+            if !is_set {
+                self.error(name, format!("is required when any of {} is set", names));
+            }
+        }
+    }
+
+    pub fn finish(self) -> Result<(), ValidationErrors> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(self.errors))
+        }
+    }
+}
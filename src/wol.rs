@@ -0,0 +1,35 @@
+//! Wake-on-LAN magic packets for gateways that power down their NIC.
+
+use std::net::{Ipv4Addr, SocketAddr};
+
+use anyhow::Context;
+
+use crate::types::Mac;
+
+/// Port magic packets are usually sent to ("discard").
+pub const PORT: u16 = 9;
+
+/// Six `0xFF` bytes followed by the mac repeated 16 times.
+pub fn magic_packet(mac: Mac) -> [u8; 102] {
+    let mut packet = [0xFF; 102];
+    for chunk in packet[6..].chunks_exact_mut(6) {
+        chunk.copy_from_slice(&mac.bytes);
+    }
+    packet
+}
+
+/// Broadcasts a magic packet for `mac` to `broadcast:port`, e.g. `10.0.3.255:9` for a gateway in
+/// `10.0.3.0/24`.
+pub async fn wake(mac: Mac, broadcast: Ipv4Addr, port: u16) -> anyhow::Result<()> {
+    let socket = tokio::net::UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)))
+        .await
+        .context("Error opening udp socket")?;
+    socket
+        .set_broadcast(true)
+        .context("Error enabling broadcast")?;
+    socket
+        .send_to(&magic_packet(mac), SocketAddr::from((broadcast, port)))
+        .await
+        .with_context(|| format!("Error sending magic packet to {}:{}", broadcast, port))?;
+    Ok(())
+}